@@ -0,0 +1,637 @@
+use std::io::{BufRead, BufReader, Read};
+use std::ops::Range;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::thread;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, State};
+use uuid::Uuid;
+
+use crate::cache::ProbeCache;
+use crate::error::{AppError, AppResult};
+use crate::probe::VideoInfo;
+use crate::timeline::compact_field;
+use crate::{is_cancelled, resolve_bundled_binary, stderr_tail, tool_command, ExportManager};
+
+const DEFAULT_BUCKET_SEC: f64 = 1.0;
+/// Shorter buckets hold less than a frame at common frame rates.
+const MIN_BUCKET_SEC: f64 = 0.01;
+/// Upper bound on the length of a bitrate series, which keeps its `kbps` array
+/// under a megabyte of JSON; at the default bucket that is over 27 hours.
+/// Frames timestamped past it, e.g. after a broken timestamp jump, are left
+/// out of the series.
+const MAX_BUCKETS: usize = 100_000;
+const DEFAULT_CHUNK_FRAMES: usize = 5000;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct FrameAnalysisParams {
+    /// Analyzed one after another; events carry the index into this list.
+    input_paths: Vec<String>,
+    /// Width of the bitrate buckets in seconds, at least 0.01. Defaults to 1.
+    bucket_sec: Option<f64>,
+    /// Frames per `frame-analysis-chunk` event. Defaults to 5000.
+    chunk_frames: Option<usize>,
+}
+
+#[derive(Serialize)]
+pub(crate) struct FrameAnalysisStarted {
+    analysis_id: String,
+}
+
+/// A run of consecutive frames in display order, as parallel arrays so large
+/// files stay small on the wire and map directly onto typed arrays.
+#[derive(Serialize, Clone)]
+struct FrameChunk {
+    analysis_id: String,
+    input_index: usize,
+    /// Index of the chunk's first frame within the input.
+    offset: u64,
+    /// One picture type per frame: `I`, `P`, `B`, or `?` when unknown.
+    types: String,
+    /// Positions of keyframes within this chunk.
+    keyframes: Vec<u32>,
+    /// Packet size of each frame in bytes.
+    sizes: Vec<u32>,
+    /// Presentation timestamps in seconds as stored in the file.
+    pts: Vec<Option<f64>>,
+    /// Share of the input analyzed so far, when its duration is known.
+    percent: Option<f64>,
+}
+
+#[derive(Serialize, Clone)]
+struct BitrateSeries {
+    bucket_sec: f64,
+    /// Timestamp the first bucket starts at.
+    start_sec: f64,
+    /// Average bitrate of each bucket. The last bucket is usually partly
+    /// filled and reads low.
+    kbps: Vec<f64>,
+}
+
+#[derive(Serialize, Clone)]
+struct InputFrameSummary {
+    input_index: usize,
+    path: String,
+    frame_count: u64,
+    i_frames: u64,
+    p_frames: u64,
+    b_frames: u64,
+    keyframes: u64,
+    /// Frames from one keyframe to the next, averaged and at most.
+    average_gop: Option<f64>,
+    max_gop: Option<u64>,
+    bitrate: BitrateSeries,
+    peak_kbps: Option<f64>,
+}
+
+/// Sent as `frame-analysis-complete` once every input is done, or when the
+/// analysis stops early.
+#[derive(Serialize, Clone)]
+struct FrameAnalysisResult {
+    analysis_id: String,
+    inputs: Vec<InputFrameSummary>,
+    cancelled: bool,
+    message: Option<String>,
+}
+
+/// Running totals for one input while its frames stream in.
+struct InputAccumulator {
+    summary: InputFrameSummary,
+    last_keyframe: Option<u64>,
+    gops: Vec<u64>,
+    bucket_bytes: Vec<u64>,
+}
+
+impl InputAccumulator {
+    fn new(input_index: usize, path: &str, bucket_sec: f64) -> Self {
+        Self {
+            summary: InputFrameSummary {
+                input_index,
+                path: path.to_string(),
+                frame_count: 0,
+                i_frames: 0,
+                p_frames: 0,
+                b_frames: 0,
+                keyframes: 0,
+                average_gop: None,
+                max_gop: None,
+                bitrate: BitrateSeries {
+                    bucket_sec,
+                    start_sec: 0.0,
+                    kbps: Vec::new(),
+                },
+                peak_kbps: None,
+            },
+            last_keyframe: None,
+            gops: Vec::new(),
+            bucket_bytes: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, frame: &FrameLine) {
+        let index = self.summary.frame_count;
+        self.summary.frame_count += 1;
+        match frame.picture_type {
+            'I' => self.summary.i_frames += 1,
+            'P' => self.summary.p_frames += 1,
+            'B' => self.summary.b_frames += 1,
+            _ => {}
+        }
+        if frame.keyframe {
+            self.summary.keyframes += 1;
+            if let Some(last) = self.last_keyframe {
+                self.gops.push(index - last);
+            }
+            self.last_keyframe = Some(index);
+        }
+        if let Some(pts) = frame.pts {
+            let bitrate = &mut self.summary.bitrate;
+            if self.bucket_bytes.is_empty() {
+                bitrate.start_sec = pts;
+            }
+            let bucket = ((pts - bitrate.start_sec) / bitrate.bucket_sec).max(0.0) as usize;
+            if bucket >= MAX_BUCKETS {
+                return;
+            }
+            if bucket >= self.bucket_bytes.len() {
+                self.bucket_bytes.resize(bucket + 1, 0);
+            }
+            self.bucket_bytes[bucket] += u64::from(frame.size);
+        }
+    }
+
+    fn finish(mut self) -> InputFrameSummary {
+        // The last GOP runs to the end of the stream.
+        if let Some(last) = self.last_keyframe {
+            self.gops.push(self.summary.frame_count - last);
+        }
+        if !self.gops.is_empty() {
+            let total: u64 = self.gops.iter().sum();
+            self.summary.average_gop = Some(total as f64 / self.gops.len() as f64);
+            self.summary.max_gop = self.gops.iter().copied().max();
+        }
+        let bucket_sec = self.summary.bitrate.bucket_sec;
+        self.summary.bitrate.kbps = self
+            .bucket_bytes
+            .iter()
+            .map(|bytes| *bytes as f64 * 8.0 / 1000.0 / bucket_sec)
+            .collect();
+        self.summary.peak_kbps = self.summary.bitrate.kbps.iter().copied().reduce(f64::max);
+        self.summary
+    }
+}
+
+struct FrameLine {
+    picture_type: char,
+    keyframe: bool,
+    size: u32,
+    pts: Option<f64>,
+}
+
+fn parse_frame_line(line: &str) -> Option<FrameLine> {
+    if !line.starts_with("frame|") {
+        return None;
+    }
+    let seconds = |key: &str| {
+        compact_field(line, key)
+            .and_then(|value| value.parse::<f64>().ok())
+            .filter(|value| value.is_finite())
+    };
+    let picture_type = compact_field(line, "pict_type")
+        .and_then(|value| value.chars().next())
+        .filter(|value| matches!(value, 'I' | 'P' | 'B'))
+        .unwrap_or('?');
+    Some(FrameLine {
+        picture_type,
+        keyframe: compact_field(line, "key_frame") == Some("1"),
+        size: compact_field(line, "pkt_size")
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(0),
+        pts: seconds("best_effort_timestamp_time").or_else(|| seconds("pkt_dts_time")),
+    })
+}
+
+/// Every frame of one input, in the layout of `FrameChunk`. Kept in the probe
+/// cache, so analyzing an unchanged file again only replays it.
+#[derive(Serialize, Deserialize, Default)]
+pub(crate) struct AnalyzedFrames {
+    types: String,
+    /// Indices of keyframes within the input.
+    keyframes: Vec<u64>,
+    sizes: Vec<u32>,
+    pts: Vec<Option<f64>>,
+}
+
+impl AnalyzedFrames {
+    fn len(&self) -> usize {
+        self.sizes.len()
+    }
+
+    /// Whether the parallel arrays line up, as `chunk` relies on.
+    fn is_consistent(&self) -> bool {
+        self.types.is_ascii()
+            && self.types.len() == self.len()
+            && self.pts.len() == self.len()
+            && self
+                .keyframes
+                .iter()
+                .all(|index| *index < self.len() as u64)
+    }
+
+    fn push(&mut self, frame: &FrameLine) {
+        if frame.keyframe {
+            self.keyframes.push(self.len() as u64);
+        }
+        self.types.push(frame.picture_type);
+        self.sizes.push(frame.size);
+        self.pts.push(frame.pts);
+    }
+
+    fn frames(&self) -> impl Iterator<Item = FrameLine> + '_ {
+        let mut keyframes = self.keyframes.iter().peekable();
+        self.types
+            .chars()
+            .zip(&self.sizes)
+            .zip(&self.pts)
+            .enumerate()
+            .map(move |(index, ((picture_type, size), pts))| FrameLine {
+                picture_type,
+                keyframe: keyframes.next_if_eq(&&(index as u64)).is_some(),
+                size: *size,
+                pts: *pts,
+            })
+    }
+
+    /// The frames in `range` as a chunk event.
+    fn chunk(&self, analysis_id: &str, input_index: usize, range: Range<usize>) -> FrameChunk {
+        FrameChunk {
+            analysis_id: analysis_id.to_string(),
+            input_index,
+            offset: range.start as u64,
+            // Picture types are ASCII, so frame indices are byte offsets.
+            types: self.types[range.clone()].to_string(),
+            keyframes: self
+                .keyframes
+                .iter()
+                .filter(|index| range.contains(&(**index as usize)))
+                .map(|index| (*index as usize - range.start) as u32)
+                .collect(),
+            sizes: self.sizes[range.clone()].to_vec(),
+            pts: self.pts[range].to_vec(),
+            percent: None,
+        }
+    }
+}
+
+/// What every input of one `analyze_frames` call shares.
+struct AnalysisRun {
+    app: AppHandle,
+    export_manager: ExportManager,
+    ffprobe: PathBuf,
+    analysis_id: String,
+    bucket_sec: f64,
+    chunk_frames: usize,
+}
+
+impl AnalysisRun {
+    /// Sends the frames in `range` as one chunk event.
+    fn emit_chunk(
+        &self,
+        frames: &AnalyzedFrames,
+        range: Range<usize>,
+        input_index: usize,
+        info: &VideoInfo,
+        totals: &InputAccumulator,
+    ) {
+        let mut chunk = frames.chunk(&self.analysis_id, input_index, range);
+        let duration = info
+            .container
+            .duration_sec
+            .filter(|duration| *duration > 0.0);
+        chunk.percent =
+            duration
+                .zip(chunk.pts.iter().rev().flatten().next())
+                .map(|(duration, pts)| {
+                    ((pts - totals.summary.bitrate.start_sec) / duration * 100.0).clamp(0.0, 100.0)
+                });
+        let _ = self.app.emit("frame-analysis-chunk", chunk);
+    }
+
+    /// Runs ffprobe over the video frames of one input, emitting a chunk every
+    /// `chunk_frames` frames. ffprobe is registered with the export manager under
+    /// the analysis id, so `cancel_export` stops it. Inputs analyzed before are
+    /// replayed from the probe cache.
+    fn analyze_input(&self, input_index: usize, info: &VideoInfo) -> AppResult<InputFrameSummary> {
+        let Self {
+            app,
+            export_manager,
+            ffprobe,
+            analysis_id,
+            bucket_sec,
+            chunk_frames,
+        } = self;
+        let analysis_id = analysis_id.as_str();
+        let mut totals = InputAccumulator::new(input_index, &info.file, *bucket_sec);
+        let cache = app.state::<ProbeCache>();
+        if let Some(frames) = cache
+            .analyzed_frames(&info.file)
+            .filter(AnalyzedFrames::is_consistent)
+        {
+            for frame in frames.frames() {
+                totals.push(&frame);
+            }
+            for start in (0..frames.len()).step_by(*chunk_frames) {
+                let end = (start + chunk_frames).min(frames.len());
+                self.emit_chunk(&frames, start..end, input_index, info, &totals);
+            }
+            return Ok(totals.finish());
+        }
+
+        let stream_index = info
+            .video
+            .as_ref()
+            .map(|video| video.index)
+            .ok_or_else(|| AppError::invalid(format!("{} has no video stream.", info.file)))?;
+        let mut child = tool_command(ffprobe)
+            .args([
+                "-v",
+                "error",
+                "-select_streams",
+                &stream_index.to_string(),
+                "-show_entries",
+                "frame=key_frame,pict_type,pkt_size,best_effort_timestamp_time,pkt_dts_time",
+                "-of",
+                "compact",
+                &info.file,
+            ])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| AppError::spawn(format!("Failed to run ffprobe: {}", e)))?;
+        let stdout = child.stdout.take();
+        let stderr = child.stderr.take();
+        if let Ok(mut children) = export_manager.children.lock() {
+            // Cancelled while ffprobe was being spawned.
+            if is_cancelled(&export_manager.cancelled, analysis_id) {
+                let _ = child.kill();
+            }
+            children.insert(analysis_id.to_string(), child);
+        }
+        let stderr_reader = thread::spawn(move || {
+            let mut buffer = String::new();
+            if let Some(stderr) = stderr {
+                let _ = BufReader::new(stderr).read_to_string(&mut buffer);
+            }
+            buffer
+        });
+
+        let mut frames = AnalyzedFrames::default();
+        let mut emitted = 0;
+        if let Some(stdout) = stdout {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                let Some(frame) = parse_frame_line(&line) else {
+                    continue;
+                };
+                totals.push(&frame);
+                frames.push(&frame);
+                if frames.len() - emitted >= *chunk_frames {
+                    self.emit_chunk(&frames, emitted..frames.len(), input_index, info, &totals);
+                    emitted = frames.len();
+                }
+            }
+        }
+        if frames.len() > emitted {
+            self.emit_chunk(&frames, emitted..frames.len(), input_index, info, &totals);
+        }
+
+        let child = export_manager
+            .children
+            .lock()
+            .ok()
+            .and_then(|mut children| children.remove(analysis_id));
+        let status = child.and_then(|mut child| child.wait().ok());
+        let stderr = stderr_reader.join().unwrap_or_default();
+        if !status.is_some_and(|status| status.success()) {
+            return Err(AppError::probe(
+                stderr_tail(&stderr)
+                    .map(|tail| format!("ffprobe failed on {}:\n{}", info.file, tail))
+                    .unwrap_or_else(|| format!("ffprobe failed on {}.", info.file)),
+            ));
+        }
+        cache.store_analyzed_frames(&info.file, &frames);
+        Ok(totals.finish())
+    }
+}
+
+/// Rejects buckets so short that an input's bitrate series would pass
+/// `MAX_BUCKETS`.
+fn check_bucket_count(infos: &[VideoInfo], bucket_sec: f64) -> AppResult<()> {
+    let longest = infos
+        .iter()
+        .filter_map(|info| info.container.duration_sec)
+        .fold(0.0, f64::max);
+    if longest / bucket_sec > MAX_BUCKETS as f64 {
+        return Err(AppError::invalid(format!(
+            "A {bucket_sec}s bitrate bucket splits {longest:.0}s into more than {MAX_BUCKETS} buckets; use a longer bucket."
+        )));
+    }
+    Ok(())
+}
+
+/// Reads the picture type, size, timestamp and key flag of every video frame
+/// of each input, plus a bitrate-over-time series. Frames arrive as
+/// `frame-analysis-chunk` events and the totals as `frame-analysis-complete`.
+/// This decodes the inputs, so it takes about as long as playing them back at
+/// decoding speed; cancel it with `cancel_export` and the analysis id.
+#[tauri::command(async)]
+pub(crate) fn analyze_frames(
+    app: AppHandle,
+    export_manager: State<'_, ExportManager>,
+    params: FrameAnalysisParams,
+) -> AppResult<FrameAnalysisStarted> {
+    if params.input_paths.is_empty() {
+        return Err(AppError::invalid("At least one input is required."));
+    }
+    let bucket_sec = params.bucket_sec.unwrap_or(DEFAULT_BUCKET_SEC);
+    if !(bucket_sec.is_finite() && bucket_sec >= MIN_BUCKET_SEC) {
+        return Err(AppError::invalid(format!(
+            "The bitrate bucket must be at least {MIN_BUCKET_SEC}s."
+        )));
+    }
+    let chunk_frames = params.chunk_frames.unwrap_or(DEFAULT_CHUNK_FRAMES).max(1);
+    let ffprobe = resolve_bundled_binary(&app, "ffprobe")?;
+    let cache = app.state::<ProbeCache>();
+    let infos = params
+        .input_paths
+        .iter()
+        .map(|path| cache.probe(&ffprobe, path))
+        .collect::<AppResult<Vec<_>>>()?;
+    check_bucket_count(&infos, bucket_sec)?;
+
+    let analysis_id = Uuid::new_v4().to_string();
+    export_manager.register(&analysis_id)?;
+    let run = AnalysisRun {
+        app,
+        export_manager: export_manager.inner().clone(),
+        ffprobe,
+        analysis_id: analysis_id.clone(),
+        bucket_sec,
+        chunk_frames,
+    };
+    thread::spawn(move || {
+        let mut result = FrameAnalysisResult {
+            analysis_id: run.analysis_id.clone(),
+            inputs: Vec::new(),
+            cancelled: false,
+            message: None,
+        };
+        let cancelled = &run.export_manager.cancelled;
+        for (index, info) in infos.iter().enumerate() {
+            // Checked before each input too: a cancel may arrive between two.
+            if is_cancelled(cancelled, &run.analysis_id) {
+                result.cancelled = true;
+                break;
+            }
+            let analyzed = run.analyze_input(index, info);
+            if is_cancelled(cancelled, &run.analysis_id) {
+                result.cancelled = true;
+                break;
+            }
+            match analyzed {
+                Ok(summary) => result.inputs.push(summary),
+                Err(error) => {
+                    result.message = Some(error.message().to_string());
+                    break;
+                }
+            }
+        }
+        run.export_manager.release(&run.analysis_id);
+        let _ = run.app.emit("frame-analysis-complete", result);
+    });
+    Ok(FrameAnalysisStarted { analysis_id })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn frame(picture_type: char, keyframe: bool, size: u32, pts: f64) -> FrameLine {
+        FrameLine {
+            picture_type,
+            keyframe,
+            size,
+            pts: Some(pts),
+        }
+    }
+
+    #[test]
+    fn parse_frame_line_reads_compact_fields() {
+        let frame = parse_frame_line(
+            "frame|key_frame=1|pkt_dts_time=0.040000|best_effort_timestamp_time=0.080000|pkt_size=1500|pict_type=I",
+        )
+        .unwrap();
+        assert_eq!(frame.picture_type, 'I');
+        assert!(frame.keyframe);
+        assert_eq!(frame.size, 1500);
+        assert_eq!(frame.pts, Some(0.08));
+
+        let frame = parse_frame_line(
+            "frame|key_frame=0|pkt_dts_time=N/A|best_effort_timestamp_time=N/A|pict_type=?",
+        )
+        .unwrap();
+        assert_eq!(frame.picture_type, '?');
+        assert_eq!(frame.pts, None);
+        assert!(parse_frame_line("packet|pts_time=0.0").is_none());
+    }
+
+    #[test]
+    fn accumulator_counts_gops_and_buckets() {
+        let mut totals = InputAccumulator::new(0, "a.mp4", 0.5);
+        for (index, picture_type) in "IPBBIPB".chars().enumerate() {
+            totals.push(&frame(
+                picture_type,
+                picture_type == 'I',
+                1000,
+                10.0 + index as f64 * 0.2,
+            ));
+        }
+        let summary = totals.finish();
+        assert_eq!(summary.frame_count, 7);
+        assert_eq!(
+            (summary.i_frames, summary.p_frames, summary.b_frames),
+            (2, 2, 3)
+        );
+        assert_eq!(summary.keyframes, 2);
+        assert_eq!(summary.average_gop, Some(3.5));
+        assert_eq!(summary.max_gop, Some(4));
+        assert_eq!(summary.bitrate.start_sec, 10.0);
+        // Frames at +0.0, 0.2, 0.4 | 0.6, 0.8 | 1.0, 1.2 seconds.
+        assert_eq!(summary.bitrate.kbps, [48.0, 32.0, 32.0]);
+        assert_eq!(summary.peak_kbps, Some(48.0));
+    }
+
+    #[test]
+    fn analyzed_frames_replay_and_split_into_chunks() {
+        let mut frames = AnalyzedFrames::default();
+        for (index, picture_type) in "IPBIP".chars().enumerate() {
+            frames.push(&frame(
+                picture_type,
+                picture_type == 'I',
+                100 + index as u32,
+                index as f64 * 0.04,
+            ));
+        }
+        assert!(frames.is_consistent());
+        let replayed: Vec<(char, bool, u32)> = frames
+            .frames()
+            .map(|frame| (frame.picture_type, frame.keyframe, frame.size))
+            .collect();
+        assert_eq!(
+            replayed,
+            [
+                ('I', true, 100),
+                ('P', false, 101),
+                ('B', false, 102),
+                ('I', true, 103),
+                ('P', false, 104)
+            ]
+        );
+        let chunk = frames.chunk("id", 1, 2..5);
+        assert_eq!(chunk.offset, 2);
+        assert_eq!(chunk.types, "BIP");
+        assert_eq!(chunk.keyframes, [1]);
+        assert_eq!(chunk.sizes, [102, 103, 104]);
+
+        frames.sizes.pop();
+        assert!(!frames.is_consistent());
+    }
+
+    #[test]
+    fn timestamp_jumps_do_not_grow_the_series_without_bound() {
+        let mut totals = InputAccumulator::new(0, "a.mp4", MIN_BUCKET_SEC);
+        totals.push(&frame('I', true, 1000, 0.0));
+        totals.push(&frame('P', false, 1000, 1.0e12));
+        let summary = totals.finish();
+        assert_eq!(summary.frame_count, 2);
+        assert_eq!(summary.bitrate.kbps.len(), 1);
+    }
+
+    #[test]
+    fn bucket_count_is_capped() {
+        let info: VideoInfo = serde_json::from_value(json!({
+            "file": "a.mp4",
+            "container": { "duration_sec": 36000.0 },
+            "subtitles": [],
+            "attachments": [],
+            "streams": [],
+        }))
+        .unwrap();
+        assert!(check_bucket_count(std::slice::from_ref(&info), 1.0).is_ok());
+        // 360,000 buckets of 0.1s would be megabytes of JSON.
+        let error = check_bucket_count(&[info], 0.1).err().unwrap();
+        assert!(matches!(error, AppError::InvalidParams { .. }));
+    }
+}
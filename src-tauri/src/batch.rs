@@ -0,0 +1,409 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+use uuid::Uuid;
+
+use crate::encoder::{EncoderSettings, VideoCodec};
+use crate::error::{AppError, AppResult};
+use crate::layout::ExportMode;
+use crate::queue::{self, ExportJob, JobStatus};
+use crate::{unique_output_path_by, ExportInput, ExportParams};
+
+/// Extensions picked up when a batch source is a directory.
+const VIDEO_EXTENSIONS: &[&str] = &[
+    "mp4", "m4v", "mov", "mkv", "webm", "avi", "mxf", "ts", "m2ts", "mts", "mpg", "mpeg", "wmv",
+    "flv", "y4m",
+];
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct BatchParams {
+    /// A directory (every video file directly inside it) or a glob such as
+    /// `clips/*_master.mov`. Wildcards (`*`, `?`) are only allowed in the file name.
+    source: String,
+    output_dir: String,
+    /// Output file name with `{stem}`, `{name}`, `{ext}`, `{source_ext}`,
+    /// `{codec}`, `{crf}`, `{mode}` and `{index}` fields, e.g.
+    /// `{stem}_{codec}_crf{crf}.{ext}`. `{ext}` is the extension of
+    /// `params.output_path`, or the codec's default container without one.
+    filename_template: String,
+    /// Settings for every export. Each file replaces the first input; further
+    /// inputs (e.g. a reference clip for a side-by-side) are kept as they are.
+    params: ExportParams,
+}
+
+#[derive(Serialize, Clone)]
+pub(crate) struct BatchFailure {
+    job_id: String,
+    input_path: String,
+    message: String,
+}
+
+#[derive(Serialize)]
+pub(crate) struct BatchJob {
+    job_id: String,
+    input_path: String,
+    output_path: String,
+}
+
+#[derive(Serialize)]
+pub(crate) struct BatchStarted {
+    batch_id: String,
+    jobs: Vec<BatchJob>,
+}
+
+/// Aggregated state of a batch, sent as `batch-progress` whenever one of its
+/// jobs starts or finishes.
+#[derive(Serialize, Clone)]
+pub(crate) struct BatchProgress {
+    batch_id: String,
+    total: usize,
+    queued: usize,
+    running: usize,
+    completed: usize,
+    failed: usize,
+    cancelled: usize,
+    /// Share of jobs that have finished, in any way.
+    percent: f64,
+    failures: Vec<BatchFailure>,
+}
+
+fn has_wildcard(value: &str) -> bool {
+    value.contains(['*', '?'])
+}
+
+/// Matches `name` against a pattern where `*` is any run of characters and `?`
+/// exactly one.
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // Position after the last `*` and the name position it was tried at.
+    let mut backtrack: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p + 1, n));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, tried)) => {
+                    p = star;
+                    n = tried + 1;
+                    backtrack = Some((star, tried + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+fn is_video_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| VIDEO_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+}
+
+/// Files of a directory that pass `keep`, sorted by name.
+fn list_files(dir: &Path, keep: impl Fn(&Path) -> bool) -> AppResult<Vec<PathBuf>> {
+    let entries = fs::read_dir(dir).map_err(|e| {
+        if e.kind() == std::io::ErrorKind::NotFound {
+            AppError::not_found(format!("Folder not found: {}", dir.display()))
+        } else {
+            AppError::io(format!("Failed to read {}: {}", dir.display(), e))
+        }
+    })?;
+    let mut files: Vec<PathBuf> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && keep(path))
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// Resolves a directory or file-name glob to the matching files.
+pub(crate) fn resolve_source(source: &str) -> AppResult<Vec<PathBuf>> {
+    let source = source.trim();
+    let path = Path::new(source);
+    if path.is_dir() {
+        return list_files(path, is_video_file);
+    }
+    let Some(pattern) = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .filter(|name| has_wildcard(name))
+    else {
+        return Err(AppError::not_found(format!(
+            "{source} is neither a folder nor a file pattern."
+        )));
+    };
+    let dir = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+    if has_wildcard(&dir.to_string_lossy()) {
+        return Err(AppError::invalid(
+            "Wildcards are only supported in the file name.",
+        ));
+    }
+    list_files(dir, |file| {
+        file.file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| wildcard_match(pattern, name))
+    })
+}
+
+/// Fills the `{field}` placeholders of `template` with `value(field)`.
+pub(crate) fn render_template(
+    template: &str,
+    value: impl Fn(&str) -> Option<String>,
+) -> AppResult<String> {
+    let mut rendered = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        rendered.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('}') else {
+            return Err(AppError::invalid("Unclosed { in the filename template."));
+        };
+        let field = &rest[start + 1..start + end];
+        let Some(replacement) = value(field) else {
+            return Err(AppError::invalid(format!(
+                "Unknown filename template field: {{{field}}}."
+            )));
+        };
+        rendered.push_str(&replacement);
+        rest = &rest[start + end + 1..];
+    }
+    rendered.push_str(rest);
+    if rendered.trim().is_empty() || rendered.contains(['/', '\\']) {
+        return Err(AppError::invalid(
+            "The filename template must produce a file name without folders.",
+        ));
+    }
+    Ok(rendered)
+}
+
+/// The export settings for one file of the batch.
+fn file_params(params: &ExportParams, path: &Path, output_path: String) -> ExportParams {
+    let mut params = params.clone();
+    let mut inputs = params.resolved_inputs();
+    let first = inputs.first().cloned().unwrap_or_default();
+    let input = ExportInput {
+        path: path.to_string_lossy().to_string(),
+        ..first
+    };
+    match inputs.first_mut() {
+        Some(slot) => *slot = input,
+        None => inputs.push(input),
+    }
+    inputs.retain(|input| !input.path.is_empty());
+    params.inputs = inputs;
+    params.input_path_a = None;
+    params.input_path_b = None;
+    params.output_path = output_path;
+    params
+}
+
+pub(crate) fn batch_progress(jobs: &[ExportJob], batch_id: &str) -> BatchProgress {
+    let jobs: Vec<&ExportJob> = jobs
+        .iter()
+        .filter(|job| job.batch_id.as_deref() == Some(batch_id))
+        .collect();
+    let count = |status: JobStatus| jobs.iter().filter(|job| job.status == status).count();
+    let total = jobs.len();
+    let finished = jobs.iter().filter(|job| job.status.is_finished()).count();
+    let failures = jobs
+        .iter()
+        .filter(|job| job.status == JobStatus::Failed)
+        .map(|job| BatchFailure {
+            job_id: job.id.clone(),
+            input_path: job
+                .params
+                .inputs
+                .first()
+                .map(|input| input.path.clone())
+                .unwrap_or_default(),
+            message: job
+                .message
+                .clone()
+                .unwrap_or_else(|| "Export failed.".to_string()),
+        })
+        .collect();
+    BatchProgress {
+        batch_id: batch_id.to_string(),
+        total,
+        queued: count(JobStatus::Queued) + count(JobStatus::Paused),
+        running: count(JobStatus::Running),
+        completed: count(JobStatus::Completed),
+        failed: count(JobStatus::Failed),
+        cancelled: count(JobStatus::Cancelled),
+        percent: if total == 0 {
+            100.0
+        } else {
+            finished as f64 * 100.0 / total as f64
+        },
+        failures,
+    }
+}
+
+/// Output paths of unfinished jobs. `unique_output_path` only sees files on
+/// disk, so queued jobs would otherwise be given the same name.
+pub(crate) fn pending_outputs(app: &AppHandle) -> AppResult<HashSet<String>> {
+    Ok(queue::jobs(app)?
+        .into_iter()
+        .filter(|job| !job.status.is_finished())
+        .map(|job| {
+            job.output_path
+                .unwrap_or_else(|| job.params.output_path.clone())
+        })
+        .collect())
+}
+
+/// Enqueues one export per file of `source`, named by the template inside
+/// `output_dir`. Names already used by files on disk or by unfinished jobs get
+/// a ` (1)`, ` (2)`, ... suffix.
+#[tauri::command]
+pub(crate) fn batch_export(app: AppHandle, params: BatchParams) -> AppResult<BatchStarted> {
+    let files = resolve_source(&params.source)?;
+    if files.is_empty() {
+        return Err(AppError::not_found(format!(
+            "No video files match {}.",
+            params.source.trim()
+        )));
+    }
+    let output_dir = PathBuf::from(params.output_dir.trim());
+    if params.output_dir.trim().is_empty() {
+        return Err(AppError::invalid("An output folder is required."));
+    }
+    fs::create_dir_all(&output_dir)
+        .map_err(|e| AppError::io(format!("Failed to create output folder: {}", e)))?;
+
+    // Settings that are the same for every file are checked once, up front.
+    let settings = &params.params;
+    ExportMode::parse(&settings.export_mode)?;
+    // `{ext}` is the container of the output path, or the codec's default one.
+    let output_ext = match Path::new(&settings.output_path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+    {
+        Some(ext) => ext.to_string(),
+        None => VideoCodec::parse(&settings.codec)?
+            .default_container()
+            .to_string(),
+    };
+    EncoderSettings::from_params(settings, &format!("output.{output_ext}"))?;
+
+    let mut taken = pending_outputs(&app)?;
+    let width = files.len().to_string().len();
+    let mut planned = Vec::new();
+    for (index, file) in files.iter().enumerate() {
+        let stem = file
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or("output");
+        let source_ext = file
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or("mp4");
+        let name = render_template(&params.filename_template, |field| match field {
+            "stem" => Some(stem.to_string()),
+            "name" => file
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string()),
+            "ext" => Some(output_ext.clone()),
+            "source_ext" => Some(source_ext.to_string()),
+            "codec" => Some(settings.codec.clone()),
+            "crf" => Some(settings.crf.to_string()),
+            "mode" => Some(settings.export_mode.clone()),
+            "index" => Some(format!("{:0width$}", index + 1)),
+            _ => None,
+        })?;
+        let output_path = output_dir.join(name).to_string_lossy().to_string();
+        // The template may name another container, e.g. through `{source_ext}`.
+        EncoderSettings::from_params(settings, &output_path)?;
+        let output_path = unique_output_path_by(&output_path, |candidate| {
+            candidate.exists() || taken.contains(candidate.to_string_lossy().as_ref())
+        });
+        taken.insert(output_path.clone());
+        planned.push(file_params(settings, file, output_path));
+    }
+
+    let batch_id = Uuid::new_v4().to_string();
+    let jobs = queue::enqueue_jobs(&app, planned, Some(&batch_id))?;
+    Ok(BatchStarted {
+        batch_id,
+        jobs: jobs
+            .into_iter()
+            .map(|job| BatchJob {
+                input_path: job
+                    .params
+                    .inputs
+                    .first()
+                    .map(|input| input.path.clone())
+                    .unwrap_or_default(),
+                output_path: job.params.output_path.clone(),
+                job_id: job.id,
+            })
+            .collect(),
+    })
+}
+
+#[tauri::command]
+pub(crate) fn batch_status(app: AppHandle, batch_id: String) -> AppResult<BatchProgress> {
+    let jobs = queue::jobs(&app)?;
+    if !jobs
+        .iter()
+        .any(|job| job.batch_id.as_deref() == Some(batch_id.as_str()))
+    {
+        return Err(AppError::not_found(format!("Unknown batch: {batch_id}")));
+    }
+    Ok(batch_progress(&jobs, &batch_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wildcard_match_handles_stars_and_question_marks() {
+        assert!(wildcard_match("*.mov", "clip.mov"));
+        assert!(wildcard_match("*_master.mov", "a_b_master.mov"));
+        assert!(wildcard_match("clip?.mp4", "clip1.mp4"));
+        assert!(wildcard_match("*", ""));
+        assert!(wildcard_match("a*b*c", "aXbYbZc"));
+        assert!(!wildcard_match("*.mov", "clip.mp4"));
+        assert!(!wildcard_match("clip?.mp4", "clip.mp4"));
+        assert!(!wildcard_match("clip", "clip.mp4"));
+    }
+
+    #[test]
+    fn render_template_fills_fields() {
+        let value = |field: &str| match field {
+            "stem" => Some("clip".to_string()),
+            "codec" => Some("h265".to_string()),
+            "ext" => Some("mkv".to_string()),
+            _ => None,
+        };
+        assert_eq!(
+            render_template("{stem}_{codec}.{ext}", value).unwrap(),
+            "clip_h265.mkv"
+        );
+        assert_eq!(render_template("fixed.mp4", value).unwrap(), "fixed.mp4");
+        for template in ["{stem", "{size}.mp4", "out/{stem}.mp4", "   "] {
+            let error = render_template(template, value).err().unwrap();
+            assert!(
+                matches!(error, AppError::InvalidParams { .. }),
+                "{template}"
+            );
+        }
+    }
+}
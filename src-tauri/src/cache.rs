@@ -0,0 +1,419 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::SystemTime;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
+
+use crate::analysis::AnalyzedFrames;
+use crate::error::{AppError, AppResult};
+use crate::probe::{probe_file, VideoInfo};
+use crate::timeline::FrameTimeline;
+
+const CACHE_DIR: &str = "probe-cache";
+/// Written into every entry. Bump it when `VideoInfo`, `FrameTimeline` or
+/// `AnalyzedFrames` change meaning, so entries from older builds are read again.
+const CACHE_VERSION: u32 = 2;
+/// The least recently used entries are removed once the cache grows past this.
+const CACHE_LIMIT_BYTES: u64 = 256 * 1024 * 1024;
+/// Bytes hashed at the start and at the end of a file. Catches files that were
+/// rewritten in place with the same size and a restored modification time.
+const SAMPLE_BYTES: u64 = 64 * 1024;
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// FNV-1a; stable across builds, unlike `DefaultHasher`.
+fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    for byte in bytes {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
+
+fn sample_hash(path: &Path, size: u64) -> Option<u64> {
+    let mut file = File::open(path).ok()?;
+    let mut head = vec![0; size.min(SAMPLE_BYTES) as usize];
+    file.read_exact(&mut head).ok()?;
+    let mut hash = fnv1a(FNV_OFFSET, &head);
+    let tail_len = size.saturating_sub(SAMPLE_BYTES).min(SAMPLE_BYTES);
+    if tail_len > 0 {
+        let mut tail = vec![0; tail_len as usize];
+        file.seek(SeekFrom::End(-(tail_len as i64))).ok()?;
+        file.read_exact(&mut tail).ok()?;
+        hash = fnv1a(hash, &tail);
+    }
+    Some(hash)
+}
+
+/// What a cached result is tied to. Any change means the file was replaced
+/// or edited and the entry is dropped.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+struct FileIdentity {
+    size: u64,
+    modified: Option<SystemTime>,
+    sample_hash: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheEntry<T> {
+    version: u32,
+    path: String,
+    identity: FileIdentity,
+    value: T,
+}
+
+#[derive(Serialize)]
+pub(crate) struct CacheStats {
+    entries: usize,
+    size_bytes: u64,
+    limit_bytes: u64,
+}
+
+fn cache_key(path: &str) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path))
+}
+
+/// Probe results, deep-probe timelines and per-frame analyses, kept in the app
+/// data directory so unchanged files are not read again. Entries are JSON
+/// files named after the file path; timelines also stay in memory for the
+/// session because exports look them up for every trimmed input.
+pub(crate) struct ProbeCache {
+    /// `None` when the app data directory is unavailable; files are then
+    /// probed every time.
+    dir: Option<PathBuf>,
+    /// Serializes writes and pruning.
+    disk: Mutex<()>,
+    timelines: Mutex<HashMap<PathBuf, (FileIdentity, Arc<FrameTimeline>)>>,
+    /// Identities computed this session, so the sample hash is only read
+    /// again once a file's size or modification time changes.
+    identities: Mutex<HashMap<PathBuf, FileIdentity>>,
+}
+
+impl ProbeCache {
+    pub(crate) fn open(app: &AppHandle) -> Self {
+        Self::new(
+            app.path()
+                .app_data_dir()
+                .ok()
+                .map(|dir| dir.join(CACHE_DIR)),
+        )
+    }
+
+    fn new(dir: Option<PathBuf>) -> Self {
+        Self {
+            dir,
+            disk: Mutex::new(()),
+            timelines: Mutex::new(HashMap::new()),
+            identities: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// What a cached result for `key` is tied to. The content is only hashed on
+    /// the first lookup per session; later lookups reuse that hash while the
+    /// size and modification time match, so an in-place rewrite that keeps both
+    /// is not noticed until the next session.
+    fn identity(&self, key: &Path) -> Option<FileIdentity> {
+        let metadata = fs::metadata(key).ok()?;
+        let size = metadata.len();
+        let modified = metadata.modified().ok();
+        if modified.is_some() {
+            let identities = self.identities.lock().ok();
+            let known = identities.as_ref().and_then(|known| known.get(key));
+            if let Some(known) =
+                known.filter(|known| known.size == size && known.modified == modified)
+            {
+                return Some(*known);
+            }
+        }
+        let identity = FileIdentity {
+            size,
+            modified,
+            sample_hash: sample_hash(key, size),
+        };
+        if let Ok(mut identities) = self.identities.lock() {
+            identities.insert(key.to_path_buf(), identity);
+        }
+        Some(identity)
+    }
+
+    fn lock(&self) -> AppResult<MutexGuard<'_, ()>> {
+        self.disk.lock().map_err(|_| AppError::lock())
+    }
+
+    fn entry_path(&self, kind: &str, key: &Path) -> Option<PathBuf> {
+        let hash = fnv1a(FNV_OFFSET, key.to_string_lossy().as_bytes());
+        Some(self.dir.as_ref()?.join(format!("{hash:016x}.{kind}.json")))
+    }
+
+    /// The cached value for `key`, if it was stored for the same file content.
+    /// Stale or unreadable entries are removed.
+    fn load<T: DeserializeOwned>(
+        &self,
+        kind: &str,
+        key: &Path,
+        identity: &FileIdentity,
+    ) -> Option<T> {
+        let file = self.entry_path(kind, key)?;
+        let json = fs::read(&file).ok()?;
+        match serde_json::from_slice::<CacheEntry<T>>(&json) {
+            Ok(entry)
+                if entry.version == CACHE_VERSION
+                    && entry.identity == *identity
+                    && Path::new(&entry.path) == key =>
+            {
+                // The modification time doubles as the last use for pruning.
+                if let Ok(handle) = File::options().append(true).open(&file) {
+                    let _ = handle.set_modified(SystemTime::now());
+                }
+                Some(entry.value)
+            }
+            _ => {
+                let _guard = self.lock().ok()?;
+                let _ = fs::remove_file(&file);
+                None
+            }
+        }
+    }
+
+    /// Writes an entry and prunes the cache. Failures only cost a later
+    /// re-probe, so they are not reported.
+    fn store<T: Serialize>(&self, kind: &str, key: &Path, identity: &FileIdentity, value: &T) {
+        let (Some(dir), Some(file)) = (self.dir.as_ref(), self.entry_path(kind, key)) else {
+            return;
+        };
+        let entry = CacheEntry {
+            version: CACHE_VERSION,
+            path: key.to_string_lossy().to_string(),
+            identity: *identity,
+            value,
+        };
+        let Ok(json) = serde_json::to_vec(&entry) else {
+            return;
+        };
+        let Ok(_guard) = self.lock() else {
+            return;
+        };
+        let temp = file.with_extension("json.tmp");
+        if fs::create_dir_all(dir).is_ok() && fs::write(&temp, json).is_ok() {
+            let _ = fs::rename(&temp, &file);
+        }
+        prune(dir, CACHE_LIMIT_BYTES);
+    }
+
+    /// Probes `path`, or returns the stored result when the file is unchanged.
+    /// A timeline from `deep_probe` is attached and its exact frame count
+    /// replaces the estimate.
+    pub(crate) fn probe(&self, ffprobe: &Path, path: &str) -> AppResult<VideoInfo> {
+        let key = cache_key(path);
+        let identity = self.identity(&key);
+        let cached = identity
+            .as_ref()
+            .and_then(|identity| self.load::<VideoInfo>("probe", &key, identity));
+        let mut info = match cached {
+            Some(mut info) => {
+                info.file = path.to_string();
+                info
+            }
+            None => {
+                let info = probe_file(ffprobe, path)?;
+                if let Some(identity) = &identity {
+                    self.store("probe", &key, identity, &info);
+                }
+                info
+            }
+        };
+        let timeline = identity.and_then(|identity| self.cached_timeline(&key, &identity));
+        if let (Some(timeline), Some(video)) = (timeline, info.video.as_mut()) {
+            if video.index == timeline.stream_index {
+                video.frame_count = Some(timeline.frame_count());
+                info.timeline = Some(timeline);
+            }
+        }
+        Ok(info)
+    }
+
+    fn cached_timeline(&self, key: &Path, identity: &FileIdentity) -> Option<Arc<FrameTimeline>> {
+        let mut timelines = self.timelines.lock().ok()?;
+        if let Some((cached, timeline)) = timelines.get(key) {
+            if cached == identity {
+                return Some(timeline.clone());
+            }
+            timelines.remove(key);
+        }
+        let timeline = Arc::new(self.load::<FrameTimeline>("timeline", key, identity)?);
+        timelines.insert(key.to_path_buf(), (*identity, timeline.clone()));
+        Some(timeline)
+    }
+
+    /// The timeline of `path` if `deep_probe` read it since it last changed.
+    pub(crate) fn timeline(&self, path: &str) -> Option<Arc<FrameTimeline>> {
+        let key = cache_key(path);
+        let identity = self.identity(&key)?;
+        self.cached_timeline(&key, &identity)
+    }
+
+    pub(crate) fn store_timeline(&self, path: &str, timeline: Arc<FrameTimeline>) {
+        let key = cache_key(path);
+        let Some(identity) = self.identity(&key) else {
+            return;
+        };
+        self.store("timeline", &key, &identity, timeline.as_ref());
+        if let Ok(mut timelines) = self.timelines.lock() {
+            timelines.insert(key, (identity, timeline));
+        }
+    }
+
+    /// The frames `analyze_frames` read from `path`, if it is unchanged since.
+    pub(crate) fn analyzed_frames(&self, path: &str) -> Option<AnalyzedFrames> {
+        let key = cache_key(path);
+        let identity = self.identity(&key)?;
+        self.load("frames", &key, &identity)
+    }
+
+    pub(crate) fn store_analyzed_frames(&self, path: &str, frames: &AnalyzedFrames) {
+        let key = cache_key(path);
+        if let Some(identity) = self.identity(&key) {
+            self.store("frames", &key, &identity, frames);
+        }
+    }
+
+    fn stats(&self) -> CacheStats {
+        let entries = self.dir.as_deref().map(cache_files).unwrap_or_default();
+        CacheStats {
+            entries: entries.len(),
+            size_bytes: entries.iter().map(|(_, size, _)| size).sum(),
+            limit_bytes: CACHE_LIMIT_BYTES,
+        }
+    }
+}
+
+/// Entry files of the cache directory with their size and last use.
+fn cache_files(dir: &Path) -> Vec<(PathBuf, u64, SystemTime)> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            let path = entry.path();
+            (metadata.is_file() && path.extension().is_some_and(|ext| ext == "json")).then(|| {
+                let used = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                (path, metadata.len(), used)
+            })
+        })
+        .collect()
+}
+
+/// Removes the least recently used entries until the cache fits `limit`.
+fn prune(dir: &Path, limit: u64) {
+    let mut files = cache_files(dir);
+    let mut total: u64 = files.iter().map(|(_, size, _)| size).sum();
+    files.sort_by_key(|(_, _, used)| *used);
+    for (path, size, _) in files {
+        if total <= limit {
+            break;
+        }
+        if fs::remove_file(&path).is_ok() {
+            total -= size;
+        }
+    }
+}
+
+#[tauri::command]
+pub(crate) fn probe_cache_stats(cache: State<'_, ProbeCache>) -> CacheStats {
+    cache.stats()
+}
+
+/// Empties the cache, on disk and in memory, and returns what was removed.
+#[tauri::command]
+pub(crate) fn clear_probe_cache(cache: State<'_, ProbeCache>) -> AppResult<CacheStats> {
+    let removed = cache.stats();
+    {
+        let _guard = cache.lock()?;
+        if let Some(dir) = cache.dir.as_deref() {
+            for (path, _, _) in cache_files(dir) {
+                fs::remove_file(&path).map_err(|e| {
+                    AppError::io(format!("Failed to remove {}: {}", path.display(), e))
+                })?;
+            }
+        }
+    }
+    cache
+        .timelines
+        .lock()
+        .map_err(|_| AppError::lock())?
+        .clear();
+    cache
+        .identities
+        .lock()
+        .map_err(|_| AppError::lock())?
+        .clear();
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    /// A cache in a fresh temporary directory, and a file to cache results for.
+    fn setup() -> (ProbeCache, PathBuf, PathBuf) {
+        let root = std::env::temp_dir().join(format!("pixelduel-cache-{}", Uuid::new_v4()));
+        fs::create_dir_all(&root).unwrap();
+        let file = root.join("clip.mp4");
+        fs::write(&file, b"first contents").unwrap();
+        (ProbeCache::new(Some(root.join(CACHE_DIR))), file, root)
+    }
+
+    #[test]
+    fn content_is_checked_on_the_first_lookup_per_session() {
+        let (cache, file, root) = setup();
+        let first = cache.identity(&file).unwrap();
+        let modified = fs::metadata(&file).unwrap().modified().unwrap();
+
+        // Same size and restored modification time: the rewrite goes unnoticed
+        // for the rest of the session.
+        fs::write(&file, b"other contents").unwrap();
+        File::options()
+            .append(true)
+            .open(&file)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+        assert!(cache.identity(&file).unwrap() == first);
+
+        // A new cache, as in the next session, hashes the file again.
+        let fresh = ProbeCache::new(None).identity(&file).unwrap();
+        assert!(fresh.sample_hash != first.sample_hash);
+
+        fs::write(&file, b"longer other contents").unwrap();
+        assert!(cache.identity(&file).unwrap().size != first.size);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn entries_are_dropped_once_the_file_changes() {
+        let (cache, file, root) = setup();
+        let key = cache_key(file.to_str().unwrap());
+        let identity = cache.identity(&key).unwrap();
+        cache.store("frames", &key, &identity, &vec![1u32, 2, 3]);
+        assert_eq!(
+            cache.load::<Vec<u32>>("frames", &key, &identity),
+            Some(vec![1, 2, 3])
+        );
+        assert_eq!(cache.stats().entries, 1);
+
+        fs::write(&file, b"changed").unwrap();
+        let changed = cache.identity(&key).unwrap();
+        assert_eq!(cache.load::<Vec<u32>>("frames", &key, &changed), None);
+        assert_eq!(cache.stats().entries, 0);
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,450 @@
+//! Headless entry point behind the `pixelduel` binary, for scripted probing and
+//! exports without the GUI. It shares probing and command building with the
+//! Tauri commands; only process handling and output differ.
+
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::thread;
+
+use serde_json::{Map, Value};
+use uuid::Uuid;
+
+use crate::diff::{field_diffs, Severity};
+use crate::error::{AppError, AppResult, ExportOutcome};
+use crate::probe::probe_file;
+use crate::progress::{ExportProgress, ProgressTracker};
+use crate::{encoder, prepare_export, resolve_binary, stderr_tail, tool_command, ExportParams};
+
+const USAGE: &str = "\
+Usage:
+  pixelduel probe <file> [--json]
+  pixelduel compare <a> <b> [--json]
+  pixelduel export [--params <file.json>] [--<field> <value>]... [-o <output>] [<input>]...
+
+Export fields are the ExportParams names in kebab-case, e.g. --export-mode
+side-by-side --codec h265 --crf 20 --label-a Source. Values are read as JSON
+when they parse (numbers, true/false, arrays), otherwise as text; quote
+text that looks like a number as '\"123\"'. Boolean fields are switches:
+--two-pass turns one on, --keep-aspect false turns one off. Positional inputs replace
+`inputs`. ffmpeg and ffprobe are taken from PIXELDUEL_FFMPEG and
+PIXELDUEL_FFPROBE, the binaries directory or PATH.
+
+Exit codes: 0 success, 1 failed, 2 invalid arguments.";
+
+/// Runs the CLI with `args` (without the program name) and returns the exit code.
+pub fn run(args: Vec<String>) -> i32 {
+    let Some((subcommand, rest)) = args.split_first() else {
+        eprintln!("{USAGE}");
+        return 2;
+    };
+    let result = match subcommand.as_str() {
+        "probe" => probe(rest),
+        "compare" => compare(rest),
+        "export" => export(rest),
+        "help" | "--help" | "-h" => {
+            println!("{USAGE}");
+            return 0;
+        }
+        other => Err(AppError::invalid(format!("Unknown command: {other}."))),
+    };
+    match result {
+        Ok(code) => code,
+        Err(error) => {
+            eprintln!("error: {}", error.message());
+            match error {
+                AppError::InvalidParams { .. } => 2,
+                _ => 1,
+            }
+        }
+    }
+}
+
+/// Splits `--json` off the positional arguments.
+fn json_flag(args: &[String]) -> (bool, Vec<&str>) {
+    let json = args.iter().any(|arg| arg == "--json");
+    let positional = args
+        .iter()
+        .filter(|arg| *arg != "--json")
+        .map(String::as_str)
+        .collect();
+    (json, positional)
+}
+
+fn print_json(value: &impl serde::Serialize) -> AppResult<()> {
+    let json = serde_json::to_string_pretty(value)
+        .map_err(|e| AppError::io(format!("Failed to serialize output: {}", e)))?;
+    println!("{json}");
+    Ok(())
+}
+
+fn ffprobe() -> AppResult<PathBuf> {
+    resolve_binary(None, "ffprobe")
+}
+
+fn probe(args: &[String]) -> AppResult<i32> {
+    let (json, positional) = json_flag(args);
+    let [path] = positional.as_slice() else {
+        return Err(AppError::invalid("probe takes exactly one file."));
+    };
+    let info = probe_file(&ffprobe()?, path)?;
+    if json {
+        print_json(&info)?;
+    } else {
+        for (field, value) in info.summary_rows() {
+            println!("{field:<18} {value}");
+        }
+    }
+    Ok(0)
+}
+
+fn compare(args: &[String]) -> AppResult<i32> {
+    let (json, positional) = json_flag(args);
+    let [path_a, path_b] = positional.as_slice() else {
+        return Err(AppError::invalid("compare takes exactly two files."));
+    };
+    let ffprobe = ffprobe()?;
+    let info_a = probe_file(&ffprobe, path_a)?;
+    let info_b = probe_file(&ffprobe, path_b)?;
+    let diffs = field_diffs(&info_a, &info_b);
+    if json {
+        print_json(&serde_json::json!({ "a": info_a, "b": info_b, "differences": diffs }))?;
+        return Ok(0);
+    }
+    let rows_a = info_a.summary_rows();
+    let rows_b = info_b.summary_rows();
+    let width = rows_a
+        .iter()
+        .map(|(_, value)| value.len())
+        .chain(["A".len()])
+        .max()
+        .unwrap_or(0);
+    println!("{:<18} {:<width$}   B", "", "A");
+    for ((field, a), (_, b)) in rows_a.iter().zip(&rows_b) {
+        let marker = if a == b { ' ' } else { '*' };
+        println!("{marker}{field:<17} {a:<width$}   {b}");
+    }
+    if !diffs.is_empty() {
+        println!();
+        println!("Differences:");
+    }
+    for diff in &diffs {
+        let severity = match diff.severity {
+            Severity::Significant => "significant",
+            Severity::Minor => "minor",
+            Severity::Info => "info",
+        };
+        let side = |value: &Option<String>| value.clone().unwrap_or_else(|| "-".to_string());
+        let delta = diff
+            .delta_percent
+            .map(|delta| format!(" ({delta:+.1}%)"))
+            .unwrap_or_default();
+        println!(
+            "  {severity:<12} {:<16} {} -> {}{delta}",
+            diff.label,
+            side(&diff.a),
+            side(&diff.b)
+        );
+    }
+    Ok(0)
+}
+
+/// `ExportParams` fields that are plain booleans and may be given as switches.
+const BOOL_FIELDS: &[&str] = &[
+    "twoPass",
+    "keepAspect",
+    "audioCopy",
+    "wipeAnimated",
+    "diffHeatmap",
+    "burnSubtitles",
+];
+
+/// `--label-a` to `labelA`, matching the serde names of `ExportParams`.
+fn field_name(flag: &str) -> String {
+    let mut name = String::new();
+    let mut upper = false;
+    for c in flag.chars() {
+        if c == '-' {
+            upper = true;
+        } else if upper {
+            name.extend(c.to_uppercase());
+            upper = false;
+        } else {
+            name.push(c);
+        }
+    }
+    name
+}
+
+fn flag_value(value: &str) -> Value {
+    serde_json::from_str(value).unwrap_or_else(|_| Value::String(value.to_string()))
+}
+
+/// Builds `ExportParams` from a `--params` file, field flags and positional
+/// inputs, filling in the GUI defaults for required fields.
+fn export_params(args: &[String]) -> AppResult<ExportParams> {
+    let mut fields = Map::new();
+    fields.insert("codec".into(), "h264".into());
+    fields.insert("crf".into(), 23.into());
+    fields.insert("keepAspect".into(), true.into());
+    fields.insert("audioCopy".into(), false.into());
+
+    let mut inputs = Vec::new();
+    let mut args = args.iter().peekable();
+    while let Some(arg) = args.next() {
+        let Some(flag) = arg.strip_prefix("--").or(arg.strip_prefix('-')) else {
+            inputs.push(Value::from(Map::from_iter([(
+                "path".to_string(),
+                Value::String(arg.clone()),
+            )])));
+            continue;
+        };
+        let field = field_name(flag);
+        if BOOL_FIELDS.contains(&field.as_str()) {
+            // The next argument is only taken as the value when it is a bool.
+            let value = match args.peek().map(|next| next.as_str()) {
+                Some(next @ ("true" | "false")) => {
+                    args.next();
+                    next == "true"
+                }
+                _ => true,
+            };
+            fields.insert(field, value.into());
+            continue;
+        }
+        let Some(value) = args.next() else {
+            return Err(AppError::invalid(format!("{arg} needs a value.")));
+        };
+        match flag {
+            "params" => {
+                let json = fs::read_to_string(value)
+                    .map_err(|e| AppError::invalid(format!("Failed to read {value}: {}", e)))?;
+                let Value::Object(file_fields) = serde_json::from_str(&json)
+                    .map_err(|e| AppError::invalid(format!("Invalid params file: {}", e)))?
+                else {
+                    return Err(AppError::invalid(
+                        "The params file must hold a JSON object.",
+                    ));
+                };
+                fields.extend(file_fields);
+            }
+            "o" | "output" => {
+                fields.insert("outputPath".into(), value.as_str().into());
+            }
+            "mode" => {
+                fields.insert("exportMode".into(), value.as_str().into());
+            }
+            _ => {
+                fields.insert(field, flag_value(value));
+            }
+        }
+    }
+    if !inputs.is_empty() {
+        if !fields.contains_key("exportMode") {
+            let mode = if inputs.len() == 1 {
+                "input-a"
+            } else {
+                "side-by-side"
+            };
+            fields.insert("exportMode".into(), mode.into());
+        }
+        fields.insert("inputs".into(), Value::Array(inputs));
+    }
+    if !fields.contains_key("outputPath") {
+        return Err(AppError::invalid(
+            "An output path is required (-o <output>).",
+        ));
+    }
+    if !fields.contains_key("exportMode") {
+        return Err(AppError::invalid(
+            "An export mode is required (--export-mode).",
+        ));
+    }
+    serde_json::from_value(Value::Object(fields))
+        .map_err(|e| AppError::invalid(format!("Invalid export options: {}", e)))
+}
+
+fn progress_line(payload: &ExportProgress) -> String {
+    let mut parts = Vec::new();
+    if let Some(pass) = payload.pass {
+        parts.push(format!("pass {pass}"));
+    }
+    if let Some(percent) = payload.percent {
+        parts.push(format!("{percent:5.1}%"));
+    }
+    if let Some(speed) = payload.speed {
+        parts.push(format!("{speed:.2}x"));
+    }
+    if let Some(eta) = payload.eta_sec {
+        parts.push(format!("ETA {eta:.0}s"));
+    }
+    parts.join("  ")
+}
+
+fn export(args: &[String]) -> AppResult<i32> {
+    let (json, _) = json_flag(args);
+    let args: Vec<String> = args
+        .iter()
+        .filter(|arg| *arg != "--json")
+        .cloned()
+        .collect();
+    let params = export_params(&args)?;
+
+    let ffmpeg = resolve_binary(None, "ffmpeg")?;
+    let ffprobe = ffprobe();
+    let probe = |path: &str| match &ffprobe {
+        Ok(ffprobe) => probe_file(ffprobe, path),
+        Err(error) => Err(error.clone()),
+    };
+    let export_id = Uuid::new_v4().to_string();
+    let prepared = prepare_export(&ffmpeg, probe, &export_id, &params)?;
+    for warning in &prepared.warnings {
+        eprintln!("warning: {warning}");
+    }
+    eprintln!("{}", prepared.command_line());
+
+    let mut tracker = ProgressTracker::new(export_id, prepared.target.clone());
+    let mut outcome = None;
+    for (pass, command) in prepared.commands.iter().enumerate() {
+        if pass > 0 {
+            tracker.next_pass();
+        }
+        let spawned = tool_command(command.program())
+            .args(command.args())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn();
+        let mut child = match spawned {
+            Ok(child) => child,
+            Err(e) => {
+                outcome = Some(ExportOutcome::SpawnFailed {
+                    message: format!("Failed to start ffmpeg: {}", e),
+                });
+                break;
+            }
+        };
+        let stderr = child.stderr.take();
+        let stderr_reader = thread::spawn(move || {
+            let mut buffer = String::new();
+            if let Some(stderr) = stderr {
+                let _ = BufReader::new(stderr).read_to_string(&mut buffer);
+            }
+            buffer
+        });
+        if let Some(stdout) = child.stdout.take() {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                if let Some(payload) = tracker.push_line(&line) {
+                    eprint!("\r{:<60}", progress_line(&payload));
+                    let _ = std::io::stderr().flush();
+                }
+            }
+        }
+        let status = child.wait().ok();
+        let stderr = stderr_reader.join().unwrap_or_default();
+        if !status.is_some_and(|status| status.success()) {
+            outcome = Some(ExportOutcome::Failed {
+                exit_code: status.and_then(|status| status.code()),
+                stderr_tail: stderr_tail(&stderr),
+            });
+            break;
+        }
+    }
+    eprintln!();
+    if prepared.commands.len() > 1 {
+        encoder::remove_pass_logs(&prepared.pass_log);
+    }
+
+    let outcome = outcome.unwrap_or_else(|| ExportOutcome::Succeeded {
+        output_size_bytes: fs::metadata(&prepared.output_path).ok().map(|m| m.len()),
+        elapsed_sec: tracker.elapsed_sec(),
+    });
+    let succeeded = matches!(outcome, ExportOutcome::Succeeded { .. });
+    let payload = tracker.finish(outcome);
+    if json {
+        print_json(&payload)?;
+    } else if succeeded {
+        println!("{}", prepared.output_path);
+    } else if let Some(message) = &payload.message {
+        eprintln!("error: {message}");
+    }
+    Ok(if succeeded { 0 } else { 1 })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn field_name_converts_kebab_case() {
+        assert_eq!(field_name("label-a"), "labelA");
+        assert_eq!(field_name("export-mode"), "exportMode");
+        assert_eq!(field_name("diff-heatmap-preset"), "diffHeatmapPreset");
+        assert_eq!(field_name("crf"), "crf");
+    }
+
+    #[test]
+    fn export_params_read_flags_and_inputs() {
+        let params = export_params(&args(&[
+            "--codec",
+            "h265",
+            "--crf",
+            "20",
+            "--label-a",
+            "Source",
+            "-o",
+            "out.mp4",
+            "a.mp4",
+            "b.mp4",
+        ]))
+        .unwrap();
+        assert_eq!(params.codec, "h265");
+        assert_eq!(params.crf, 20);
+        assert_eq!(params.label_a.as_deref(), Some("Source"));
+        assert_eq!(params.output_path, "out.mp4");
+        assert_eq!(params.export_mode, "side-by-side");
+        let paths: Vec<&str> = params
+            .inputs
+            .iter()
+            .map(|input| input.path.as_str())
+            .collect();
+        assert_eq!(paths, ["a.mp4", "b.mp4"]);
+        assert!(params.keep_aspect);
+    }
+
+    #[test]
+    fn bool_fields_are_switches() {
+        let params = export_params(&args(&[
+            "--two-pass",
+            "-o",
+            "out.mp4",
+            "--keep-aspect",
+            "false",
+            "--audio-copy",
+            "a.mp4",
+        ]))
+        .unwrap();
+        assert!(params.two_pass);
+        assert!(!params.keep_aspect);
+        assert!(params.audio_copy);
+        assert_eq!(params.export_mode, "input-a");
+        assert_eq!(params.inputs.len(), 1);
+    }
+
+    #[test]
+    fn export_params_report_missing_values() {
+        for case in [
+            &["a.mp4"][..],
+            &["-o", "out.mp4"],
+            &["a.mp4", "-o"],
+            &["--crf", "high", "-o", "out.mp4", "a.mp4"],
+        ] {
+            let error = export_params(&args(case)).err().unwrap();
+            assert!(matches!(error, AppError::InvalidParams { .. }), "{case:?}");
+        }
+    }
+}
@@ -0,0 +1,351 @@
+use crate::encoder::VideoCodec;
+use crate::error::{AppError, AppResult};
+use crate::ffmpeg::FfmpegCommand;
+use crate::layout::input_letter;
+use crate::probe::{HdrInfo, MasteringDisplay, VideoInfo};
+use crate::ExportParams;
+
+const DEFAULT_TONEMAP: &str = "hable";
+/// Common pixel format composite layouts are normalized to.
+const COMMON_PIX_FMT: &str = "yuv420p";
+const TONEMAP_ALGORITHMS: &[&str] = &[
+    "none", "linear", "gamma", "clip", "reinhard", "hable", "mobius",
+];
+
+/// What an export does with HDR inputs. Without a mode, HDR video is re-encoded
+/// as-is and loses its metadata.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum HdrMode {
+    /// Convert HDR inputs to SDR BT.709 with `zscale` and `tonemap`.
+    Tonemap,
+    /// Keep PQ/HLG signalling and static metadata when encoding with libx265.
+    /// Dolby Vision RPUs and HDR10+ dynamic metadata are not carried over.
+    Preserve,
+}
+
+impl HdrMode {
+    pub(crate) fn from_params(params: &ExportParams) -> AppResult<Option<Self>> {
+        match params.hdr_mode.as_deref().map(str::trim) {
+            None | Some("") | Some("none") => Ok(None),
+            Some("tonemap") => Ok(Some(HdrMode::Tonemap)),
+            Some("preserve") => Ok(Some(HdrMode::Preserve)),
+            Some(other) => Err(AppError::invalid(format!("Unknown HDR mode: {other}."))),
+        }
+    }
+}
+
+pub(crate) fn hdr_of(info: Option<&VideoInfo>) -> Option<&HdrInfo> {
+    info?.video.as_ref()?.hdr.as_ref()
+}
+
+/// Filters that map one HDR input to SDR BT.709. `zscale` reads the transfer
+/// (PQ or HLG) from the frames, so both go through the same chain.
+pub(crate) fn tonemap_filters(params: &ExportParams) -> AppResult<Vec<String>> {
+    let algorithm = params
+        .tonemap_algorithm
+        .as_deref()
+        .map(str::trim)
+        .filter(|algorithm| !algorithm.is_empty())
+        .unwrap_or(DEFAULT_TONEMAP);
+    if !TONEMAP_ALGORITHMS.contains(&algorithm) {
+        return Err(AppError::invalid(format!(
+            "Unknown tonemap algorithm: {algorithm}."
+        )));
+    }
+    Ok(vec![
+        "zscale=t=linear:npl=100".to_string(),
+        "format=gbrpf32le".to_string(),
+        "zscale=p=bt709".to_string(),
+        format!("tonemap=tonemap={algorithm}:desat=0"),
+        "zscale=t=bt709:m=bt709:r=tv".to_string(),
+        "format=yuv420p".to_string(),
+    ])
+}
+
+/// Tags the output as BT.709 after tonemapping or normalization.
+pub(crate) fn apply_sdr_output(command: &mut FfmpegCommand) {
+    command.option("-color_primaries", "bt709");
+    command.option("-color_trc", "bt709");
+    command.option("-colorspace", "bt709");
+}
+
+/// x265 expects chromaticities in 0.00002 and luminance in 0.0001 cd/m² units.
+fn x265_master_display(display: &MasteringDisplay) -> String {
+    let xy = |x: f64, y: f64| {
+        format!(
+            "({},{})",
+            (x / 0.00002).round() as u64,
+            (y / 0.00002).round() as u64
+        )
+    };
+    format!(
+        "G{}B{}R{}WP{}L({},{})",
+        xy(display.green_x, display.green_y),
+        xy(display.blue_x, display.blue_y),
+        xy(display.red_x, display.red_y),
+        xy(display.white_point_x, display.white_point_y),
+        (display.max_luminance / 0.0001).round() as u64,
+        (display.min_luminance / 0.0001).round() as u64,
+    )
+}
+
+/// Output options that keep `info`'s HDR signalling and static metadata.
+/// Returns the `-x265-params` entries, which the encoder merges with its own.
+pub(crate) fn apply_hdr_output(
+    command: &mut FfmpegCommand,
+    codec: VideoCodec,
+    info: Option<&VideoInfo>,
+) -> AppResult<Vec<String>> {
+    if codec != VideoCodec::H265 {
+        return Err(AppError::invalid(
+            "Preserving HDR requires the H.265 codec.",
+        ));
+    }
+    let video = info.and_then(|info| info.video.as_ref());
+    let hdr = hdr_of(info)
+        .ok_or_else(|| AppError::invalid("The primary input has no HDR metadata to preserve."))?;
+    let transfer = video
+        .and_then(|video| video.color_transfer.as_deref())
+        .filter(|transfer| *transfer == "arib-std-b67")
+        .unwrap_or("smpte2084");
+
+    let mut x265 = vec![
+        "hdr-opt=1".to_string(),
+        "repeat-headers=1".to_string(),
+        "colorprim=bt2020".to_string(),
+        format!("transfer={transfer}"),
+        "colormatrix=bt2020nc".to_string(),
+    ];
+    if let Some(display) = &hdr.mastering_display {
+        x265.push(format!("master-display={}", x265_master_display(display)));
+    }
+    if let (Some(cll), Some(fall)) = (hdr.max_cll, hdr.max_fall) {
+        x265.push(format!("max-cll={cll},{fall}"));
+    }
+
+    command.option("-pix_fmt", "yuv420p10le");
+    command.option("-color_primaries", "bt2020");
+    command.option("-color_trc", transfer);
+    command.option("-colorspace", "bt2020nc");
+    Ok(x265)
+}
+
+/// How an input's pixels should be interpreted, with unknown values guessed the
+/// way players do: BT.601 for SD, BT.709 above, limited range unless the pixel
+/// format is a full-range `yuvj` one.
+#[derive(Clone, PartialEq, Eq)]
+struct ColorDescription {
+    /// `colorspace` preset covering matrix, primaries and transfer.
+    preset: &'static str,
+    /// `full` or `limited`.
+    range: &'static str,
+    pix_fmt: Option<String>,
+}
+
+impl ColorDescription {
+    fn of(info: Option<&VideoInfo>) -> Option<Self> {
+        let video = info?.video.as_ref()?;
+        let height = video.height.unwrap_or(u32::MAX);
+        let preset = match video.color_space.as_deref() {
+            Some("bt709") => "bt709",
+            Some("smpte170m") => "bt601-6-525",
+            Some("bt470bg") => "bt601-6-625",
+            Some("bt2020nc" | "bt2020c") => "bt2020",
+            Some("smpte240m") => "smpte240m",
+            Some("fcc") => "bt470m",
+            _ if height <= 480 => "bt601-6-525",
+            _ if height <= 576 => "bt601-6-625",
+            _ => "bt709",
+        };
+        let full_pix_fmt = video
+            .pix_fmt
+            .as_deref()
+            .is_some_and(|pix_fmt| pix_fmt.starts_with("yuvj"));
+        let range = match video.color_range.as_deref() {
+            Some("pc") => "full",
+            Some("tv") => "limited",
+            _ if full_pix_fmt => "full",
+            _ => "limited",
+        };
+        Some(Self {
+            preset,
+            range,
+            pix_fmt: video.pix_fmt.clone(),
+        })
+    }
+
+    fn summary(&self) -> String {
+        format!(
+            "{} {} range {}",
+            self.preset,
+            self.range,
+            self.pix_fmt.as_deref().unwrap_or("unknown pixel format")
+        )
+    }
+}
+
+/// Whether combined inputs are described differently and need normalizing.
+pub(crate) fn inputs_differ(infos: &[Option<&VideoInfo>]) -> bool {
+    let mut descriptions = infos
+        .iter()
+        .map(|info| ColorDescription::of(*info))
+        .filter(Option::is_some);
+    let Some(first) = descriptions.next() else {
+        return false;
+    };
+    descriptions.any(|description| description != first)
+}
+
+/// Filters that convert one SDR input of a composite layout to BT.709
+/// (matrix, primaries and transfer) limited range `yuv420p`, so every branch
+/// shares a color space before stacking. Empty when the inputs already match,
+/// the input is HDR or it cannot be described.
+pub(crate) fn normalize_filters(
+    infos: &[Option<&VideoInfo>],
+    index: usize,
+    tonemapped: bool,
+) -> Vec<String> {
+    // Tonemapping already produces BT.709 limited range.
+    if tonemapped || !inputs_differ(infos) {
+        return Vec::new();
+    }
+    let info = infos.get(index).copied().flatten();
+    // HDR needs tonemapping, not a gamut conversion, to become comparable.
+    if hdr_of(info).is_some() {
+        return Vec::new();
+    }
+    let Some(description) = ColorDescription::of(info) else {
+        return Vec::new();
+    };
+    let range = if description.range == "full" {
+        "pc"
+    } else {
+        "tv"
+    };
+    // The input side is spelled out: `colorspace` refuses untagged frames.
+    vec![format!(
+        "colorspace=all=bt709:range=tv:format={COMMON_PIX_FMT}:iall={}:irange={range}",
+        description.preset
+    )]
+}
+
+/// Whether `normalize_filters` converts this input, given the export's HDR mode.
+fn is_normalized(info: Option<&VideoInfo>, hdr_mode: Option<HdrMode>) -> bool {
+    hdr_of(info).is_none() || hdr_mode == Some(HdrMode::Tonemap)
+}
+
+/// Human-readable notes about inputs that are combined despite differing color
+/// descriptions, for display next to the started export.
+pub(crate) fn color_warnings(
+    infos: &[Option<&VideoInfo>],
+    hdr_mode: Option<HdrMode>,
+) -> Vec<String> {
+    let mut warnings = Vec::new();
+    let hdr_inputs = infos.iter().filter(|info| hdr_of(**info).is_some()).count();
+    if hdr_inputs > 0 && hdr_inputs < infos.len() && hdr_mode != Some(HdrMode::Tonemap) {
+        warnings.push(
+            "HDR and SDR inputs are combined without tonemapping; set the HDR mode to tonemap for a fair comparison."
+                .to_string(),
+        );
+    }
+    if !inputs_differ(infos) {
+        return warnings;
+    }
+    let first_info = infos.first().copied().flatten();
+    let Some(first) = ColorDescription::of(first_info) else {
+        return warnings;
+    };
+    let first_normalized = is_normalized(first_info, hdr_mode);
+    for (index, info) in infos.iter().enumerate().skip(1) {
+        let Some(description) = ColorDescription::of(*info).filter(|d| *d != first) else {
+            continue;
+        };
+        let letter = input_letter(index);
+        let outcome = match (first_normalized, is_normalized(*info, hdr_mode)) {
+            (true, true) => format!("both are converted to BT.709 limited range {COMMON_PIX_FMT}"),
+            (true, false) => {
+                format!("only input A is converted to BT.709, HDR input {letter} is left as is")
+            }
+            (false, true) => {
+                format!("only input {letter} is converted to BT.709, HDR input A is left as is")
+            }
+            (false, false) => "neither is converted because both are HDR".to_string(),
+        };
+        warnings.push(format!(
+            "Input {letter} is {} while input A is {}; {outcome} before combining.",
+            description.summary(),
+            first.summary()
+        ));
+    }
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// A probed video stream with the given color tags; `hdr` adds HDR10 metadata.
+    fn info(height: u32, color_space: Option<&str>, pix_fmt: &str, hdr: bool) -> VideoInfo {
+        serde_json::from_value(json!({
+            "file": "a.mp4",
+            "container": {},
+            "video": {
+                "index": 0,
+                "codec_name": "hevc",
+                "height": height,
+                "pix_fmt": pix_fmt,
+                "color_space": color_space,
+                "hdr": if hdr { json!({ "format": "hdr10", "hdr10_plus": false }) } else { json!(null) },
+            },
+            "subtitles": [],
+            "attachments": [],
+            "streams": [],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn matching_inputs_are_not_normalized() {
+        let a = info(1080, Some("bt709"), "yuv420p", false);
+        let b = info(1080, None, "yuv420p", false);
+        let infos = [Some(&a), Some(&b)];
+        assert!(!inputs_differ(&infos));
+        assert!(normalize_filters(&infos, 1, false).is_empty());
+        assert!(color_warnings(&infos, None).is_empty());
+    }
+
+    #[test]
+    fn sdr_inputs_are_converted_with_colorspace() {
+        let a = info(1080, Some("bt709"), "yuv420p", false);
+        let b = info(576, None, "yuvj420p", false);
+        let infos = [Some(&a), Some(&b)];
+        assert_eq!(
+            normalize_filters(&infos, 0, false),
+            ["colorspace=all=bt709:range=tv:format=yuv420p:iall=bt709:irange=tv"]
+        );
+        assert_eq!(
+            normalize_filters(&infos, 1, false),
+            ["colorspace=all=bt709:range=tv:format=yuv420p:iall=bt601-6-625:irange=pc"]
+        );
+        assert_eq!(
+            color_warnings(&infos, None),
+            ["Input B is bt601-6-625 full range yuvj420p while input A is bt709 limited range yuv420p; \
+              both are converted to BT.709 limited range yuv420p before combining."]
+        );
+    }
+
+    #[test]
+    fn hdr_inputs_are_left_for_tonemapping() {
+        let a = info(1080, Some("bt709"), "yuv420p", false);
+        let b = info(2160, Some("bt2020nc"), "yuv420p10le", true);
+        let infos = [Some(&a), Some(&b)];
+        assert!(normalize_filters(&infos, 1, false).is_empty());
+        let warnings = color_warnings(&infos, None);
+        assert_eq!(warnings.len(), 2);
+        assert!(warnings[1].ends_with(
+            "only input A is converted to BT.709, HDR input B is left as is before combining."
+        ));
+        assert!(color_warnings(&infos, Some(HdrMode::Tonemap))[0].contains("both are converted"));
+    }
+}
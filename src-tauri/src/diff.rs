@@ -0,0 +1,588 @@
+use serde::Serialize;
+
+use crate::probe::{StreamInfo, VideoInfo};
+
+/// Frame rates closer than this (relative) are treated as equal, so 23.976 and
+/// 24000/1001 do not show up as a mismatch.
+const FPS_TOLERANCE: f64 = 0.001;
+/// Durations may differ by this much before the difference is significant;
+/// encoders routinely add or drop a frame or some audio priming.
+const DURATION_TOLERANCE_SEC: f64 = 0.5;
+
+/// How much a difference matters when comparing two encodes of the same source.
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Severity {
+    /// Expected to differ between encodes, e.g. bitrate or file size.
+    Info,
+    /// Worth a look but usually harmless, e.g. a different profile or audio codec.
+    Minor,
+    /// Changes what is seen or heard, e.g. resolution, frame rate or color.
+    Significant,
+}
+
+/// One field whose value differs between A and B.
+#[derive(Serialize, Clone)]
+pub(crate) struct FieldDiff {
+    /// Stable identifier such as `video.resolution`, for the UI to match rows.
+    pub(crate) field: &'static str,
+    pub(crate) label: &'static str,
+    pub(crate) severity: Severity,
+    /// Display value, `None` when the field is missing on that side.
+    pub(crate) a: Option<String>,
+    pub(crate) b: Option<String>,
+    /// Relative change from A to B in percent, for numeric fields.
+    pub(crate) delta_percent: Option<f64>,
+}
+
+struct Differ {
+    diffs: Vec<FieldDiff>,
+}
+
+fn percent_change(a: f64, b: f64) -> Option<f64> {
+    (a != 0.0).then(|| (b - a) / a * 100.0)
+}
+
+impl Differ {
+    fn push(
+        &mut self,
+        field: &'static str,
+        label: &'static str,
+        severity: Severity,
+        a: Option<String>,
+        b: Option<String>,
+        delta_percent: Option<f64>,
+    ) {
+        self.diffs.push(FieldDiff {
+            field,
+            label,
+            severity,
+            a,
+            b,
+            delta_percent,
+        });
+    }
+
+    /// Compares text values; both missing counts as equal.
+    fn text(
+        &mut self,
+        field: &'static str,
+        label: &'static str,
+        severity: Severity,
+        a: Option<&str>,
+        b: Option<&str>,
+    ) {
+        if a != b {
+            self.push(
+                field,
+                label,
+                severity,
+                a.map(str::to_string),
+                b.map(str::to_string),
+                None,
+            );
+        }
+    }
+
+    /// Compares numbers and reports the relative change. Values within
+    /// `tolerance` (relative) are equal.
+    #[allow(clippy::too_many_arguments)]
+    fn number(
+        &mut self,
+        field: &'static str,
+        label: &'static str,
+        severity: Severity,
+        a: Option<f64>,
+        b: Option<f64>,
+        tolerance: f64,
+        format: impl Fn(f64) -> String,
+    ) {
+        match (a, b) {
+            (None, None) => {}
+            (Some(a), Some(b)) => {
+                let delta = percent_change(a, b);
+                let within = match delta {
+                    Some(delta) => delta.abs() <= tolerance * 100.0,
+                    None => a == b,
+                };
+                if !within {
+                    self.push(
+                        field,
+                        label,
+                        severity,
+                        Some(format(a)),
+                        Some(format(b)),
+                        delta,
+                    );
+                }
+            }
+            (a, b) => self.push(field, label, severity, a.map(&format), b.map(&format), None),
+        }
+    }
+
+    /// Compares one property of the streams both files have, paired in file
+    /// order. Each side lists the streams that differ as `#index value`.
+    fn streams(
+        &mut self,
+        field: &'static str,
+        label: &'static str,
+        severity: Severity,
+        (a, b): (&[StreamInfo], &[StreamInfo]),
+        value: impl Fn(&StreamInfo) -> Option<String>,
+    ) {
+        let describe = |stream: &StreamInfo, value: &Option<String>| {
+            format!("#{} {}", stream.index, value.as_deref().unwrap_or("-"))
+        };
+        let (mut in_a, mut in_b) = (Vec::new(), Vec::new());
+        for (stream_a, stream_b) in a.iter().zip(b) {
+            let (value_a, value_b) = (value(stream_a), value(stream_b));
+            if value_a != value_b {
+                in_a.push(describe(stream_a, &value_a));
+                in_b.push(describe(stream_b, &value_b));
+            }
+        }
+        if !in_a.is_empty() {
+            self.push(
+                field,
+                label,
+                severity,
+                Some(in_a.join(", ")),
+                Some(in_b.join(", ")),
+                None,
+            );
+        }
+    }
+}
+
+fn integer(value: f64) -> String {
+    format!("{}", value.round() as i64)
+}
+
+/// Lists the fields that differ between two probes, most severe first and in
+/// table order within a severity.
+pub(crate) fn field_diffs(a: &VideoInfo, b: &VideoInfo) -> Vec<FieldDiff> {
+    use Severity::{Info, Minor, Significant};
+
+    let mut differ = Differ { diffs: Vec::new() };
+    let d = &mut differ;
+    let to_f64 = |value: Option<u64>| value.map(|value| value as f64);
+
+    d.number(
+        "size",
+        "Size",
+        Info,
+        to_f64(a.size_bytes),
+        to_f64(b.size_bytes),
+        0.0,
+        integer,
+    );
+    d.text(
+        "container.format",
+        "Container",
+        Minor,
+        a.container.format_name.as_deref(),
+        b.container.format_name.as_deref(),
+    );
+    let duration_tolerance = a
+        .container
+        .duration_sec
+        .filter(|duration| *duration > 0.0)
+        .map_or(0.0, |duration| DURATION_TOLERANCE_SEC / duration);
+    d.number(
+        "container.duration",
+        "Duration",
+        Significant,
+        a.container.duration_sec,
+        b.container.duration_sec,
+        duration_tolerance,
+        |value| format!("{value:.3} s"),
+    );
+    d.number(
+        "container.bitrate",
+        "Bitrate",
+        Info,
+        to_f64(a.container.bitrate),
+        to_f64(b.container.bitrate),
+        0.0,
+        integer,
+    );
+
+    match (&a.video, &b.video) {
+        (None, None) => {}
+        (Some(_), None) | (None, Some(_)) => d.push(
+            "video.present",
+            "Video",
+            Significant,
+            a.video.as_ref().map(|_| "yes".to_string()),
+            b.video.as_ref().map(|_| "yes".to_string()),
+            None,
+        ),
+        (Some(va), Some(vb)) => {
+            d.text(
+                "video.codec",
+                "Video Codec",
+                Minor,
+                va.codec_name.as_deref(),
+                vb.codec_name.as_deref(),
+            );
+            d.text(
+                "video.profile",
+                "Profile",
+                Info,
+                va.profile.as_deref(),
+                vb.profile.as_deref(),
+            );
+            let resolution = |width: Option<u32>, height: Option<u32>| {
+                width.zip(height).map(|(w, h)| format!("{w}x{h}"))
+            };
+            d.text(
+                "video.resolution",
+                "Resolution",
+                Significant,
+                resolution(va.width, va.height).as_deref(),
+                resolution(vb.width, vb.height).as_deref(),
+            );
+            d.text(
+                "video.pix_fmt",
+                "Pixel Format",
+                Minor,
+                va.pix_fmt.as_deref(),
+                vb.pix_fmt.as_deref(),
+            );
+            d.text(
+                "video.color_space",
+                "Color Space",
+                Significant,
+                va.color_space.as_deref(),
+                vb.color_space.as_deref(),
+            );
+            d.text(
+                "video.color_range",
+                "Color Range",
+                Significant,
+                va.color_range.as_deref(),
+                vb.color_range.as_deref(),
+            );
+            d.text(
+                "video.color_transfer",
+                "Transfer",
+                Significant,
+                va.color_transfer.as_deref(),
+                vb.color_transfer.as_deref(),
+            );
+            d.text(
+                "video.color_primaries",
+                "Primaries",
+                Significant,
+                va.color_primaries.as_deref(),
+                vb.color_primaries.as_deref(),
+            );
+            let hdr = |video: &crate::probe::VideoStreamInfo| {
+                video.hdr.as_ref().map(|hdr| hdr.format.clone())
+            };
+            d.text(
+                "video.hdr",
+                "HDR",
+                Significant,
+                hdr(va).as_deref(),
+                hdr(vb).as_deref(),
+            );
+            d.number(
+                "video.fps",
+                "Frame Rate",
+                Significant,
+                va.fps,
+                vb.fps,
+                FPS_TOLERANCE,
+                |fps| format!("{fps:.3} fps"),
+            );
+            // A frame or two is encoder padding; more is missing content.
+            let frame_gap = va
+                .frame_count
+                .zip(vb.frame_count)
+                .map(|(a, b)| a.abs_diff(b));
+            d.number(
+                "video.frame_count",
+                "Frame Count",
+                if frame_gap.is_some_and(|gap| gap <= 2) {
+                    Minor
+                } else {
+                    Significant
+                },
+                to_f64(va.frame_count),
+                to_f64(vb.frame_count),
+                0.0,
+                integer,
+            );
+            d.number(
+                "video.bitrate",
+                "Video Bitrate",
+                Info,
+                to_f64(va.bit_rate),
+                to_f64(vb.bit_rate),
+                0.0,
+                integer,
+            );
+        }
+    }
+
+    match (&a.audio, &b.audio) {
+        (None, None) => {}
+        (Some(_), None) | (None, Some(_)) => d.push(
+            "audio.present",
+            "Audio",
+            Significant,
+            a.audio.as_ref().map(|_| "yes".to_string()),
+            b.audio.as_ref().map(|_| "yes".to_string()),
+            None,
+        ),
+        (Some(aa), Some(ab)) => {
+            d.text(
+                "audio.codec",
+                "Audio Codec",
+                Minor,
+                aa.codec_name.as_deref(),
+                ab.codec_name.as_deref(),
+            );
+            d.number(
+                "audio.channels",
+                "Channels",
+                Significant,
+                aa.channels.map(f64::from),
+                ab.channels.map(f64::from),
+                0.0,
+                integer,
+            );
+            d.number(
+                "audio.sample_rate",
+                "Sample Rate",
+                Minor,
+                aa.sample_rate.map(f64::from),
+                ab.sample_rate.map(f64::from),
+                0.0,
+                |rate| format!("{} Hz", integer(rate)),
+            );
+            d.text(
+                "audio.language",
+                "Audio Language",
+                Minor,
+                aa.language.as_deref(),
+                ab.language.as_deref(),
+            );
+            d.number(
+                "audio.bitrate",
+                "Audio Bitrate",
+                Info,
+                to_f64(aa.bit_rate),
+                to_f64(ab.bit_rate),
+                0.0,
+                integer,
+            );
+        }
+    }
+
+    d.number(
+        "subtitles.count",
+        "Subtitles",
+        Minor,
+        Some(a.subtitles.len() as f64),
+        Some(b.subtitles.len() as f64),
+        0.0,
+        integer,
+    );
+    d.number(
+        "attachments.count",
+        "Attachments",
+        Info,
+        Some(a.attachments.len() as f64),
+        Some(b.attachments.len() as f64),
+        0.0,
+        integer,
+    );
+    d.number(
+        "streams.count",
+        "Streams",
+        Minor,
+        Some(a.streams.len() as f64),
+        Some(b.streams.len() as f64),
+        0.0,
+        integer,
+    );
+    let streams = (a.streams.as_slice(), b.streams.as_slice());
+    d.streams("streams.codec", "Stream Codecs", Minor, streams, |stream| {
+        let codec = stream.codec_name.as_deref().unwrap_or("-");
+        Some(format!("{} {codec}", stream.codec_type))
+    });
+    d.streams(
+        "streams.language",
+        "Stream Languages",
+        Minor,
+        streams,
+        |stream| stream.language.clone(),
+    );
+    d.streams(
+        "streams.disposition",
+        "Stream Flags",
+        Minor,
+        streams,
+        |stream| (!stream.disposition.is_empty()).then(|| stream.disposition.join("+")),
+    );
+
+    // Stable sort keeps table order within a severity.
+    differ
+        .diffs
+        .sort_by_key(|diff| std::cmp::Reverse(diff.severity));
+    differ.diffs
+}
+
+/// Field-level differences between two probe results, as returned by `probe_video`.
+#[tauri::command]
+pub(crate) fn diff_video_info(a: VideoInfo, b: VideoInfo) -> Vec<FieldDiff> {
+    field_diffs(&a, &b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn info(fps: f64, duration_sec: f64, frame_count: u64) -> VideoInfo {
+        serde_json::from_value(json!({
+            "file": "a.mp4",
+            "size_bytes": 1000,
+            "container": { "format_name": "mov,mp4", "duration_sec": duration_sec },
+            "video": {
+                "index": 0,
+                "codec_name": "h264",
+                "width": 1920,
+                "height": 1080,
+                "fps": fps,
+                "frame_count": frame_count,
+            },
+            "subtitles": [],
+            "attachments": [],
+            "streams": [{ "index": 0, "codec_type": "video", "disposition": [] }],
+        }))
+        .unwrap()
+    }
+
+    fn severities(a: &VideoInfo, b: &VideoInfo) -> Vec<(&'static str, Severity)> {
+        field_diffs(a, b)
+            .iter()
+            .map(|diff| (diff.field, diff.severity))
+            .collect()
+    }
+
+    #[test]
+    fn identical_probes_have_no_differences() {
+        let a = info(25.0, 10.0, 250);
+        assert!(field_diffs(&a, &a.clone()).is_empty());
+    }
+
+    #[test]
+    fn frame_rates_within_tolerance_are_equal() {
+        let a = info(23.976, 10.0, 240);
+        let b = info(24000.0 / 1001.0, 10.0, 240);
+        assert!(field_diffs(&a, &b).is_empty());
+
+        let b = info(25.0, 10.0, 240);
+        let diffs = field_diffs(&a, &b);
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].field, "video.fps");
+        assert_eq!(diffs[0].severity, Severity::Significant);
+        assert_eq!(diffs[0].a.as_deref(), Some("23.976 fps"));
+        assert_eq!(diffs[0].b.as_deref(), Some("25.000 fps"));
+    }
+
+    #[test]
+    fn durations_may_differ_by_half_a_second() {
+        let a = info(25.0, 10.0, 250);
+        assert!(field_diffs(&a, &info(25.0, 10.4, 250)).is_empty());
+        assert!(field_diffs(&a, &info(25.0, 9.6, 250)).is_empty());
+        assert_eq!(
+            severities(&a, &info(25.0, 10.6, 250)),
+            [("container.duration", Severity::Significant)]
+        );
+    }
+
+    #[test]
+    fn frame_gap_sets_the_frame_count_severity() {
+        let a = info(25.0, 10.0, 250);
+        assert_eq!(
+            severities(&a, &info(25.0, 10.0, 252)),
+            [("video.frame_count", Severity::Minor)]
+        );
+        assert_eq!(
+            severities(&a, &info(25.0, 10.0, 247)),
+            [("video.frame_count", Severity::Significant)]
+        );
+    }
+
+    #[test]
+    fn most_severe_differences_come_first() {
+        let a = info(25.0, 10.0, 250);
+        let mut b = info(25.0, 10.0, 251);
+        b.size_bytes = Some(2000);
+        b.video.as_mut().unwrap().width = Some(1280);
+        b.video.as_mut().unwrap().height = Some(720);
+        assert_eq!(
+            severities(&a, &b),
+            [
+                ("video.resolution", Severity::Significant),
+                ("video.frame_count", Severity::Minor),
+                ("size", Severity::Info),
+            ]
+        );
+        let diffs = field_diffs(&a, &b);
+        assert_eq!(diffs[2].delta_percent, Some(100.0));
+    }
+
+    fn with_streams(streams: serde_json::Value) -> VideoInfo {
+        let mut info = info(25.0, 10.0, 250);
+        info.streams = serde_json::from_value(streams).unwrap();
+        info
+    }
+
+    #[test]
+    fn streams_are_compared_by_codec_language_and_flags() {
+        let a = with_streams(json!([
+            { "index": 0, "codec_type": "video", "codec_name": "h264", "disposition": ["default"] },
+            { "index": 1, "codec_type": "audio", "codec_name": "aac", "language": "eng", "disposition": ["default"] },
+            { "index": 2, "codec_type": "subtitle", "codec_name": "subrip", "language": "ger", "disposition": [] },
+        ]));
+        assert!(field_diffs(&a, &a.clone()).is_empty());
+
+        let b = with_streams(json!([
+            { "index": 0, "codec_type": "video", "codec_name": "h264", "disposition": ["default"] },
+            { "index": 1, "codec_type": "audio", "codec_name": "opus", "language": "eng", "disposition": [] },
+            { "index": 2, "codec_type": "subtitle", "codec_name": "subrip", "disposition": ["default", "forced"] },
+        ]));
+        let diffs = field_diffs(&a, &b);
+        let diff = |field: &str| diffs.iter().find(|diff| diff.field == field).unwrap();
+        assert_eq!(diffs.len(), 3);
+        assert_eq!(diff("streams.codec").a.as_deref(), Some("#1 audio aac"));
+        assert_eq!(diff("streams.codec").b.as_deref(), Some("#1 audio opus"));
+        assert_eq!(diff("streams.language").a.as_deref(), Some("#2 ger"));
+        assert_eq!(diff("streams.language").b.as_deref(), Some("#2 -"));
+        assert_eq!(
+            diff("streams.disposition").a.as_deref(),
+            Some("#1 default, #2 -")
+        );
+        assert_eq!(
+            diff("streams.disposition").b.as_deref(),
+            Some("#1 -, #2 default+forced")
+        );
+    }
+
+    #[test]
+    fn extra_streams_only_change_the_count() {
+        let a = with_streams(json!([
+            { "index": 0, "codec_type": "video", "codec_name": "h264", "disposition": [] },
+        ]));
+        let b = with_streams(json!([
+            { "index": 0, "codec_type": "video", "codec_name": "h264", "disposition": [] },
+            { "index": 1, "codec_type": "audio", "codec_name": "aac", "disposition": [] },
+        ]));
+        assert_eq!(severities(&a, &b), [("streams.count", Severity::Minor)]);
+    }
+}
@@ -0,0 +1,548 @@
+use std::path::{Path, PathBuf};
+
+use crate::error::{AppError, AppResult};
+use crate::ffmpeg::FfmpegCommand;
+use crate::ExportParams;
+
+/// Software video encoders an export can use, selected by `ExportParams::codec`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum VideoCodec {
+    H264,
+    H265,
+    Vp9,
+    /// AV1 through SVT-AV1, the faster of the two AV1 encoders.
+    Av1,
+    /// AV1 through libaom, slower but supports two-pass encoding.
+    Av1Aom,
+    ProRes,
+    /// Lossless FFV1, for archival masters.
+    Ffv1,
+}
+
+impl VideoCodec {
+    pub(crate) fn parse(value: &str) -> AppResult<Self> {
+        match value {
+            "h264" => Ok(VideoCodec::H264),
+            "h265" => Ok(VideoCodec::H265),
+            "vp9" => Ok(VideoCodec::Vp9),
+            "av1" => Ok(VideoCodec::Av1),
+            "av1-aom" => Ok(VideoCodec::Av1Aom),
+            "prores" => Ok(VideoCodec::ProRes),
+            "ffv1" => Ok(VideoCodec::Ffv1),
+            _ => Err(AppError::invalid(format!("Unknown codec: {value}."))),
+        }
+    }
+
+    fn encoder(self) -> &'static str {
+        match self {
+            VideoCodec::H264 => "libx264",
+            VideoCodec::H265 => "libx265",
+            VideoCodec::Vp9 => "libvpx-vp9",
+            VideoCodec::Av1 => "libsvtav1",
+            VideoCodec::Av1Aom => "libaom-av1",
+            VideoCodec::ProRes => "prores_ks",
+            VideoCodec::Ffv1 => "ffv1",
+        }
+    }
+
+    fn display_name(self) -> &'static str {
+        match self {
+            VideoCodec::H264 => "H.264",
+            VideoCodec::H265 => "H.265",
+            VideoCodec::Vp9 => "VP9",
+            VideoCodec::Av1 | VideoCodec::Av1Aom => "AV1",
+            VideoCodec::ProRes => "ProRes",
+            VideoCodec::Ffv1 => "FFV1",
+        }
+    }
+
+    /// Output file extensions whose muxer can carry the codec.
+    fn containers(self) -> &'static [&'static str] {
+        match self {
+            VideoCodec::H264 | VideoCodec::H265 => &["mp4", "mov", "mkv"],
+            VideoCodec::Vp9 | VideoCodec::Av1 | VideoCodec::Av1Aom => &["mkv", "webm", "mp4"],
+            VideoCodec::ProRes => &["mov", "mkv"],
+            VideoCodec::Ffv1 => &["mkv"],
+        }
+    }
+
+    /// Extension used when the output path does not name a container.
+    pub(crate) fn default_container(self) -> &'static str {
+        self.containers()[0]
+    }
+
+    /// Valid `-crf` values, or `None` for codecs without a quality-based mode.
+    fn crf_range(self) -> Option<(u8, u8)> {
+        match self {
+            VideoCodec::H264 | VideoCodec::H265 => Some((0, 51)),
+            VideoCodec::Vp9 | VideoCodec::Av1 | VideoCodec::Av1Aom => Some((0, 63)),
+            VideoCodec::ProRes | VideoCodec::Ffv1 => None,
+        }
+    }
+
+    fn supports_two_pass(self) -> bool {
+        matches!(
+            self,
+            VideoCodec::H264 | VideoCodec::H265 | VideoCodec::Vp9 | VideoCodec::Av1Aom
+        )
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum RateControl {
+    /// Constant quality from `crf`.
+    Crf,
+    /// Constant quality capped by `maxrate_kbps`/`bufsize_kbps`.
+    ConstrainedVbr,
+    /// Average bitrate from `bitrate_kbps`, optionally in two passes.
+    Abr,
+}
+
+/// Codec, rate control and pass count resolved and validated from the params.
+pub(crate) struct EncoderSettings {
+    pub(crate) codec: VideoCodec,
+    rate_control: RateControl,
+    pub(crate) two_pass: bool,
+    container: Option<String>,
+}
+
+/// One pass of a two-pass encode; both share the stats file prefix `log`.
+pub(crate) struct Pass<'a> {
+    pub(crate) number: u8,
+    pub(crate) log: &'a Path,
+}
+
+fn container_of(output_path: &str) -> Option<String> {
+    Path::new(output_path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_ascii_lowercase())
+}
+
+impl EncoderSettings {
+    /// Checks the codec against the output container and the rate control
+    /// options against the codec, before anything is spawned.
+    pub(crate) fn from_params(params: &ExportParams, output_path: &str) -> AppResult<Self> {
+        let codec = VideoCodec::parse(&params.codec)?;
+        let container = container_of(output_path);
+        if let Some(container) = container.as_deref() {
+            if !codec.containers().contains(&container) {
+                return Err(AppError::invalid(format!(
+                    "{} cannot be written to .{container} files; use {}.",
+                    codec.display_name(),
+                    codec
+                        .containers()
+                        .iter()
+                        .map(|ext| format!(".{ext}"))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )));
+            }
+        }
+
+        let rate_control = match params.rate_control.as_deref().map(str::trim) {
+            None | Some("") | Some("crf") => RateControl::Crf,
+            Some("cvbr") => RateControl::ConstrainedVbr,
+            Some("abr") => RateControl::Abr,
+            Some(other) => {
+                return Err(AppError::invalid(format!(
+                    "Unknown rate control mode: {other}."
+                )))
+            }
+        };
+        // Only `abr` reads the target bitrate; anywhere else it would be ignored.
+        if rate_control != RateControl::Abr && params.bitrate_kbps.unwrap_or(0) > 0 {
+            return Err(AppError::invalid(
+                "A target bitrate needs the average bitrate mode.",
+            ));
+        }
+        match (codec.crf_range(), rate_control) {
+            (None, RateControl::Crf) => {}
+            (None, _) => {
+                return Err(AppError::invalid(format!(
+                    "{} does not support bitrate control.",
+                    codec.display_name()
+                )))
+            }
+            (Some((min, max)), RateControl::Crf | RateControl::ConstrainedVbr) => {
+                if !(min..=max).contains(&params.crf) {
+                    return Err(AppError::invalid(format!(
+                        "CRF for {} must be between {min} and {max}.",
+                        codec.display_name()
+                    )));
+                }
+            }
+            (Some(_), RateControl::Abr) => {}
+        }
+        if rate_control == RateControl::Abr && params.bitrate_kbps.unwrap_or(0) == 0 {
+            return Err(AppError::invalid(
+                "Average bitrate mode needs a target bitrate.",
+            ));
+        }
+        if rate_control == RateControl::ConstrainedVbr && params.maxrate_kbps.unwrap_or(0) == 0 {
+            return Err(AppError::invalid(
+                "Constrained VBR needs a maximum bitrate.",
+            ));
+        }
+        if params.two_pass {
+            if rate_control != RateControl::Abr {
+                return Err(AppError::invalid(
+                    "Two-pass encoding is only available with an average bitrate.",
+                ));
+            }
+            if !codec.supports_two_pass() {
+                return Err(AppError::invalid(format!(
+                    "Two-pass encoding is not supported for {} ({}).",
+                    codec.display_name(),
+                    codec.encoder()
+                )));
+            }
+        }
+
+        Ok(Self {
+            codec,
+            rate_control,
+            two_pass: params.two_pass,
+            container,
+        })
+    }
+
+    /// Audio encoder used when audio is not copied; WebM only takes Opus or Vorbis.
+    pub(crate) fn audio_encoder(&self) -> &'static str {
+        match self.container.as_deref() {
+            Some("webm") => "libopus",
+            _ => "aac",
+        }
+    }
+
+    /// Adds `-c:v` and every encoder option. `x265_params` are merged into the
+    /// single `-x265-params` option libx265 accepts, e.g. HDR metadata.
+    pub(crate) fn apply(
+        &self,
+        command: &mut FfmpegCommand,
+        params: &ExportParams,
+        pass: Option<&Pass>,
+        mut x265_params: Vec<String>,
+    ) {
+        let codec = self.codec;
+        command.option("-c:v", codec.encoder());
+
+        let text = |value: &Option<String>| {
+            value
+                .as_deref()
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+                .map(str::to_string)
+        };
+        let preset = text(&params.preset);
+        let tune = text(&params.tune);
+        let profile = text(&params.profile);
+        let level = text(&params.level);
+
+        match codec {
+            VideoCodec::H264 | VideoCodec::H265 => {
+                if let Some(preset) = preset {
+                    command.option("-preset", preset);
+                }
+                if let Some(tune) = tune {
+                    command.option("-tune", tune);
+                }
+                if let Some(profile) = profile {
+                    command.option("-profile:v", profile);
+                }
+                if let Some(level) = level {
+                    if codec == VideoCodec::H265 {
+                        x265_params.push(format!("level-idc={level}"));
+                    } else {
+                        command.option("-level", level);
+                    }
+                }
+            }
+            VideoCodec::Vp9 | VideoCodec::Av1Aom => {
+                // Both take speed as -cpu-used; higher is faster.
+                if codec == VideoCodec::Vp9 {
+                    command.option("-deadline", "good");
+                    command.option("-row-mt", "1");
+                }
+                if let Some(preset) = preset {
+                    command.option("-cpu-used", preset);
+                }
+                if let Some(tune) = tune {
+                    command.option("-tune", tune);
+                }
+                if let Some(profile) = profile {
+                    command.option("-profile:v", profile);
+                }
+            }
+            VideoCodec::Av1 => {
+                if let Some(preset) = preset {
+                    command.option("-preset", preset);
+                }
+                if let Some(profile) = profile {
+                    command.option("-profile:v", profile);
+                }
+            }
+            VideoCodec::ProRes => {
+                // prores_ks profiles: proxy, lt, standard, hq, 4444, 4444xq.
+                let profile = profile.unwrap_or_else(|| "hq".to_string());
+                let pix_fmt = if profile.starts_with("4444") {
+                    "yuva444p10le"
+                } else {
+                    "yuv422p10le"
+                };
+                command.option("-profile:v", profile);
+                command.option("-pix_fmt", pix_fmt);
+                command.option("-vendor", "apl0");
+            }
+            VideoCodec::Ffv1 => {
+                command.option("-level", "3");
+                command.option("-g", "1");
+                command.option("-slicecrc", "1");
+            }
+        }
+
+        if codec.crf_range().is_some() {
+            match self.rate_control {
+                RateControl::Crf => {
+                    command.option("-crf", params.crf.to_string());
+                    // libvpx and libaom only use constant quality when the
+                    // bitrate is explicitly unlimited.
+                    if matches!(codec, VideoCodec::Vp9 | VideoCodec::Av1Aom) {
+                        command.option("-b:v", "0");
+                    }
+                }
+                RateControl::ConstrainedVbr => {
+                    let maxrate = params.maxrate_kbps.unwrap_or_default();
+                    let bufsize = params.bufsize_kbps.unwrap_or(maxrate * 2);
+                    command.option("-crf", params.crf.to_string());
+                    if matches!(codec, VideoCodec::Vp9 | VideoCodec::Av1Aom) {
+                        // Constrained quality: -b:v is the ceiling.
+                        command.option("-b:v", format!("{maxrate}k"));
+                    } else {
+                        command.option("-maxrate", format!("{maxrate}k"));
+                        command.option("-bufsize", format!("{bufsize}k"));
+                    }
+                }
+                RateControl::Abr => {
+                    let bitrate = params.bitrate_kbps.unwrap_or_default();
+                    command.option("-b:v", format!("{bitrate}k"));
+                    if let Some(maxrate) = params.maxrate_kbps.filter(|rate| *rate > 0) {
+                        command.option("-maxrate", format!("{maxrate}k"));
+                        command.option(
+                            "-bufsize",
+                            format!("{}k", params.bufsize_kbps.unwrap_or(maxrate * 2)),
+                        );
+                    }
+                }
+            }
+        }
+
+        if let Some(pass) = pass {
+            let log = pass.log.to_string_lossy().to_string();
+            if codec == VideoCodec::H265 {
+                // libx265 ignores -pass; its stats file is set through x265-params.
+                x265_params.push(format!("pass={}", pass.number));
+                x265_params.push(format!("stats={}", log.replace(':', "\\:")));
+            } else {
+                command.option("-pass", pass.number.to_string());
+                command.option("-passlogfile", log);
+            }
+        }
+
+        if codec == VideoCodec::H265 && !x265_params.is_empty() {
+            command.option("-x265-params", x265_params.join(":"));
+        }
+    }
+}
+
+/// Stats file prefix for a two-pass export, in the temp directory.
+pub(crate) fn pass_log_path(export_id: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("pixelduel-pass-{export_id}"))
+}
+
+/// Removes the stats files both passes leave behind (`-0.log`, `.mbtree`, ...).
+pub(crate) fn remove_pass_logs(log: &Path) {
+    let (Some(dir), Some(prefix)) = (log.parent(), log.file_name()) else {
+        return;
+    };
+    let prefix = prefix.to_string_lossy().to_string();
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        if entry.file_name().to_string_lossy().starts_with(&prefix) {
+            let _ = std::fs::remove_file(entry.path());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn params(overrides: serde_json::Value) -> ExportParams {
+        let mut value = json!({
+            "exportMode": "input-a",
+            "outputPath": "out.mp4",
+            "codec": "h264",
+            "crf": 23,
+            "keepAspect": true,
+            "audioCopy": false,
+        });
+        for (key, field) in overrides.as_object().unwrap() {
+            value[key] = field.clone();
+        }
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn settings(overrides: serde_json::Value) -> AppResult<EncoderSettings> {
+        let params = params(overrides);
+        EncoderSettings::from_params(&params, &params.output_path)
+    }
+
+    fn args(overrides: serde_json::Value, pass: Option<&Pass>) -> String {
+        let params = params(overrides);
+        let settings = EncoderSettings::from_params(&params, &params.output_path).unwrap();
+        let mut command = FfmpegCommand::new("ffmpeg");
+        settings.apply(&mut command, &params, pass, Vec::new());
+        command.args().join(" ")
+    }
+
+    fn is_invalid(result: AppResult<EncoderSettings>) -> bool {
+        matches!(result, Err(AppError::InvalidParams { .. }))
+    }
+
+    #[test]
+    fn codecs_are_checked_against_the_container() {
+        for (codec, ok, rejected) in [
+            ("h264", "out.MOV", "out.webm"),
+            ("vp9", "out.webm", "out.mov"),
+            ("av1-aom", "out.mkv", "out.mov"),
+            ("prores", "out.mov", "out.mp4"),
+            ("ffv1", "out.mkv", "out.mp4"),
+        ] {
+            let crf = if codec == "h264" { 23 } else { 30 };
+            assert!(settings(json!({ "codec": codec, "crf": crf, "outputPath": ok })).is_ok());
+            assert!(is_invalid(settings(
+                json!({ "codec": codec, "crf": crf, "outputPath": rejected })
+            )));
+        }
+        // Without an extension ffmpeg picks the muxer, so anything goes.
+        assert!(settings(json!({ "codec": "ffv1", "outputPath": "out" })).is_ok());
+        assert!(is_invalid(settings(json!({ "codec": "mpeg2" }))));
+    }
+
+    #[test]
+    fn every_codec_defaults_to_a_container_it_supports() {
+        for codec in ["h264", "h265", "vp9", "av1", "av1-aom", "prores", "ffv1"] {
+            let codec = VideoCodec::parse(codec).unwrap();
+            assert!(codec.containers().contains(&codec.default_container()));
+        }
+        assert_eq!(VideoCodec::Vp9.default_container(), "mkv");
+    }
+
+    #[test]
+    fn crf_is_checked_against_the_codec_range() {
+        for (codec, max, output) in [
+            ("h264", 51, "out.mp4"),
+            ("h265", 51, "out.mp4"),
+            ("vp9", 63, "out.webm"),
+            ("av1", 63, "out.mkv"),
+        ] {
+            let crf = |crf: u32| json!({ "codec": codec, "crf": crf, "outputPath": output });
+            assert!(settings(crf(0)).is_ok());
+            assert!(settings(crf(max)).is_ok());
+            assert!(is_invalid(settings(crf(max + 1))), "{codec}");
+        }
+        // ProRes and FFV1 have no quality scale; the CRF is not used.
+        assert!(settings(json!({ "codec": "prores", "crf": 99, "outputPath": "out.mov" })).is_ok());
+    }
+
+    #[test]
+    fn rate_control_options_must_agree() {
+        assert!(is_invalid(settings(json!({ "bitrateKbps": 4000 }))));
+        assert!(is_invalid(settings(
+            json!({ "rateControl": "cvbr", "maxrateKbps": 6000, "bitrateKbps": 4000 })
+        )));
+        assert!(settings(json!({ "rateControl": "crf", "bitrateKbps": 0 })).is_ok());
+        assert!(is_invalid(settings(json!({ "rateControl": "abr" }))));
+        assert!(is_invalid(settings(json!({ "rateControl": "cvbr" }))));
+        assert!(is_invalid(settings(json!({ "rateControl": "vbr" }))));
+        assert!(is_invalid(settings(json!({ "twoPass": true }))));
+        assert!(is_invalid(settings(json!({
+            "codec": "prores",
+            "outputPath": "out.mov",
+            "rateControl": "abr",
+            "bitrateKbps": 4000,
+        }))));
+        assert!(is_invalid(settings(json!({
+            "codec": "av1",
+            "outputPath": "out.mkv",
+            "rateControl": "abr",
+            "bitrateKbps": 4000,
+            "twoPass": true,
+        }))));
+        let abr = settings(json!({ "rateControl": "abr", "bitrateKbps": 4000, "twoPass": true }));
+        assert!(abr.is_ok_and(|settings| settings.two_pass));
+    }
+
+    #[test]
+    fn libvpx_and_libaom_need_an_unlimited_bitrate_for_crf() {
+        for codec in ["vp9", "av1-aom"] {
+            let crf = json!({ "codec": codec, "crf": 30, "outputPath": "out.mkv" });
+            assert!(args(crf, None).ends_with("-crf 30 -b:v 0"), "{codec}");
+            let cvbr = json!({
+                "codec": codec,
+                "crf": 30,
+                "outputPath": "out.mkv",
+                "rateControl": "cvbr",
+                "maxrateKbps": 6000,
+            });
+            assert!(args(cvbr, None).ends_with("-crf 30 -b:v 6000k"), "{codec}");
+        }
+        let svt = args(
+            json!({ "codec": "av1", "crf": 30, "outputPath": "out.mkv" }),
+            None,
+        );
+        assert!(svt.ends_with("-crf 30"));
+        assert!(!args(json!({}), None).contains("-b:v"));
+    }
+
+    #[test]
+    fn x265_takes_its_pass_through_x265_params() {
+        let log = Path::new("C:/temp/pixelduel-pass-id");
+        let abr = |codec: &str| json!({ "codec": codec, "rateControl": "abr", "bitrateKbps": 4000, "twoPass": true });
+        let first = Pass { number: 1, log };
+        let x265 = args(abr("h265"), Some(&first));
+        assert!(x265.ends_with("-x265-params pass=1:stats=C\\:/temp/pixelduel-pass-id"));
+        assert!(!x265.contains("-pass "));
+        assert!(!x265.contains("-passlogfile"));
+
+        let second = Pass { number: 2, log };
+        let x264 = args(abr("h264"), Some(&second));
+        assert!(x264.ends_with("-pass 2 -passlogfile C:/temp/pixelduel-pass-id"));
+        assert!(!x264.contains("-x265-params"));
+    }
+
+    #[test]
+    fn x265_params_are_merged_into_one_option() {
+        let params = params(json!({ "codec": "h265", "level": "5.1" }));
+        let settings = EncoderSettings::from_params(&params, "out.mp4").unwrap();
+        let mut command = FfmpegCommand::new("ffmpeg");
+        let log = Path::new("stats");
+        let pass = Pass { number: 2, log };
+        settings.apply(
+            &mut command,
+            &params,
+            Some(&pass),
+            vec!["hdr10=1".to_string()],
+        );
+        let args = command.args();
+        assert_eq!(args.iter().filter(|arg| *arg == "-x265-params").count(), 1);
+        assert_eq!(
+            args.last().unwrap(),
+            "hdr10=1:level-idc=5.1:pass=2:stats=stats"
+        );
+    }
+}
@@ -0,0 +1,144 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// Error returned by every command. Serialized as `{ "kind": ..., "message": ... }`
+/// so the frontend can branch on `kind` and still show `message` as-is.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub(crate) enum AppError {
+    /// ffmpeg or ffprobe could not be located.
+    BinaryNotFound { message: String },
+    /// The request itself is invalid, e.g. a missing input or unknown mode.
+    InvalidParams { message: String },
+    /// ffprobe failed or returned output that could not be parsed, or ffmpeg
+    /// could not decode a frame for inspection.
+    Probe { message: String },
+    /// A child process could not be started.
+    SpawnFailed { message: String },
+    /// A file could not be read or written.
+    Io { message: String },
+    /// The referenced job, preset or file does not exist.
+    NotFound { message: String },
+    /// The operation is not allowed in the current state, e.g. pausing a running job.
+    InvalidState { message: String },
+    /// Internal failures such as a poisoned lock.
+    Internal { message: String },
+}
+
+pub(crate) type AppResult<T> = Result<T, AppError>;
+
+impl AppError {
+    pub(crate) fn binary_not_found(message: impl Into<String>) -> Self {
+        AppError::BinaryNotFound {
+            message: message.into(),
+        }
+    }
+
+    pub(crate) fn invalid(message: impl Into<String>) -> Self {
+        AppError::InvalidParams {
+            message: message.into(),
+        }
+    }
+
+    pub(crate) fn probe(message: impl Into<String>) -> Self {
+        AppError::Probe {
+            message: message.into(),
+        }
+    }
+
+    pub(crate) fn spawn(message: impl Into<String>) -> Self {
+        AppError::SpawnFailed {
+            message: message.into(),
+        }
+    }
+
+    pub(crate) fn io(message: impl Into<String>) -> Self {
+        AppError::Io {
+            message: message.into(),
+        }
+    }
+
+    pub(crate) fn not_found(message: impl Into<String>) -> Self {
+        AppError::NotFound {
+            message: message.into(),
+        }
+    }
+
+    pub(crate) fn invalid_state(message: impl Into<String>) -> Self {
+        AppError::InvalidState {
+            message: message.into(),
+        }
+    }
+
+    pub(crate) fn lock() -> Self {
+        AppError::Internal {
+            message: "Lock error".to_string(),
+        }
+    }
+
+    pub(crate) fn message(&self) -> &str {
+        match self {
+            AppError::BinaryNotFound { message }
+            | AppError::InvalidParams { message }
+            | AppError::Probe { message }
+            | AppError::SpawnFailed { message }
+            | AppError::Io { message }
+            | AppError::NotFound { message }
+            | AppError::InvalidState { message }
+            | AppError::Internal { message } => message,
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl std::error::Error for AppError {}
+
+/// How a tracked ffmpeg run ended. Sent on the final progress event and passed
+/// to finish hooks.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub(crate) enum ExportOutcome {
+    Succeeded {
+        output_size_bytes: Option<u64>,
+        elapsed_sec: f64,
+    },
+    Failed {
+        exit_code: Option<i32>,
+        /// Last lines of ffmpeg's stderr.
+        stderr_tail: Option<String>,
+    },
+    /// Stopped through `cancel_export` or `cancel_job`.
+    Cancelled,
+    SpawnFailed {
+        message: String,
+    },
+}
+
+impl ExportOutcome {
+    pub(crate) fn succeeded(&self) -> bool {
+        matches!(self, ExportOutcome::Succeeded { .. })
+    }
+
+    /// Short human-readable description for job lists and status lines.
+    pub(crate) fn summary(&self) -> Option<String> {
+        match self {
+            ExportOutcome::Succeeded { .. } => None,
+            ExportOutcome::Failed {
+                exit_code,
+                stderr_tail,
+            } => Some(match (stderr_tail, exit_code) {
+                (Some(tail), _) => tail.clone(),
+                (None, Some(code)) => format!("ffmpeg exited with code {code}."),
+                (None, None) => "ffmpeg was terminated.".to_string(),
+            }),
+            ExportOutcome::Cancelled => Some("Cancelled.".to_string()),
+            ExportOutcome::SpawnFailed { message } => Some(message.clone()),
+        }
+    }
+}
@@ -0,0 +1,348 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// One `[in]filter,filter[out]` segment of a `-filter_complex` graph.
+#[derive(Clone, Default)]
+pub(crate) struct FilterChain {
+    inputs: Vec<String>,
+    filters: Vec<String>,
+    outputs: Vec<String>,
+}
+
+impl FilterChain {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an input pad, e.g. `0:v` or a label produced by an earlier chain.
+    pub(crate) fn input(mut self, label: impl Into<String>) -> Self {
+        self.inputs.push(label.into());
+        self
+    }
+
+    pub(crate) fn filter(mut self, filter: impl Into<String>) -> Self {
+        self.filters.push(filter.into());
+        self
+    }
+
+    pub(crate) fn filters<I, S>(mut self, filters: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.filters.extend(filters.into_iter().map(Into::into));
+        self
+    }
+
+    pub(crate) fn output(mut self, label: impl Into<String>) -> Self {
+        self.outputs.push(label.into());
+        self
+    }
+}
+
+impl fmt::Display for FilterChain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for label in &self.inputs {
+            write!(f, "[{label}]")?;
+        }
+        if self.filters.is_empty() {
+            f.write_str("null")?;
+        } else {
+            f.write_str(&self.filters.join(","))?;
+        }
+        for label in &self.outputs {
+            write!(f, "[{label}]")?;
+        }
+        Ok(())
+    }
+}
+
+/// A `-filter_complex` graph made of labeled chains joined with `;`.
+#[derive(Clone, Default)]
+pub(crate) struct FilterGraph {
+    chains: Vec<FilterChain>,
+}
+
+impl FilterGraph {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn push(&mut self, chain: FilterChain) {
+        self.chains.push(chain);
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.chains.is_empty()
+    }
+}
+
+impl fmt::Display for FilterGraph {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, chain) in self.chains.iter().enumerate() {
+            if index > 0 {
+                f.write_str(";")?;
+            }
+            write!(f, "{chain}")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+struct Input {
+    options: Vec<String>,
+    path: String,
+}
+
+/// Arguments for a single ffmpeg run, in the order ffmpeg expects them:
+/// global options, inputs, filters, maps, output options and the output.
+#[derive(Clone)]
+pub(crate) struct FfmpegCommand {
+    program: PathBuf,
+    global_options: Vec<String>,
+    inputs: Vec<Input>,
+    filter_graph: Option<FilterGraph>,
+    video_filters: Vec<String>,
+    maps: Vec<String>,
+    output_options: Vec<String>,
+    output: Option<String>,
+}
+
+impl FfmpegCommand {
+    pub(crate) fn new(program: impl Into<PathBuf>) -> Self {
+        Self {
+            program: program.into(),
+            global_options: Vec::new(),
+            inputs: Vec::new(),
+            filter_graph: None,
+            video_filters: Vec::new(),
+            maps: Vec::new(),
+            output_options: Vec::new(),
+            output: None,
+        }
+    }
+
+    pub(crate) fn program(&self) -> &Path {
+        &self.program
+    }
+
+    pub(crate) fn global(&mut self, arg: impl Into<String>) {
+        self.global_options.push(arg.into());
+    }
+
+    /// Adds `-i path` and returns the input's index for use in stream specifiers.
+    pub(crate) fn input(&mut self, path: impl Into<String>) -> usize {
+        self.input_with_options(Vec::<String>::new(), path)
+    }
+
+    /// Adds an input preceded by input options such as `-ss`.
+    pub(crate) fn input_with_options<I, S>(&mut self, options: I, path: impl Into<String>) -> usize
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.inputs.push(Input {
+            options: options.into_iter().map(Into::into).collect(),
+            path: path.into(),
+        });
+        self.inputs.len() - 1
+    }
+
+    pub(crate) fn filter_graph(&mut self, graph: FilterGraph) {
+        self.filter_graph = Some(graph).filter(|graph| !graph.is_empty());
+    }
+
+    /// Appends filters to the simple `-vf` chain used when there is no complex graph.
+    pub(crate) fn video_filters<I, S>(&mut self, filters: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.video_filters
+            .extend(filters.into_iter().map(Into::into));
+    }
+
+    pub(crate) fn map(&mut self, specifier: impl Into<String>) {
+        self.maps.push(specifier.into());
+    }
+
+    /// Adds an output option followed by its value, e.g. `-crf 23`.
+    pub(crate) fn option(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        self.output_options.push(key.into());
+        self.output_options.push(value.into());
+    }
+
+    /// Adds a valueless output option such as `-an`.
+    pub(crate) fn flag(&mut self, key: impl Into<String>) {
+        self.output_options.push(key.into());
+    }
+
+    pub(crate) fn output(&mut self, path: impl Into<String>) {
+        self.output = Some(path.into());
+    }
+
+    pub(crate) fn args(&self) -> Vec<String> {
+        let mut args = self.global_options.clone();
+        for input in &self.inputs {
+            args.extend(input.options.iter().cloned());
+            args.push("-i".to_string());
+            args.push(input.path.clone());
+        }
+        if let Some(graph) = &self.filter_graph {
+            args.push("-filter_complex".to_string());
+            args.push(graph.to_string());
+        }
+        if !self.video_filters.is_empty() {
+            args.push("-vf".to_string());
+            args.push(self.video_filters.join(","));
+        }
+        for map in &self.maps {
+            args.push("-map".to_string());
+            args.push(map.clone());
+        }
+        args.extend(self.output_options.iter().cloned());
+        if let Some(output) = &self.output {
+            args.push(output.clone());
+        }
+        args
+    }
+}
+
+/// Renders the command the way it would be typed into a shell, for display in the UI.
+impl fmt::Display for FfmpegCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&format_arg(self.program.to_string_lossy().as_ref()))?;
+        for arg in self.args() {
+            write!(f, " {}", format_arg(&arg))?;
+        }
+        Ok(())
+    }
+}
+
+fn format_arg(value: &str) -> String {
+    if value.contains(' ') {
+        format!("\"{}\"", value.replace('"', "\\\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filter_chain_display() {
+        let chain = FilterChain::new()
+            .input("0:v")
+            .input("1:v")
+            .filter("hstack=inputs=2")
+            .output("vout");
+        assert_eq!(chain.to_string(), "[0:v][1:v]hstack=inputs=2[vout]");
+
+        let chain = FilterChain::new()
+            .input("0:v")
+            .filters(["fps=fps=30", "format=yuv420p"]);
+        assert_eq!(chain.to_string(), "[0:v]fps=fps=30,format=yuv420p");
+    }
+
+    #[test]
+    fn empty_filter_chain_passes_through_with_null() {
+        let chain = FilterChain::new().input("0:v").output("in0");
+        assert_eq!(chain.to_string(), "[0:v]null[in0]");
+        assert_eq!(FilterChain::new().to_string(), "null");
+    }
+
+    #[test]
+    fn filter_graph_joins_chains() {
+        let mut graph = FilterGraph::new();
+        assert!(graph.is_empty());
+        graph.push(
+            FilterChain::new()
+                .input("0:v")
+                .filter("scale=640:-2")
+                .output("a"),
+        );
+        graph.push(
+            FilterChain::new()
+                .input("a")
+                .input("1:v")
+                .filter("vstack")
+                .output("vout"),
+        );
+        assert_eq!(
+            graph.to_string(),
+            "[0:v]scale=640:-2[a];[a][1:v]vstack[vout]"
+        );
+    }
+
+    #[test]
+    fn args_follow_ffmpeg_order() {
+        let mut command = FfmpegCommand::new("ffmpeg");
+        // Added out of order on purpose: args() must still sort them into
+        // globals, inputs, filters, maps, output options and output.
+        command.output("out.mp4");
+        command.option("-c:v", "libx264");
+        command.map("[vout]");
+        let mut graph = FilterGraph::new();
+        graph.push(
+            FilterChain::new()
+                .input("0:v")
+                .input("1:v")
+                .filter("hstack")
+                .output("vout"),
+        );
+        command.filter_graph(graph);
+        assert_eq!(command.input("a.mp4"), 0);
+        assert_eq!(command.input_with_options(["-ss", "1.000000"], "b.mp4"), 1);
+        command.global("-hide_banner");
+        command.flag("-an");
+
+        assert_eq!(
+            command.args(),
+            [
+                "-hide_banner",
+                "-i",
+                "a.mp4",
+                "-ss",
+                "1.000000",
+                "-i",
+                "b.mp4",
+                "-filter_complex",
+                "[0:v][1:v]hstack[vout]",
+                "-map",
+                "[vout]",
+                "-c:v",
+                "libx264",
+                "-an",
+                "out.mp4",
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_graph_is_dropped_and_video_filters_join() {
+        let mut command = FfmpegCommand::new("ffmpeg");
+        command.input("a.mp4");
+        command.filter_graph(FilterGraph::new());
+        command.video_filters(["fps=fps=30"]);
+        command.video_filters(["scale=1280:-1"]);
+        command.output("out.mp4");
+        assert_eq!(
+            command.args(),
+            ["-i", "a.mp4", "-vf", "fps=fps=30,scale=1280:-1", "out.mp4"]
+        );
+    }
+
+    #[test]
+    fn display_quotes_arguments_with_spaces() {
+        let mut command = FfmpegCommand::new("/opt/my tools/ffmpeg");
+        command.input("clip \"a\".mp4");
+        command.output("out.mp4");
+        assert_eq!(
+            command.to_string(),
+            r#""/opt/my tools/ffmpeg" -i "clip \"a\".mp4" out.mp4"#
+        );
+    }
+}
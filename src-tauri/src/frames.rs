@@ -0,0 +1,413 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::cache::ProbeCache;
+use crate::error::{AppError, AppResult};
+use crate::ffmpeg::FfmpegCommand;
+use crate::probe::VideoInfo;
+use crate::{frame_boundary_sec, resolve_bundled_binary, tool_command, unique_output_path};
+
+const DEFAULT_SHEET_COLUMNS: u32 = 4;
+const DEFAULT_SHEET_ROWS: u32 = 4;
+const DEFAULT_TILE_WIDTH: u32 = 320;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct FrameParams {
+    input_path: String,
+    /// Frame number as shown in the UI, converted with the probed frame rate
+    /// like trim frames are. Set this or `time_sec`.
+    frame_index: Option<u64>,
+    time_sec: Option<f64>,
+    /// PNG file to write.
+    output_path: String,
+    /// Scales the frame to this width; the native size by default.
+    width: Option<u32>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct FramePairParams {
+    input_path_a: String,
+    input_path_b: String,
+    /// Converted to a timestamp with A's frame rate; B is taken at the same time.
+    frame_index: Option<u64>,
+    time_sec: Option<f64>,
+    /// Folder for the two PNG files, named after the inputs and the position.
+    output_dir: String,
+    width: Option<u32>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ContactSheetParams {
+    input_path: String,
+    /// PNG file to write.
+    output_path: String,
+    /// Grid size; frames are spread evenly over the whole file. Defaults to 4x4.
+    columns: Option<u32>,
+    rows: Option<u32>,
+    /// Width of each tile in pixels. Defaults to 320.
+    tile_width: Option<u32>,
+}
+
+#[derive(Serialize)]
+pub(crate) struct ExtractedFrame {
+    path: String,
+    time_sec: f64,
+    /// Frame number at `time_sec`, when the frame rate is known.
+    frame_index: Option<u64>,
+}
+
+#[derive(Serialize)]
+pub(crate) struct ExtractedFramePair {
+    a: ExtractedFrame,
+    b: ExtractedFrame,
+}
+
+/// Decodes the frame shown at `time_sec` as PNG, scaled to `width` when set.
+pub(crate) fn grab_frame(
+    ffmpeg: &Path,
+    path: &str,
+    time_sec: f64,
+    width: Option<u32>,
+) -> AppResult<Vec<u8>> {
+    let mut command = FfmpegCommand::new(ffmpeg);
+    command.global("-hide_banner");
+    // Input seeking decodes from the preceding keyframe and drops frames up to
+    // the timestamp, so the result is exact rather than the nearest keyframe.
+    command.input_with_options(["-ss", &format!("{time_sec:.6}")], path);
+    command.option("-frames:v", "1");
+    if let Some(width) = width {
+        command.video_filters([format!("scale={width}:-2")]);
+    }
+    command.option("-f", "image2pipe");
+    command.option("-c:v", "png");
+    command.output("-");
+
+    let output = tool_command(command.program())
+        .args(command.args())
+        .output()
+        .map_err(|e| AppError::spawn(format!("Failed to run ffmpeg: {}", e)))?;
+    if !output.status.success() || output.stdout.is_empty() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(AppError::probe(format!(
+            "No frame at {time_sec:.3}s in {path}: {}",
+            stderr.lines().last().unwrap_or("ffmpeg returned no image")
+        )));
+    }
+    Ok(output.stdout)
+}
+
+fn frame_rate(info: &VideoInfo) -> Option<f64> {
+    info.video
+        .as_ref()
+        .and_then(|video| video.fps)
+        .filter(|fps| *fps > 0.0)
+}
+
+/// Where a requested frame is in an input.
+#[derive(Clone, Copy)]
+struct FramePosition {
+    /// Passed to `-ss`. For a frame index this is halfway after the previous
+    /// frame, so rounding in either direction still lands on the frame.
+    seek_sec: f64,
+    /// The frame's own timestamp.
+    time_sec: f64,
+    frame_index: Option<u64>,
+}
+
+/// The position of a frame given by index or time, checked against the probed
+/// duration. Indices use the deep-probe timeline when one is cached, and the
+/// frame rate otherwise.
+fn frame_position(
+    info: &VideoInfo,
+    frame_index: Option<u64>,
+    time_sec: Option<f64>,
+) -> AppResult<FramePosition> {
+    let position = match (frame_index, time_sec) {
+        (Some(index), None) => match info.timeline.as_deref() {
+            Some(timeline) => {
+                let Some(pts) = timeline.pts.get(index as usize) else {
+                    return Err(AppError::invalid(format!(
+                        "Frame {index} is past the last frame of {} ({}).",
+                        info.file,
+                        timeline.frame_count().saturating_sub(1)
+                    )));
+                };
+                FramePosition {
+                    seek_sec: timeline.boundary_sec(index),
+                    time_sec: *pts,
+                    frame_index: Some(index),
+                }
+            }
+            None => {
+                let fps = frame_rate(info).ok_or_else(|| {
+                    AppError::invalid("Unable to find the frame: the input frame rate is unknown.")
+                })?;
+                FramePosition {
+                    seek_sec: frame_boundary_sec(index, fps),
+                    time_sec: index as f64 / fps,
+                    frame_index: Some(index),
+                }
+            }
+        },
+        (None, Some(time_sec)) if time_sec.is_finite() && time_sec >= 0.0 => FramePosition {
+            seek_sec: time_sec,
+            time_sec,
+            frame_index: frame_rate(info).map(|fps| (time_sec * fps).round() as u64),
+        },
+        (None, Some(_)) => {
+            return Err(AppError::invalid(
+                "The frame time must be zero or positive.",
+            ))
+        }
+        _ => {
+            return Err(AppError::invalid(
+                "Set exactly one of a frame index and a time.",
+            ))
+        }
+    };
+    if let Some(duration) = info.container.duration_sec {
+        if position.time_sec >= duration {
+            return Err(AppError::invalid(format!(
+                "{:.3}s is past the end of {} ({duration:.3}s).",
+                position.time_sec, info.file
+            )));
+        }
+    }
+    Ok(position)
+}
+
+fn write_png(path: &Path, png: &[u8]) -> AppResult<()> {
+    if let Some(parent) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)
+            .map_err(|e| AppError::io(format!("Failed to create output folder: {}", e)))?;
+    }
+    fs::write(path, png)
+        .map_err(|e| AppError::io(format!("Failed to write {}: {}", path.display(), e)))
+}
+
+fn extract(
+    ffmpeg: &Path,
+    info: &VideoInfo,
+    position: FramePosition,
+    output_path: &Path,
+    width: Option<u32>,
+) -> AppResult<ExtractedFrame> {
+    let png = grab_frame(ffmpeg, &info.file, position.seek_sec, width)?;
+    write_png(output_path, &png)?;
+    Ok(ExtractedFrame {
+        path: output_path.to_string_lossy().to_string(),
+        time_sec: position.time_sec,
+        frame_index: position.frame_index,
+    })
+}
+
+/// Saves one frame of an input as PNG.
+#[tauri::command]
+pub(crate) fn extract_frame(app: AppHandle, params: FrameParams) -> AppResult<ExtractedFrame> {
+    if params.output_path.trim().is_empty() {
+        return Err(AppError::invalid("An output path is required."));
+    }
+    let info = app.state::<ProbeCache>().probe(
+        &resolve_bundled_binary(&app, "ffprobe")?,
+        &params.input_path,
+    )?;
+    let position = frame_position(&info, params.frame_index, params.time_sec)?;
+    let ffmpeg = resolve_bundled_binary(&app, "ffmpeg")?;
+    extract(
+        &ffmpeg,
+        &info,
+        position,
+        Path::new(params.output_path.trim()),
+        params.width,
+    )
+}
+
+/// Saves the frames of A and B at the same timestamp, e.g. `a_clip_f000120.png`
+/// and `b_clip_x265_f000120.png`. Existing files are not overwritten.
+#[tauri::command]
+pub(crate) fn extract_frame_pair(
+    app: AppHandle,
+    params: FramePairParams,
+) -> AppResult<ExtractedFramePair> {
+    if params.output_dir.trim().is_empty() {
+        return Err(AppError::invalid("An output folder is required."));
+    }
+    let ffprobe = resolve_bundled_binary(&app, "ffprobe")?;
+    let cache = app.state::<ProbeCache>();
+    let info_a = cache.probe(&ffprobe, &params.input_path_a)?;
+    let info_b = cache.probe(&ffprobe, &params.input_path_b)?;
+    let position_a = frame_position(&info_a, params.frame_index, params.time_sec)?;
+    // B shows whatever frame is on screen when A's frame is.
+    let position_b = FramePosition {
+        seek_sec: position_a.seek_sec,
+        ..frame_position(&info_b, None, Some(position_a.time_sec))?
+    };
+
+    let position = match params.frame_index {
+        Some(index) => format!("f{index:06}"),
+        None => format!("{}ms", (position_a.time_sec * 1000.0).round() as u64),
+    };
+    let output_dir = PathBuf::from(params.output_dir.trim());
+    let output_path = |letter: &str, info: &VideoInfo| {
+        let stem = Path::new(&info.file)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_else(|| "frame".to_string());
+        let path = output_dir.join(format!("{letter}_{stem}_{position}.png"));
+        PathBuf::from(unique_output_path(&path.to_string_lossy()))
+    };
+
+    let ffmpeg = resolve_bundled_binary(&app, "ffmpeg")?;
+    let a = extract(
+        &ffmpeg,
+        &info_a,
+        position_a,
+        &output_path("a", &info_a),
+        params.width,
+    )?;
+    let b = extract(
+        &ffmpeg,
+        &info_b,
+        position_b,
+        &output_path("b", &info_b),
+        params.width,
+    )?;
+    Ok(ExtractedFramePair { a, b })
+}
+
+/// Renders a grid of frames spread evenly over the whole file into one PNG
+/// with ffmpeg's `tile` filter.
+#[tauri::command]
+pub(crate) fn contact_sheet(app: AppHandle, params: ContactSheetParams) -> AppResult<String> {
+    let output_path = params.output_path.trim();
+    if output_path.is_empty() {
+        return Err(AppError::invalid("An output path is required."));
+    }
+    let columns = params.columns.unwrap_or(DEFAULT_SHEET_COLUMNS);
+    let rows = params.rows.unwrap_or(DEFAULT_SHEET_ROWS);
+    if columns == 0 || rows == 0 {
+        return Err(AppError::invalid(
+            "The contact sheet needs at least one row and column.",
+        ));
+    }
+    let tile_width = params.tile_width.unwrap_or(DEFAULT_TILE_WIDTH);
+    let info = app.state::<ProbeCache>().probe(
+        &resolve_bundled_binary(&app, "ffprobe")?,
+        &params.input_path,
+    )?;
+    let duration = info
+        .container
+        .duration_sec
+        .filter(|duration| *duration > 0.0)
+        .ok_or_else(|| AppError::invalid("Unable to space frames: the duration is unknown."))?;
+
+    let mut command = FfmpegCommand::new(resolve_bundled_binary(&app, "ffmpeg")?);
+    command.global("-hide_banner");
+    command.global("-y");
+    command.input(params.input_path.as_str());
+    // `fps` keeps one frame per slice of the file; `tile` fills the grid with
+    // them in order and emits a single image once it is full.
+    command.video_filters([
+        format!("fps={}/{duration:.6}", columns * rows),
+        format!("scale={tile_width}:-2"),
+        format!("tile={columns}x{rows}"),
+    ]);
+    command.option("-frames:v", "1");
+    command.flag("-an");
+    command.output(output_path);
+
+    if let Some(parent) = Path::new(output_path)
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+    {
+        fs::create_dir_all(parent)
+            .map_err(|e| AppError::io(format!("Failed to create output folder: {}", e)))?;
+    }
+    let output = tool_command(command.program())
+        .args(command.args())
+        .output()
+        .map_err(|e| AppError::spawn(format!("Failed to run ffmpeg: {}", e)))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(AppError::probe(format!(
+            "Failed to render the contact sheet:\n{}",
+            stderr.trim()
+        )));
+    }
+    Ok(output_path.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    use crate::timeline::FrameTimeline;
+
+    fn info(fps: f64) -> VideoInfo {
+        serde_json::from_value(json!({
+            "file": "a.mp4",
+            "container": { "duration_sec": 10.0 },
+            "video": { "index": 0, "fps": fps },
+            "subtitles": [],
+            "attachments": [],
+            "streams": [],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn frame_index_seeks_half_a_frame_early() {
+        let position = frame_position(&info(25.0), Some(10), None).unwrap();
+        assert!((position.seek_sec - 0.38).abs() < 1e-9);
+        assert!((position.time_sec - 0.4).abs() < 1e-9);
+        assert_eq!(position.frame_index, Some(10));
+
+        let first = frame_position(&info(25.0), Some(0), None).unwrap();
+        assert_eq!(first.seek_sec, 0.0);
+    }
+
+    #[test]
+    fn frame_index_uses_the_cached_timeline() {
+        let mut info = info(25.0);
+        info.timeline = Some(Arc::new(FrameTimeline {
+            stream_index: 0,
+            pts: vec![0.0, 0.04, 0.1, 0.14],
+            keyframes: vec![0],
+            frame_interval_sec: Some(0.04),
+            min_frame_interval_sec: Some(0.04),
+            max_frame_interval_sec: Some(0.06),
+            variable_frame_rate: true,
+        }));
+        let position = frame_position(&info, Some(2), None).unwrap();
+        assert!((position.seek_sec - 0.07).abs() < 1e-9);
+        assert!((position.time_sec - 0.1).abs() < 1e-9);
+        let error = frame_position(&info, Some(4), None).err().unwrap();
+        assert!(matches!(error, AppError::InvalidParams { .. }));
+    }
+
+    #[test]
+    fn frame_time_is_checked() {
+        let info = info(25.0);
+        let position = frame_position(&info, None, Some(1.5)).unwrap();
+        assert_eq!(position.seek_sec, 1.5);
+        assert_eq!(position.frame_index, Some(38));
+        for (index, time) in [
+            (None, Some(-1.0)),
+            (None, Some(10.0)),
+            (Some(250), None),
+            (Some(1), Some(1.0)),
+            (None, None),
+        ] {
+            let error = frame_position(&info, index, time).err().unwrap();
+            assert!(matches!(error, AppError::InvalidParams { .. }));
+        }
+    }
+}
@@ -0,0 +1,571 @@
+use crate::error::{AppError, AppResult};
+use crate::ffmpeg::{FilterChain, FilterGraph};
+use crate::{label_filters, label_filters_at, ExportParams};
+
+const DEFAULT_WIPE_POSITION: f64 = 0.5;
+const DEFAULT_WIPE_PERIOD_SEC: f64 = 4.0;
+const DEFAULT_BLEND_MODE: &str = "difference";
+const DEFAULT_DIFF_GAIN: f64 = 4.0;
+const DEFAULT_HEATMAP_PRESET: &str = "turbo";
+/// Stack size used when neither the params nor any probe give one, so inputs
+/// of different sizes can still be stacked.
+const DEFAULT_STACK_HEIGHT: u32 = 720;
+const DEFAULT_STACK_WIDTH: u32 = 1280;
+/// Values of the `pseudocolor` filter's `preset` option.
+const HEATMAP_PRESETS: &[&str] = &[
+    "magma",
+    "inferno",
+    "plasma",
+    "viridis",
+    "turbo",
+    "cividis",
+    "range1",
+    "range2",
+    "shadows",
+    "highlights",
+    "solar",
+    "nominal",
+    "preferred",
+    "total",
+    "spectral",
+    "cool",
+    "heat",
+    "fiery",
+    "blues",
+    "green",
+    "helix",
+];
+/// Values of the `blend` filter's `all_mode` option.
+const BLEND_MODES: &[&str] = &[
+    "addition",
+    "and",
+    "average",
+    "bleach",
+    "burn",
+    "darken",
+    "difference",
+    "divide",
+    "dodge",
+    "exclusion",
+    "extremity",
+    "freeze",
+    "geometric",
+    "glow",
+    "grainextract",
+    "grainmerge",
+    "hardlight",
+    "hardmix",
+    "hardoverlay",
+    "harmonic",
+    "heat",
+    "interpolate",
+    "lighten",
+    "linearlight",
+    "multiply",
+    "multiply128",
+    "negation",
+    "normal",
+    "or",
+    "overlay",
+    "phoenix",
+    "pinlight",
+    "reflect",
+    "screen",
+    "softdifference",
+    "softlight",
+    "stain",
+    "subtract",
+    "vividlight",
+    "xor",
+];
+
+/// How the inputs are arranged in the exported video.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ExportMode {
+    /// One input on its own, by index (`input-a` is 0, `input-b` is 1, ...).
+    Single(usize),
+    /// Inputs next to each other (`hstack`), scaled to `stack_height`.
+    SideBySide,
+    /// Inputs above each other (`vstack`), scaled to `stack_width`.
+    VerticalStack,
+    /// Left part of A meets the right part of B, with a static or moving divider.
+    Wipe,
+    /// A and B blended on top of each other, e.g. as a difference image.
+    Blend,
+    /// Every input as an equal tile (`xstack`).
+    Grid,
+    /// Amplified per-pixel luma difference of A and B, optionally false-colored
+    /// or reduced to a threshold mask.
+    Difference,
+    /// Every input one after another (`concat`), video only.
+    Sequence,
+}
+
+/// Letter used for an input in modes and messages: 0 is `A`, 1 is `B`, ...
+pub(crate) fn input_letter(index: usize) -> char {
+    char::from(b'A' + (index % 26) as u8)
+}
+
+impl ExportMode {
+    pub(crate) fn parse(value: &str) -> AppResult<Self> {
+        if let Some(letter) = value.strip_prefix("input-") {
+            if let [letter @ b'a'..=b'z'] = letter.as_bytes() {
+                return Ok(ExportMode::Single(usize::from(letter - b'a')));
+            }
+        }
+        match value {
+            "side-by-side" => Ok(ExportMode::SideBySide),
+            "vertical-stack" => Ok(ExportMode::VerticalStack),
+            "wipe" => Ok(ExportMode::Wipe),
+            "blend" => Ok(ExportMode::Blend),
+            "grid" => Ok(ExportMode::Grid),
+            "difference" => Ok(ExportMode::Difference),
+            "sequence" => Ok(ExportMode::Sequence),
+            _ => Err(AppError::invalid("Unknown export mode.")),
+        }
+    }
+
+    /// Whether the mode combines several inputs into one frame.
+    pub(crate) fn is_composite(self) -> bool {
+        !matches!(self, ExportMode::Single(_))
+    }
+
+    /// Whether the first input's audio still lines up with the output video.
+    pub(crate) fn keeps_audio(self) -> bool {
+        self != ExportMode::Sequence
+    }
+
+    /// Modes whose blend filters convert to 8-bit `yuv420p`, so HDR cannot be
+    /// preserved through them.
+    pub(crate) fn is_8bit(self) -> bool {
+        self.is_pairwise()
+    }
+
+    /// Modes whose inputs must share one frame size before combining.
+    fn needs_common_size(self) -> bool {
+        matches!(
+            self,
+            ExportMode::Wipe
+                | ExportMode::Blend
+                | ExportMode::Grid
+                | ExportMode::Difference
+                | ExportMode::Sequence
+        )
+    }
+
+    /// Modes that overlay exactly two inputs.
+    fn is_pairwise(self) -> bool {
+        matches!(
+            self,
+            ExportMode::Wipe | ExportMode::Blend | ExportMode::Difference
+        )
+    }
+
+    pub(crate) fn display_name(self) -> &'static str {
+        match self {
+            ExportMode::Single(_) => "single input",
+            ExportMode::SideBySide => "side-by-side",
+            ExportMode::VerticalStack => "vertical stack",
+            ExportMode::Wipe => "wipe",
+            ExportMode::Blend => "blend",
+            ExportMode::Grid => "grid",
+            ExportMode::Difference => "difference",
+            ExportMode::Sequence => "sequence",
+        }
+    }
+}
+
+/// One prepared input branch: its per-input filters and caption.
+pub(crate) struct Branch {
+    pub(crate) chain: FilterChain,
+    pub(crate) caption: Option<String>,
+}
+
+/// Rounds down to an even size, as 4:2:0 encoders require, but never below 2.
+fn even(value: u32) -> u32 {
+    (value - value % 2).max(2)
+}
+
+/// Scales to exactly `width`x`height`, letterboxing instead of distorting.
+fn fit_filters(width: u32, height: u32) -> Vec<String> {
+    vec![
+        format!("scale={width}:{height}:flags=lanczos:force_original_aspect_ratio=decrease"),
+        format!("pad={width}:{height}:(ow-iw)/2:(oh-ih)/2:color=black"),
+        "setsar=1".to_string(),
+    ]
+}
+
+fn caption_of(branch: &Branch) -> Option<&str> {
+    branch
+        .caption
+        .as_deref()
+        .map(str::trim)
+        .filter(|caption| !caption.is_empty())
+}
+
+/// Combines the prepared branches into a graph whose output pad is `vout`.
+/// `tile` is the common frame size for modes that need one; it defaults to
+/// `stack_width`/`stack_height` from the params. Stacks scale every input to
+/// the stack height (or width), taken from the params, then `tile`, then a
+/// fixed size, since `hstack`/`vstack` reject inputs of different sizes.
+pub(crate) fn compose(
+    mode: ExportMode,
+    params: &ExportParams,
+    branches: Vec<Branch>,
+    tile: Option<(u32, u32)>,
+) -> AppResult<FilterGraph> {
+    let count = branches.len();
+    if count < 2 {
+        return Err(AppError::invalid(format!(
+            "The {} layout needs at least two inputs.",
+            mode.display_name()
+        )));
+    }
+    if mode.is_pairwise() && count != 2 {
+        return Err(AppError::invalid(format!(
+            "The {} layout compares exactly two inputs.",
+            mode.display_name()
+        )));
+    }
+
+    let probed = tile;
+    let tile = match (params.stack_width, params.stack_height) {
+        (Some(width), Some(height)) => Some((even(width), even(height))),
+        _ => tile.map(|(width, height)| (even(width), even(height))),
+    };
+    if mode.needs_common_size() && tile.is_none() {
+        return Err(AppError::invalid(format!(
+            "Unable to determine a common frame size for the {} layout.",
+            mode.display_name()
+        )));
+    }
+
+    let mut graph = FilterGraph::new();
+    let mut pads = Vec::with_capacity(count);
+    let mut captions = Vec::with_capacity(count);
+    for (index, branch) in branches.into_iter().enumerate() {
+        let caption = caption_of(&branch).map(str::to_string);
+        let mut chain = branch.chain;
+        match mode {
+            ExportMode::SideBySide => {
+                let height = params
+                    .stack_height
+                    .or(probed.map(|(_, height)| height))
+                    .unwrap_or(DEFAULT_STACK_HEIGHT);
+                // Use -2 to force even width for encoders like libx264.
+                let height = even(height);
+                chain = chain.filter(format!("scale=-2:{height}:flags=lanczos"));
+            }
+            ExportMode::VerticalStack => {
+                let width = params
+                    .stack_width
+                    .or(probed.map(|(width, _)| width))
+                    .unwrap_or(DEFAULT_STACK_WIDTH);
+                let width = even(width);
+                chain = chain.filter(format!("scale={width}:-2:flags=lanczos"));
+            }
+            _ => {
+                if let Some((width, height)) = tile {
+                    chain = chain.filters(fit_filters(width, height));
+                }
+                // blend needs identical pixel formats on both inputs; the
+                // difference view only compares luma.
+                match mode {
+                    ExportMode::Wipe | ExportMode::Blend => {
+                        chain = chain.filter("format=yuv444p");
+                    }
+                    ExportMode::Difference => chain = chain.filter("format=gray"),
+                    _ => {}
+                }
+            }
+        }
+        // Stacked layouts caption each tile; overlapping ones caption the result.
+        if matches!(
+            mode,
+            ExportMode::SideBySide
+                | ExportMode::VerticalStack
+                | ExportMode::Grid
+                | ExportMode::Sequence
+        ) {
+            if let Some(caption) = caption.as_deref() {
+                chain = chain.filters(label_filters(caption));
+            }
+        }
+        let pad = format!("in{index}");
+        graph.push(chain.output(pad.clone()));
+        pads.push(pad);
+        captions.push(caption);
+    }
+
+    let mut combine = FilterChain::new();
+    for pad in &pads {
+        combine = combine.input(pad.clone());
+    }
+    let combine = match mode {
+        ExportMode::SideBySide => combine.filter(format!("hstack=inputs={count}")),
+        ExportMode::VerticalStack => combine.filter(format!("vstack=inputs={count}")),
+        ExportMode::Grid => combine.filter(grid_filter(count, params.grid_columns)),
+        ExportMode::Wipe => combine.filters(wipe_filters(params, &captions)),
+        ExportMode::Blend => combine.filters(blend_filters(params, &captions)?),
+        ExportMode::Difference => combine.filters(difference_filters(params, &captions)?),
+        ExportMode::Sequence => combine.filter(format!("concat=n={count}:v=1:a=0")),
+        ExportMode::Single(_) => unreachable!("single-input mode"),
+    };
+    graph.push(combine.output("vout"));
+    Ok(graph)
+}
+
+fn grid_filter(count: usize, columns: Option<u32>) -> String {
+    let columns = columns
+        .map(|c| c as usize)
+        .filter(|c| *c > 0)
+        .unwrap_or_else(|| (count as f64).sqrt().ceil() as usize)
+        .min(count);
+    // Tiles share one size, so offsets are multiples of the first tile's w0/h0.
+    let offset = |steps: usize, unit: &str| {
+        if steps == 0 {
+            "0".to_string()
+        } else {
+            vec![unit; steps].join("+")
+        }
+    };
+    let layout: Vec<String> = (0..count)
+        .map(|index| {
+            let column = index % columns;
+            let row = index / columns;
+            format!("{}_{}", offset(column, "w0"), offset(row, "h0"))
+        })
+        .collect();
+    format!(
+        "xstack=inputs={count}:layout={}:fill=black",
+        layout.join("|")
+    )
+}
+
+/// Split position as an expression of time: fixed, or sweeping across and back.
+fn wipe_position(params: &ExportParams) -> String {
+    if params.wipe_animated {
+        let period = params
+            .wipe_period_sec
+            .filter(|p| *p > 0.0)
+            .unwrap_or(DEFAULT_WIPE_PERIOD_SEC);
+        format!("(0.5+0.45*sin(2*PI*T/{period}))")
+    } else {
+        let position = params
+            .wipe_position
+            .unwrap_or(DEFAULT_WIPE_POSITION)
+            .clamp(0.0, 1.0);
+        format!("{position}")
+    }
+}
+
+fn wipe_filters(params: &ExportParams, captions: &[Option<String>]) -> Vec<String> {
+    let position = wipe_position(params);
+    let mut filters = vec![format!("blend=all_expr='if(lt(X,W*{position}),A,B)'")];
+    filters.push("format=yuv420p".to_string());
+
+    let (left_x, right_x) = if params.wipe_animated {
+        (
+            "(w/2-text_w)/2".to_string(),
+            "w/2+(w/2-text_w)/2".to_string(),
+        )
+    } else {
+        filters.push(format!(
+            "drawbox=x=iw*{position}-1:y=0:w=2:h=ih:color=white@0.8:t=fill"
+        ));
+        (
+            format!("(w*{position}-text_w)/2"),
+            format!("w*{position}+(w*(1-{position})-text_w)/2"),
+        )
+    };
+    filters.extend(caption_filters(captions, &[left_x, right_x]));
+    filters
+}
+
+fn blend_filters(params: &ExportParams, captions: &[Option<String>]) -> AppResult<Vec<String>> {
+    let mode = params
+        .blend_mode
+        .as_deref()
+        .map(str::trim)
+        .filter(|mode| !mode.is_empty())
+        .unwrap_or(DEFAULT_BLEND_MODE);
+    if !BLEND_MODES.contains(&mode) {
+        return Err(AppError::invalid(format!("Unknown blend mode: {mode}.")));
+    }
+    let mut blend = format!("blend=all_mode={mode}");
+    if let Some(opacity) = params.blend_opacity {
+        blend.push_str(&format!(":all_opacity={}", opacity.clamp(0.0, 1.0)));
+    }
+    let mut filters = vec![blend, "format=yuv420p".to_string()];
+    filters.extend(caption_filters(
+        captions,
+        &["20".to_string(), "w-text_w-20".to_string()],
+    ));
+    Ok(filters)
+}
+
+fn difference_filters(
+    params: &ExportParams,
+    captions: &[Option<String>],
+) -> AppResult<Vec<String>> {
+    let mut filters = vec!["blend=all_mode=difference".to_string()];
+    match params.diff_threshold {
+        // Binary mask: white wherever the inputs differ by more than the threshold.
+        Some(threshold) => {
+            filters.push(format!("lut=c0='if(gt(val,{}),255,0)'", threshold.min(255)))
+        }
+        None => {
+            let gain = params
+                .diff_gain
+                .filter(|gain| *gain > 0.0)
+                .unwrap_or(DEFAULT_DIFF_GAIN);
+            filters.push(format!("lut=c0='min(val*{gain},255)'"));
+        }
+    }
+    if params.diff_heatmap {
+        let preset = params
+            .diff_heatmap_preset
+            .as_deref()
+            .map(str::trim)
+            .filter(|preset| !preset.is_empty())
+            .unwrap_or(DEFAULT_HEATMAP_PRESET);
+        if !HEATMAP_PRESETS.contains(&preset) {
+            return Err(AppError::invalid(format!(
+                "Unknown heatmap preset: {preset}."
+            )));
+        }
+        filters.push("format=yuv444p".to_string());
+        filters.push(format!("pseudocolor=preset={preset}"));
+    }
+    filters.push("format=yuv420p".to_string());
+
+    // A single centered "A vs B" caption reads better than two corner labels.
+    if let [Some(a), Some(b)] = captions {
+        filters.extend(label_filters(&format!("{a} vs {b}")));
+    } else {
+        filters.extend(caption_filters(
+            captions,
+            &["20".to_string(), "w-text_w-20".to_string()],
+        ));
+    }
+    Ok(filters)
+}
+
+fn caption_filters(captions: &[Option<String>], positions: &[String]) -> Vec<String> {
+    let placed: Vec<(&str, &str)> = captions
+        .iter()
+        .zip(positions)
+        .filter_map(|(caption, x)| caption.as_deref().map(|caption| (caption, x.as_str())))
+        .collect();
+    label_filters_at(&placed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn params(mode: &str, overrides: serde_json::Value) -> ExportParams {
+        let mut value = json!({
+            "exportMode": mode,
+            "outputPath": "out.mp4",
+            "codec": "h264",
+            "crf": 23,
+            "keepAspect": false,
+            "audioCopy": false,
+        });
+        for (key, field) in overrides.as_object().unwrap() {
+            value[key] = field.clone();
+        }
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn branches() -> Vec<Branch> {
+        (0..2)
+            .map(|index| Branch {
+                chain: FilterChain::new().input(format!("{index}:v")),
+                caption: None,
+            })
+            .collect()
+    }
+
+    fn compose_mode(
+        mode: &str,
+        overrides: serde_json::Value,
+        tile: Option<(u32, u32)>,
+    ) -> AppResult<String> {
+        let params = params(mode, overrides);
+        let mode = ExportMode::parse(mode)?;
+        compose(mode, &params, branches(), tile).map(|graph| graph.to_string())
+    }
+
+    #[test]
+    fn blend_mode_must_be_known() {
+        let graph =
+            compose_mode("blend", json!({ "blendMode": "average" }), Some((640, 360))).unwrap();
+        assert!(graph.contains("blend=all_mode=average,"));
+        for mode in ["average:all_opacity=1", "difference,split", "nope"] {
+            assert!(matches!(
+                compose_mode("blend", json!({ "blendMode": mode }), Some((640, 360))),
+                Err(AppError::InvalidParams { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn heatmap_preset_must_be_known() {
+        let tile = Some((640, 360));
+        let graph = compose_mode(
+            "difference",
+            json!({ "diffHeatmap": true, "diffHeatmapPreset": "heat", "diffGain": 8.0 }),
+            tile,
+        )
+        .unwrap();
+        assert!(graph.contains("lut=c0='min(val*8,255)',format=yuv444p,pseudocolor=preset=heat,"));
+        let graph = compose_mode("difference", json!({ "diffThreshold": 300 }), tile).unwrap();
+        assert!(graph.contains("lut=c0='if(gt(val,255),255,0)'"));
+        assert!(!graph.contains("pseudocolor"));
+        for preset in ["turbo:opacity=0", "heat,split", "rainbow"] {
+            assert!(matches!(
+                compose_mode(
+                    "difference",
+                    json!({ "diffHeatmap": true, "diffHeatmapPreset": preset }),
+                    tile,
+                ),
+                Err(AppError::InvalidParams { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn stacks_fall_back_to_a_fixed_size_without_probes() {
+        let graph = compose_mode("side-by-side", json!({}), None).unwrap();
+        assert_eq!(
+            graph,
+            "[0:v]scale=-2:720:flags=lanczos[in0];[1:v]scale=-2:720:flags=lanczos[in1];\
+             [in0][in1]hstack=inputs=2[vout]"
+        );
+        let graph = compose_mode("vertical-stack", json!({ "stackWidth": 1 }), None).unwrap();
+        assert!(graph.starts_with("[0:v]scale=2:-2:flags=lanczos[in0]"));
+    }
+
+    #[test]
+    fn stacks_scale_mismatched_inputs_to_the_probed_size() {
+        let graph = compose_mode("side-by-side", json!({}), Some((1920, 1080))).unwrap();
+        assert_eq!(
+            graph,
+            "[0:v]scale=-2:1080:flags=lanczos[in0];[1:v]scale=-2:1080:flags=lanczos[in1];\
+             [in0][in1]hstack=inputs=2[vout]"
+        );
+        let graph = compose_mode("vertical-stack", json!({}), Some((1281, 720))).unwrap();
+        assert!(graph.starts_with("[0:v]scale=1280:-2:flags=lanczos[in0];[1:v]scale=1280:-2"));
+        // An explicit size still wins over the probed one.
+        let graph = compose_mode(
+            "side-by-side",
+            json!({ "stackHeight": 480 }),
+            Some((1920, 1080)),
+        )
+        .unwrap();
+        assert!(graph.contains("[1:v]scale=-2:480:flags=lanczos[in1]"));
+    }
+}
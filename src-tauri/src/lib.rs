@@ -1,9 +1,8 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::io::{BufRead, BufReader, Read};
-use std::os::windows::process::CommandExt;
 use std::path::{Path, PathBuf};
-use std::process::{Child, Command, Stdio};
+use std::process::{Child, ChildStderr, ChildStdout, Command, Stdio};
 use std::sync::{Arc, Mutex};
 use std::thread;
 
@@ -11,51 +10,98 @@ use serde::{Deserialize, Serialize};
 use tauri::{AppHandle, Emitter, Manager, State};
 use uuid::Uuid;
 
-#[derive(Default)]
+mod analysis;
+mod batch;
+mod cache;
+pub mod cli;
+mod color;
+mod diff;
+mod encoder;
+mod error;
+mod ffmpeg;
+mod frames;
+mod layout;
+mod metrics;
+mod pairs;
+mod presets;
+mod probe;
+mod progress;
+mod queue;
+mod report;
+mod timeline;
+mod tracks;
+
+use cache::ProbeCache;
+use color::HdrMode;
+use encoder::{EncoderSettings, Pass};
+use error::{AppError, AppResult, ExportOutcome};
+use ffmpeg::{FfmpegCommand, FilterChain, FilterGraph};
+use layout::{input_letter, Branch, ExportMode};
+use probe::VideoInfo;
+use progress::{ProgressTarget, ProgressTracker};
+use timeline::FrameTimeline;
+use tracks::TrackSelector;
+
+#[derive(Default, Clone)]
 struct ExportManager {
     children: Arc<Mutex<HashMap<String, Child>>>,
+    /// Ids of runs from `register` until `release`, including while they are
+    /// still being prepared or between two passes.
+    registered: Arc<Mutex<HashSet<String>>>,
+    /// Ids killed on request, so their exit is reported as cancelled rather than failed.
+    cancelled: Arc<Mutex<HashSet<String>>>,
 }
 
-#[derive(Serialize)]
-struct ContainerInfo {
-    format_name: Option<String>,
-    duration_sec: Option<f64>,
-    bitrate: Option<u64>,
-}
-
-#[derive(Serialize)]
-struct VideoStreamInfo {
-    codec_name: Option<String>,
-    profile: Option<String>,
-    width: Option<u32>,
-    height: Option<u32>,
-    pix_fmt: Option<String>,
-    color_space: Option<String>,
-    color_range: Option<String>,
-    color_transfer: Option<String>,
-    color_primaries: Option<String>,
-    bit_rate: Option<u64>,
-    avg_frame_rate: Option<String>,
-    r_frame_rate: Option<String>,
-    fps: Option<f64>,
-    frame_count: Option<u64>,
-}
+impl ExportManager {
+    /// Starts tracking a run before anything is probed or spawned for it, so a
+    /// cancel that arrives early is kept. Clears a mark left by an earlier run
+    /// with the same id.
+    fn register(&self, id: &str) -> AppResult<()> {
+        let mut registered = self.registered.lock().map_err(|_| AppError::lock())?;
+        registered.insert(id.to_string());
+        self.cancelled
+            .lock()
+            .map_err(|_| AppError::lock())?
+            .remove(id);
+        Ok(())
+    }
 
-#[derive(Serialize)]
-struct AudioStreamInfo {
-    codec_name: Option<String>,
-    channels: Option<u32>,
-    sample_rate: Option<u32>,
-    bit_rate: Option<u64>,
-}
+    /// Stops tracking a run and returns whether it was cancelled.
+    fn release(&self, id: &str) -> bool {
+        let Ok(mut registered) = self.registered.lock() else {
+            return false;
+        };
+        registered.remove(id);
+        self.cancelled
+            .lock()
+            .map(|mut cancelled| cancelled.remove(id))
+            .unwrap_or(false)
+    }
 
-#[derive(Serialize)]
-struct VideoInfo {
-    file: String,
-    size_bytes: Option<u64>,
-    container: ContainerInfo,
-    video: Option<VideoStreamInfo>,
-    audio: Option<AudioStreamInfo>,
+    /// Kills the tracked process for `id`, if it is still running, and marks
+    /// the run as cancelled. The mark also covers the moments before the first
+    /// pass and between two passes when no process is registered, so the next
+    /// pass is not started. Ids that are not registered are left alone.
+    fn kill(&self, id: &str) -> AppResult<()> {
+        let mut children = self.children.lock().map_err(|_| AppError::lock())?;
+        let registered = self.registered.lock().map_err(|_| AppError::lock())?;
+        if !registered.contains(id) {
+            return Ok(());
+        }
+        if let Some(child) = children.get_mut(id) {
+            child
+                .kill()
+                .map_err(|e| AppError::io(format!("Failed to cancel export: {}", e)))?;
+        }
+        // Still holding `children`, so a pass being registered right now
+        // sees the mark once it gets the lock, and `registered`, so the run
+        // cannot be released in between and leave the mark behind.
+        self.cancelled
+            .lock()
+            .map_err(|_| AppError::lock())?
+            .insert(id.to_string());
+        Ok(())
+    }
 }
 
 #[derive(Serialize)]
@@ -63,25 +109,55 @@ struct ExportStarted {
     export_id: String,
     command: String,
     output_path: String,
+    /// Non-fatal notes about the inputs, e.g. differing color spaces.
+    warnings: Vec<String>,
 }
 
-#[derive(Serialize, Clone)]
-struct ExportProgress {
-    export_id: String,
-    progress: String,
-    out_time_ms: Option<u64>,
-    message: Option<String>,
+/// One source of an export. Trim and resize fields left unset fall back to the
+/// export-wide values in `ExportParams`.
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+struct ExportInput {
+    path: String,
+    label: Option<String>,
+    trim_start_frame: Option<u64>,
+    trim_end_frame: Option<u64>,
+    resize_width: Option<u32>,
+    resize_height: Option<u32>,
 }
 
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 struct ExportParams {
+    /// Inputs in order: `input-a` exports the first, `input-c` the third, and
+    /// composite modes use all of them. When empty, `input_path_a`/`input_path_b`
+    /// with `label_a`/`label_b` are used instead.
+    #[serde(default)]
+    inputs: Vec<ExportInput>,
     input_path_a: Option<String>,
     input_path_b: Option<String>,
     export_mode: String,
     output_path: String,
+    /// `h264`, `h265`, `vp9`, `av1` (SVT-AV1), `av1-aom`, `prores` or `ffv1`.
     codec: String,
     crf: u8,
+    /// Encoder speed preset, e.g. `slow` for x264/x265, `6` for SVT-AV1 or the
+    /// `-cpu-used` value for VP9 and libaom.
+    preset: Option<String>,
+    tune: Option<String>,
+    /// Encoder profile, e.g. `high` for H.264 or `hq` for ProRes.
+    profile: Option<String>,
+    level: Option<String>,
+    /// `crf` (default), `cvbr` (CRF capped by `maxrate_kbps`) or `abr`
+    /// (average `bitrate_kbps`).
+    rate_control: Option<String>,
+    bitrate_kbps: Option<u32>,
+    maxrate_kbps: Option<u32>,
+    /// Rate control buffer; defaults to twice `maxrate_kbps`.
+    bufsize_kbps: Option<u32>,
+    /// Encode `abr` exports in two passes.
+    #[serde(default)]
+    two_pass: bool,
     resize_width: Option<u32>,
     resize_height: Option<u32>,
     keep_aspect: bool,
@@ -92,17 +168,134 @@ struct ExportParams {
     label_b: Option<String>,
     audio_copy: bool,
     stack_height: Option<u32>,
+    /// Tile width for `vertical-stack`; with `stack_height` also the tile size for
+    /// `wipe`, `blend` and `grid`.
+    stack_width: Option<u32>,
+    /// Deprecated: extra `grid` tiles after A and B. Only read when `inputs`
+    /// is empty; new callers list every input in `inputs`.
+    #[serde(default)]
+    extra_input_paths: Vec<String>,
+    /// Divider position for `wipe`, as a fraction of the width from the left.
+    wipe_position: Option<f64>,
+    /// Sweep the wipe divider back and forth instead of keeping it fixed.
+    #[serde(default)]
+    wipe_animated: bool,
+    wipe_period_sec: Option<f64>,
+    /// ffmpeg `blend` mode for `blend`, e.g. `difference` or `average`.
+    blend_mode: Option<String>,
+    blend_opacity: Option<f64>,
+    grid_columns: Option<u32>,
+    /// Multiplier applied to the absolute difference in `difference` mode.
+    diff_gain: Option<f64>,
+    /// Show the difference as a false-color heatmap instead of grayscale.
+    #[serde(default)]
+    diff_heatmap: bool,
+    /// `pseudocolor` preset for the heatmap, e.g. `turbo` or `heat`.
+    diff_heatmap_preset: Option<String>,
+    /// Replace the amplified difference with a mask of pixels whose luma
+    /// differs by more than this value (0-255).
+    diff_threshold: Option<u32>,
+    /// Input (0 = A) whose audio and subtitle streams are exported; only
+    /// meaningful for composite modes.
+    track_source: Option<usize>,
+    /// Audio streams to include. Empty keeps the default: the first audio
+    /// stream, and in composite modes only with `audio_copy`.
+    #[serde(default)]
+    audio_streams: Vec<TrackSelector>,
+    #[serde(default)]
+    subtitle_streams: Vec<TrackSelector>,
+    /// Render the (single) selected subtitle stream into the video instead of
+    /// muxing it as a separate track.
+    #[serde(default)]
+    burn_subtitles: bool,
+    /// `tonemap` converts HDR inputs to SDR, `preserve` keeps HDR metadata
+    /// (H.265 only). Unset re-encodes without touching color.
+    hdr_mode: Option<String>,
+    /// `tonemap` filter algorithm, e.g. `hable` (default), `mobius` or `reinhard`.
+    tonemap_algorithm: Option<String>,
+}
+
+impl ExportParams {
+    /// The inputs with export-wide trim and resize values filled in. Positions
+    /// are kept, so an empty slot still counts as that letter.
+    fn resolved_inputs(&self) -> Vec<ExportInput> {
+        let mut inputs = if self.inputs.is_empty() {
+            let legacy = |path: &Option<String>, label: &Option<String>| ExportInput {
+                path: path.clone().unwrap_or_default(),
+                label: label.clone(),
+                ..ExportInput::default()
+            };
+            let mut inputs = vec![
+                legacy(&self.input_path_a, &self.label_a),
+                legacy(&self.input_path_b, &self.label_b),
+            ];
+            if self.export_mode == "grid" {
+                inputs.extend(
+                    self.extra_input_paths
+                        .iter()
+                        .map(|path| legacy(&Some(path.clone()), &None)),
+                );
+            }
+            inputs
+        } else {
+            self.inputs.clone()
+        };
+        for input in &mut inputs {
+            input.path = input.path.trim().to_string();
+            if input.trim_start_frame.is_none() && input.trim_end_frame.is_none() {
+                input.trim_start_frame = self.trim_start_frame;
+                input.trim_end_frame = self.trim_end_frame;
+            }
+            if input.resize_width.is_none() && input.resize_height.is_none() {
+                input.resize_width = self.resize_width;
+                input.resize_height = self.resize_height;
+            }
+        }
+        inputs
+    }
 }
 
+#[cfg(windows)]
 const CREATE_NO_WINDOW: u32 = 0x08000000;
 
+/// Builds a `Command` for a bundled tool without flashing a console window on Windows.
+fn tool_command(program: &Path) -> Command {
+    #[cfg_attr(not(windows), allow(unused_mut))]
+    let mut command = Command::new(program);
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        command.creation_flags(CREATE_NO_WINDOW);
+    }
+    command
+}
+
+#[cfg(windows)]
+const FONT_CANDIDATES: &[&str] = &[
+    "C:\\Windows\\Fonts\\segoeui.ttf",
+    "C:\\Windows\\Fonts\\SegoeUI.ttf",
+    "C:\\Windows\\Fonts\\arial.ttf",
+    "C:\\Windows\\Fonts\\Arial.ttf",
+];
+
+#[cfg(target_os = "macos")]
+const FONT_CANDIDATES: &[&str] = &[
+    "/System/Library/Fonts/Supplemental/Arial.ttf",
+    "/Library/Fonts/Arial.ttf",
+    "/System/Library/Fonts/Helvetica.ttc",
+];
+
+#[cfg(not(any(windows, target_os = "macos")))]
+const FONT_CANDIDATES: &[&str] = &[
+    "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf",
+    "/usr/share/fonts/dejavu/DejaVuSans.ttf",
+    "/usr/share/fonts/TTF/DejaVuSans.ttf",
+    "/usr/share/fonts/truetype/liberation/LiberationSans-Regular.ttf",
+    "/usr/share/fonts/liberation/LiberationSans-Regular.ttf",
+];
+
 fn resolve_font_file() -> Option<String> {
-    let candidates = [
-        "C:\\Windows\\Fonts\\segoeui.ttf",
-        "C:\\Windows\\Fonts\\SegoeUI.ttf",
-        "C:\\Windows\\Fonts\\arial.ttf",
-        "C:\\Windows\\Fonts\\Arial.ttf",
-    ];
+    let candidates = FONT_CANDIDATES.iter().copied();
     for path in candidates {
         if Path::new(path).exists() {
             return Some(path.replace('\\', "/"));
@@ -123,102 +316,101 @@ fn escape_drawtext(value: &str) -> String {
 }
 
 fn label_filters(label: &str) -> Vec<String> {
-    let trimmed = label.trim();
-    if trimmed.is_empty() {
-        return Vec::new();
-    }
-
-    let text = escape_drawtext(trimmed);
-    let mut drawtext = String::new();
-    if let Some(fontfile) = resolve_font_file() {
-        let escaped = escape_filter_value(&fontfile);
-        drawtext.push_str(&format!("fontfile='{escaped}':"));
-    }
-    drawtext.push_str(&format!(
-        "text='{text}':fontcolor=white:fontsize=h*0.05:x=(w-text_w)/2:y=h-60"
-    ));
-
-    vec![
-        "drawbox=x=0:y=ih-90:w=iw:h=90:color=black@0.45:t=fill".to_string(),
-        format!("drawtext={drawtext}"),
-    ]
+    label_filters_at(&[(label, "(w-text_w)/2")])
 }
 
-fn resolve_bundled_binary(app: &AppHandle, name: &str) -> Result<PathBuf, String> {
-    let dev_path = PathBuf::from("binaries").join(name);
-    let repo_dev_path = PathBuf::from("src-tauri").join("binaries").join(name);
-    if dev_path.exists() {
-        return Ok(dev_path);
-    }
-    if repo_dev_path.exists() {
-        return Ok(repo_dev_path);
+/// Draws one caption band along the bottom edge with each label at its own
+/// horizontal position expression, e.g. both halves of a wipe.
+fn label_filters_at(labels: &[(&str, &str)]) -> Vec<String> {
+    let labels: Vec<(&str, &str)> = labels
+        .iter()
+        .map(|(label, x)| (label.trim(), *x))
+        .filter(|(label, _)| !label.is_empty())
+        .collect();
+    if labels.is_empty() {
+        return Vec::new();
     }
 
-    let resource_dir = app
-        .path()
-        .resource_dir()
-        .map_err(|e| format!("Failed to resolve resource_dir: {}", e))?;
-    let prod_path = resource_dir.join("binaries").join(name);
-
-    if prod_path.exists() {
-        return Ok(prod_path);
+    let fontfile = resolve_font_file()
+        .map(|fontfile| format!("fontfile='{}':", escape_filter_value(&fontfile)))
+        .unwrap_or_default();
+    let mut filters = vec!["drawbox=x=0:y=ih-90:w=iw:h=90:color=black@0.45:t=fill".to_string()];
+    for (label, x) in labels {
+        let text = escape_drawtext(label);
+        filters.push(format!(
+            "drawtext={fontfile}text='{text}':fontcolor=white:fontsize=h*0.05:x={x}:y=h-60"
+        ));
     }
+    filters
+}
 
-    Err(format!(
-    "Bundled binary not found: {} (looked in {:?}, {:?}, and {:?}). Run scripts/setup-ffmpeg.ps1 to install.",
-    name, dev_path, repo_dev_path, prod_path
-  ))
+/// Environment variable that points at a specific build of `name`, e.g. `PIXELDUEL_FFMPEG`.
+fn binary_override_var(name: &str) -> String {
+    format!("PIXELDUEL_{}", name.to_ascii_uppercase())
 }
 
-fn parse_u64(value: &serde_json::Value) -> Option<u64> {
-    match value {
-        serde_json::Value::String(text) => text.parse::<u64>().ok(),
-        serde_json::Value::Number(num) => num.as_u64(),
-        _ => None,
-    }
+fn find_on_path(file_name: &str) -> Option<PathBuf> {
+    let paths = std::env::var_os("PATH")?;
+    std::env::split_paths(&paths)
+        .map(|dir| dir.join(file_name))
+        .find(|candidate| candidate.is_file())
 }
 
-fn parse_f64(value: &serde_json::Value) -> Option<f64> {
-    match value {
-        serde_json::Value::String(text) => text.parse::<f64>().ok(),
-        serde_json::Value::Number(num) => num.as_f64(),
-        _ => None,
+/// Resolves `name` (without extension) to an ffmpeg tool. The env override wins,
+/// then the bundled copies in dev and resource dirs, then whatever is on `PATH`.
+fn resolve_binary(resource_dir: Option<&Path>, name: &str) -> AppResult<PathBuf> {
+    let override_var = binary_override_var(name);
+    if let Some(value) = std::env::var_os(&override_var).filter(|v| !v.is_empty()) {
+        let path = PathBuf::from(value);
+        if path.is_file() {
+            return Ok(path);
+        }
+        return Err(AppError::binary_not_found(format!(
+            "{} points to {:?}, which does not exist.",
+            override_var, path
+        )));
     }
-}
 
-fn parse_fraction(value: &str) -> Option<f64> {
-    if value == "0/0" {
-        return None;
+    let file_name = format!("{}{}", name, std::env::consts::EXE_SUFFIX);
+    let mut searched = vec![
+        PathBuf::from("binaries").join(&file_name),
+        PathBuf::from("src-tauri").join("binaries").join(&file_name),
+    ];
+    if let Some(resource_dir) = resource_dir {
+        searched.push(resource_dir.join("binaries").join(&file_name));
     }
-    let mut parts = value.split('/');
-    let num = parts.next()?.parse::<f64>().ok()?;
-    let den = parts.next()?.parse::<f64>().ok()?;
-    if den == 0.0 {
-        return None;
+    if let Some(path) = searched.iter().find(|path| path.exists()) {
+        return Ok(path.clone());
     }
-    Some(num / den)
-}
 
-fn build_command_string(executable: &PathBuf, args: &[String]) -> String {
-    let mut parts = Vec::new();
-    parts.push(format_arg(executable.to_string_lossy().as_ref()));
-    for arg in args {
-        parts.push(format_arg(arg));
+    if let Some(path) = find_on_path(&file_name) {
+        return Ok(path);
     }
-    parts.join(" ")
-}
 
-fn format_arg(value: &str) -> String {
-    if value.contains(' ') {
-        format!("\"{}\"", value.replace('"', "\\\""))
+    let hint = if cfg!(windows) {
+        "Run scripts/setup-ffmpeg.ps1 to install."
     } else {
-        value.to_string()
-    }
+        "Install ffmpeg with your package manager."
+    };
+    Err(AppError::binary_not_found(format!(
+        "{} not found (looked in {:?} and PATH). Set {} or {}",
+        file_name, searched, override_var, hint
+    )))
+}
+
+fn resolve_bundled_binary(app: &AppHandle, name: &str) -> AppResult<PathBuf> {
+    let resource_dir = app.path().resource_dir().ok();
+    resolve_binary(resource_dir.as_deref(), name)
 }
 
 fn unique_output_path(path: &str) -> String {
+    unique_output_path_by(path, |candidate| candidate.exists())
+}
+
+/// Adds ` (1)`, ` (2)`, ... before the extension until `taken` is false.
+fn unique_output_path_by(path: &str, taken: impl Fn(&Path) -> bool) -> String {
     let candidate = Path::new(path);
-    if !candidate.exists() {
+    if !taken(candidate) {
         return path.to_string();
     }
 
@@ -236,7 +428,7 @@ fn unique_output_path(path: &str) -> String {
             format!("{stem} ({index})")
         };
         let next = parent.join(file_name);
-        if !next.exists() {
+        if !taken(&next) {
             return next.to_string_lossy().to_string();
         }
     }
@@ -244,205 +436,148 @@ fn unique_output_path(path: &str) -> String {
     path.to_string()
 }
 
-#[tauri::command]
-fn probe_video(app: AppHandle, path: String) -> Result<VideoInfo, String> {
-    let ffprobe = resolve_bundled_binary(&app, "ffprobe.exe")?;
-
-    let output = Command::new(ffprobe)
-        .args([
-            "-hide_banner",
-            "-print_format",
-            "json",
-            "-show_format",
-            "-show_streams",
-            &path,
-        ])
-        .output()
-        .map_err(|e| format!("Failed to run ffprobe: {}", e))?;
-
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-        return Err(format!("ffprobe error:\n{}", stderr));
+/// The time halfway between frame `index - 1` and frame `index` at a constant
+/// frame rate; 0 for the first frame.
+fn frame_boundary_sec(index: u64, fps: f64) -> f64 {
+    if index == 0 {
+        0.0
+    } else {
+        (index as f64 - 0.5) / fps
     }
+}
 
-    let json: serde_json::Value = serde_json::from_slice(&output.stdout)
-        .map_err(|e| format!("Failed to parse ffprobe output: {}", e))?;
-    let format = json.get("format").cloned().unwrap_or_default();
-    let streams = json
-        .get("streams")
-        .and_then(|v| v.as_array())
-        .cloned()
-        .unwrap_or_default();
-
-    let video_stream = streams
-        .iter()
-        .find(|s| s.get("codec_type").and_then(|v| v.as_str()) == Some("video"));
-    let audio_stream = streams
-        .iter()
-        .find(|s| s.get("codec_type").and_then(|v| v.as_str()) == Some("audio"));
-
-    let avg_frame_rate = video_stream
-        .and_then(|s| s.get("avg_frame_rate"))
-        .and_then(|v| v.as_str())
-        .map(|s| s.to_string());
-    let r_frame_rate = video_stream
-        .and_then(|s| s.get("r_frame_rate"))
-        .and_then(|v| v.as_str())
-        .map(|s| s.to_string());
-
-    let fps = avg_frame_rate
-        .as_deref()
-        .and_then(parse_fraction)
-        .or_else(|| r_frame_rate.as_deref().and_then(parse_fraction));
-
-    let duration_sec = format.get("duration").and_then(parse_f64);
-
-    let frame_count = video_stream
-        .and_then(|s| s.get("nb_frames"))
-        .and_then(parse_u64)
-        .or_else(|| {
-            if let (Some(duration), Some(fps_value)) = (duration_sec, fps) {
-                Some((duration * fps_value).round() as u64)
-            } else {
-                None
-            }
-        });
-
-    let size_bytes = format
-        .get("size")
-        .and_then(parse_u64)
-        .or_else(|| fs::metadata(&path).map(|m| m.len()).ok());
-
-    let container = ContainerInfo {
-        format_name: format
-            .get("format_name")
-            .and_then(|v| v.as_str())
-            .map(|s| s.to_string()),
-        duration_sec,
-        bitrate: format.get("bit_rate").and_then(parse_u64),
-    };
+/// A frame range converted to input timestamps using the probed frame rate.
+#[derive(Clone, Copy)]
+struct TrimWindow {
+    start_sec: f64,
+    duration_sec: Option<f64>,
+    frame_count: Option<u64>,
+}
 
-    let video = video_stream.map(|stream| VideoStreamInfo {
-        codec_name: stream
-            .get("codec_name")
-            .and_then(|v| v.as_str())
-            .map(|s| s.to_string()),
-        profile: stream
-            .get("profile")
-            .and_then(|v| v.as_str())
-            .map(|s| s.to_string()),
-        width: stream
-            .get("width")
-            .and_then(|v| v.as_u64())
-            .map(|v| v as u32),
-        height: stream
-            .get("height")
-            .and_then(|v| v.as_u64())
-            .map(|v| v as u32),
-        pix_fmt: stream
-            .get("pix_fmt")
-            .and_then(|v| v.as_str())
-            .map(|s| s.to_string()),
-        color_space: stream
-            .get("color_space")
-            .and_then(|v| v.as_str())
-            .map(|s| s.to_string()),
-        color_range: stream
-            .get("color_range")
-            .and_then(|v| v.as_str())
-            .map(|s| s.to_string()),
-        color_transfer: stream
-            .get("color_transfer")
-            .and_then(|v| v.as_str())
-            .map(|s| s.to_string()),
-        color_primaries: stream
-            .get("color_primaries")
-            .and_then(|v| v.as_str())
-            .map(|s| s.to_string()),
-        bit_rate: stream.get("bit_rate").and_then(parse_u64),
-        avg_frame_rate,
-        r_frame_rate,
-        fps,
-        frame_count,
-    });
+impl TrimWindow {
+    /// `end_frame` is inclusive, matching the frame numbers shown in the UI.
+    /// Cuts halfway between frames like `from_timeline`: `frame / fps` rounded
+    /// to microseconds can land just after the frame's real timestamp (frame 1
+    /// at 29.97 fps is 0.0333667s), which would drop that frame.
+    fn from_frames(start_frame: Option<u64>, end_frame: Option<u64>, fps: f64) -> Option<Self> {
+        let start = start_frame.unwrap_or(0);
+        if start == 0 && end_frame.is_none() {
+            return None;
+        }
+        let end = end_frame.map(|end| end.max(start));
+        let start_sec = frame_boundary_sec(start, fps);
+        Some(Self {
+            start_sec,
+            duration_sec: end.map(|end| frame_boundary_sec(end + 1, fps) - start_sec),
+            frame_count: end.map(|end| end - start + 1),
+        })
+    }
 
-    let audio = audio_stream.map(|stream| AudioStreamInfo {
-        codec_name: stream
-            .get("codec_name")
-            .and_then(|v| v.as_str())
-            .map(|s| s.to_string()),
-        channels: stream
-            .get("channels")
-            .and_then(|v| v.as_u64())
-            .map(|v| v as u32),
-        sample_rate: stream
-            .get("sample_rate")
-            .and_then(|v| v.as_str())
-            .and_then(|v| v.parse::<u32>().ok()),
-        bit_rate: stream.get("bit_rate").and_then(parse_u64),
-    });
+    /// Cuts halfway between frames of a deep-probed timeline, so the window
+    /// holds exactly the requested frames even at a variable frame rate.
+    fn from_timeline(
+        start_frame: Option<u64>,
+        end_frame: Option<u64>,
+        timeline: &FrameTimeline,
+    ) -> AppResult<Option<Self>> {
+        let start = start_frame.unwrap_or(0);
+        if start == 0 && end_frame.is_none() {
+            return Ok(None);
+        }
+        let count = timeline.frame_count();
+        if start >= count {
+            return Err(AppError::invalid(format!(
+                "Trim start frame {start} is past the last frame ({}).",
+                count.saturating_sub(1)
+            )));
+        }
+        let end = end_frame.map(|end| end.max(start).min(count - 1));
+        let start_sec = timeline.boundary_sec(start);
+        Ok(Some(Self {
+            start_sec,
+            duration_sec: end.map(|end| timeline.boundary_sec(end + 1) - start_sec),
+            frame_count: Some(end.unwrap_or(count - 1) - start + 1),
+        }))
+    }
 
-    Ok(VideoInfo {
-        file: path,
-        size_bytes,
-        container,
-        video,
-        audio,
-    })
+    /// Input options that seek before decoding. When transcoding, ffmpeg decodes
+    /// from the preceding keyframe and drops frames up to the exact timestamp, so
+    /// video and audio (re-encoded or copied) are cut at the same point.
+    fn input_options(&self) -> Vec<String> {
+        let mut options = vec!["-ss".to_string(), format!("{:.6}", self.start_sec)];
+        if let Some(duration) = self.duration_sec {
+            options.push("-t".to_string());
+            options.push(format!("{:.6}", duration));
+        }
+        options
+    }
 }
 
-#[tauri::command]
-fn export_video(
-    app: AppHandle,
-    export_manager: State<'_, ExportManager>,
-    params: ExportParams,
-) -> Result<ExportStarted, String> {
-    let ffmpeg = resolve_bundled_binary(&app, "ffmpeg.exe")?;
-    let export_mode = params.export_mode.as_str();
-    let input_a = params.input_path_a.as_ref().filter(|path| !path.is_empty());
-    let input_b = params.input_path_b.as_ref().filter(|path| !path.is_empty());
-
-    let primary_input = match export_mode {
-        "input-b" => input_b.ok_or_else(|| "Input B is required.".to_string())?,
-        "side-by-side" | "input-a" => input_a.ok_or_else(|| "Input A is required.".to_string())?,
-        _ => return Err("Unknown export mode.".to_string()),
-    };
-
-    let output_path = unique_output_path(&params.output_path);
-    let mut args: Vec<String> = Vec::new();
+fn trim_requested(input: &ExportInput) -> bool {
+    input.trim_start_frame.unwrap_or(0) > 0 || input.trim_end_frame.is_some()
+}
 
-    args.push("-i".to_string());
-    args.push(primary_input.to_string());
+fn trim_window_for(input: &ExportInput, info: Option<&VideoInfo>) -> AppResult<Option<TrimWindow>> {
+    if !trim_requested(input) {
+        return Ok(None);
+    }
+    if let Some(timeline) = info.and_then(|info| info.timeline.as_deref()) {
+        return TrimWindow::from_timeline(input.trim_start_frame, input.trim_end_frame, timeline);
+    }
+    let fps = info
+        .and_then(|info| info.video.as_ref())
+        .and_then(|video| video.fps)
+        .filter(|fps| *fps > 0.0)
+        .ok_or_else(|| AppError::invalid("Unable to trim: the input frame rate is unknown."))?;
+    Ok(TrimWindow::from_frames(
+        input.trim_start_frame,
+        input.trim_end_frame,
+        fps,
+    ))
+}
 
-    if export_mode == "side-by-side" {
-        let second =
-            input_b.ok_or_else(|| "Input B is required for side-by-side export.".to_string())?;
-        args.push("-i".to_string());
-        args.push(second.to_string());
+/// The inputs an export reads, in ffmpeg input order.
+fn export_sources(params: &ExportParams, mode: ExportMode) -> AppResult<Vec<ExportInput>> {
+    let inputs = params.resolved_inputs();
+    match mode {
+        ExportMode::Single(index) => inputs
+            .into_iter()
+            .nth(index)
+            .filter(|input| !input.path.is_empty())
+            .map(|input| vec![input])
+            .ok_or_else(|| {
+                AppError::invalid(format!("Input {} is required.", input_letter(index)))
+            }),
+        _ => {
+            let sources: Vec<ExportInput> = inputs
+                .into_iter()
+                .filter(|input| !input.path.is_empty())
+                .collect();
+            if sources.len() < 2 {
+                return Err(AppError::invalid(format!(
+                    "At least two inputs are required for {} export.",
+                    params.export_mode
+                )));
+            }
+            Ok(sources)
+        }
     }
+}
 
+/// Per-input filters applied before any layout: frame rate and resize.
+fn input_filters(params: &ExportParams, input: &ExportInput) -> Vec<String> {
     let mut filters: Vec<String> = Vec::new();
-    let mut uses_select = false;
-
-    if let Some(end) = params.trim_end_frame {
-        let start = params.trim_start_frame.unwrap_or(0);
-        let end = end.max(start);
-        filters.push(format!(
-            "select=between(n\\,{start}\\,{end}),setpts=N/FRAME_RATE/TB"
-        ));
-        uses_select = true;
-    }
 
     if let Some(fps) = params.fps {
         filters.push(format!("fps=fps={fps}"));
     }
 
-    if params.resize_width.is_some() || params.resize_height.is_some() {
-        let width = params
+    if input.resize_width.is_some() || input.resize_height.is_some() {
+        let width = input
             .resize_width
             .map(|v| v.to_string())
             .unwrap_or_else(|| "-1".into());
-        let height = params
+        let height = input
             .resize_height
             .map(|v| v.to_string())
             .unwrap_or_else(|| "-1".into());
@@ -452,205 +587,616 @@ fn export_video(
         }
         filters.push(scale);
     }
+    filters
+}
 
-    if export_mode == "side-by-side" {
-        let mut left_filters: Vec<String> = Vec::new();
-        let mut right_filters: Vec<String> = Vec::new();
-
-        if !filters.is_empty() {
-            left_filters.extend(filters.clone());
-            right_filters.extend(filters.clone());
-        }
+/// Builds the ffmpeg invocations for an export, one per encoding pass, without
+/// touching the filesystem, so every mode and parameter combination can be
+/// checked without ffmpeg present. `infos` holds the probe result for each entry
+/// of `export_sources`, used to convert trim frames to timestamps and to size
+/// composite layouts. Two-pass exports write their stats to `pass_log`.
+fn build_export_commands(
+    ffmpeg: &Path,
+    params: &ExportParams,
+    output_path: &str,
+    infos: &[Option<&VideoInfo>],
+    pass_log: &Path,
+) -> AppResult<Vec<FfmpegCommand>> {
+    let encoder = EncoderSettings::from_params(params, output_path)?;
+    if !encoder.two_pass {
+        return Ok(vec![build_export_command(
+            ffmpeg,
+            params,
+            output_path,
+            infos,
+            &encoder,
+            None,
+        )?]);
+    }
+    (1..=2)
+        .map(|number| {
+            let pass = Pass {
+                number,
+                log: pass_log,
+            };
+            build_export_command(ffmpeg, params, output_path, infos, &encoder, Some(&pass))
+        })
+        .collect()
+}
 
-        if let Some(height) = params.stack_height {
-            // Use -2 to force even width for encoders like libx264.
-            left_filters.push(format!("scale=-2:{height}:flags=lanczos"));
-            right_filters.push(format!("scale=-2:{height}:flags=lanczos"));
+/// Builds one ffmpeg run of an export. The first of two passes only analyzes
+/// the video and discards its output.
+fn build_export_command(
+    ffmpeg: &Path,
+    params: &ExportParams,
+    output_path: &str,
+    infos: &[Option<&VideoInfo>],
+    encoder: &EncoderSettings,
+    pass: Option<&Pass>,
+) -> AppResult<FfmpegCommand> {
+    let mode = ExportMode::parse(&params.export_mode)?;
+    let sources = export_sources(params, mode)?;
+    let info_at = |index: usize| infos.get(index).copied().flatten();
+
+    let mut command = FfmpegCommand::new(ffmpeg);
+    let mut primary_trim = None;
+    for (index, source) in sources.iter().enumerate() {
+        let trim = trim_window_for(source, info_at(index))?;
+        if index == 0 {
+            primary_trim = trim;
         }
+        let options = trim.map(|trim| trim.input_options());
+        command.input_with_options(options.unwrap_or_default(), source.path.as_str());
+    }
 
-        if let Some(label) = params.label_a.as_deref() {
-            left_filters.extend(label_filters(label));
+    let paths: Vec<&str> = sources.iter().map(|source| source.path.as_str()).collect();
+    let tracks = tracks::plan_tracks(params, &paths, infos, output_path)?;
+    let hdr_mode = HdrMode::from_params(params)?;
+    let tonemap = match hdr_mode {
+        Some(HdrMode::Tonemap) => color::tonemap_filters(params)?,
+        _ => Vec::new(),
+    };
+    let tonemapped = |index: usize| !tonemap.is_empty() && color::hdr_of(info_at(index)).is_some();
+    // Per-input video pad and leading filters: tonemapping and color
+    // normalization, then subtitles burned in, so text is rendered at SDR levels.
+    let video_input = |index: usize| -> (String, Vec<String>) {
+        let mut filters = Vec::new();
+        if tonemapped(index) {
+            filters.extend(tonemap.iter().cloned());
         }
-        if let Some(label) = params.label_b.as_deref() {
-            right_filters.extend(label_filters(label));
+        if mode.is_composite() {
+            filters.extend(color::normalize_filters(infos, index, tonemapped(index)));
         }
-
-        let mut complex_filter = String::new();
-        if !left_filters.is_empty() {
-            complex_filter.push_str(&format!("[0:v]{}[left];", left_filters.join(",")));
-        } else {
-            complex_filter.push_str("[0:v]null[left];");
+        let mut pad = format!("{index}:v");
+        if let Some(burn_in) = tracks.burn_in.as_ref().filter(|b| b.input == index) {
+            let trim = trim_window_for(&sources[index], info_at(index))
+                .ok()
+                .flatten();
+            filters.extend(burn_in.filters(trim.map(|trim| trim.start_sec)));
+            if let Some((_, overlay_pad)) = burn_in.overlay_chain() {
+                pad = overlay_pad;
+            }
         }
-        if !right_filters.is_empty() {
-            complex_filter.push_str(&format!("[1:v]{}[right];", right_filters.join(",")));
-        } else {
-            complex_filter.push_str("[1:v]null[right];");
+        filters.extend(input_filters(params, &sources[index]));
+        (pad, filters)
+    };
+    let overlay = tracks
+        .burn_in
+        .as_ref()
+        .and_then(|burn_in| burn_in.overlay_chain())
+        .map(|(chain, _)| chain);
+
+    if mode.is_composite() {
+        // Overlapping layouts need one frame size: the first input's resize
+        // target if fully specified, otherwise the first probed size.
+        let tile = match (sources[0].resize_width, sources[0].resize_height) {
+            (Some(width), Some(height)) => Some((width, height)),
+            _ => (0..sources.len()).find_map(|index| {
+                info_at(index)
+                    .and_then(|info| info.video.as_ref())
+                    .and_then(|video| video.width.zip(video.height))
+            }),
+        };
+        let branches = (0..sources.len())
+            .map(|index| {
+                let (pad, filters) = video_input(index);
+                Branch {
+                    chain: FilterChain::new().input(pad).filters(filters),
+                    caption: sources[index].label.clone(),
+                }
+            })
+            .collect();
+        let mut graph = layout::compose(mode, params, branches, tile)?;
+        if let Some(chain) = overlay {
+            graph.push(chain);
         }
-        complex_filter.push_str("[left][right]hstack=inputs=2[vout]");
-        args.push("-filter_complex".to_string());
-        args.push(complex_filter);
-        args.push("-map".to_string());
-        args.push("[vout]".to_string());
-        if params.audio_copy {
-            args.push("-map".to_string());
-            args.push("0:a?".to_string());
+        command.filter_graph(graph);
+        command.map("[vout]");
+        if mode.keeps_audio() {
+            if !tracks.audio_maps.is_empty() {
+                for map in &tracks.audio_maps {
+                    command.map(map.as_str());
+                }
+            } else if params.audio_copy || params.track_source.is_some() {
+                command.map(format!("{}:a?", tracks.source));
+            }
         }
     } else {
-        let label = if export_mode == "input-b" {
-            params.label_b.as_deref()
-        } else {
-            params.label_a.as_deref()
-        };
-        if let Some(label) = label {
+        let source = &sources[0];
+        let (pad, mut filters) = video_input(0);
+        if let Some(label) = source.label.as_deref() {
             filters.extend(label_filters(label));
         }
-        if !filters.is_empty() {
-            args.push("-vf".to_string());
-            args.push(filters.join(","));
+        if let Some(chain) = overlay {
+            let mut graph = FilterGraph::new();
+            graph.push(chain);
+            graph.push(
+                FilterChain::new()
+                    .input(pad)
+                    .filters(filters)
+                    .output("vout"),
+            );
+            command.filter_graph(graph);
+            command.map("[vout]");
+        } else {
+            if tracks.needs_explicit_maps() {
+                command.map("0:v:0");
+            }
+            command.video_filters(filters);
+        }
+        if tracks.needs_explicit_maps() {
+            if tracks.audio_maps.is_empty() {
+                command.map("0:a:0?");
+            }
+            for map in &tracks.audio_maps {
+                command.map(map.as_str());
+            }
         }
     }
+    for map in &tracks.subtitle_maps {
+        command.map(map.as_str());
+    }
 
-    if uses_select {
-        args.push("-vsync".to_string());
-        args.push("vfr".to_string());
+    // Pin the exact frame count unless the frame rate is being changed.
+    if let Some(frame_count) = primary_trim.and_then(|trim| trim.frame_count) {
+        if params.fps.is_none() && mode != ExportMode::Sequence {
+            command.option("-frames:v", frame_count.to_string());
+        }
     }
 
-    match params.codec.as_str() {
-        "h265" => {
-            args.push("-c:v".to_string());
-            args.push("libx265".to_string());
+    let x265_params = match hdr_mode {
+        Some(HdrMode::Preserve) if mode.is_8bit() => {
+            return Err(AppError::invalid(format!(
+                "HDR cannot be preserved in {} exports, which are encoded as 8-bit video; use tonemap instead.",
+                mode.display_name()
+            )));
         }
-        _ => {
-            args.push("-c:v".to_string());
-            args.push("libx264".to_string());
+        Some(HdrMode::Preserve) => {
+            color::apply_hdr_output(&mut command, encoder.codec, info_at(0))?
+        }
+        Some(HdrMode::Tonemap) if (0..sources.len()).any(tonemapped) => {
+            color::apply_sdr_output(&mut command);
+            Vec::new()
+        }
+        _ if mode.is_composite() && color::inputs_differ(infos) => {
+            color::apply_sdr_output(&mut command);
+            Vec::new()
         }
+        _ => Vec::new(),
+    };
+    encoder.apply(&mut command, params, pass, x265_params);
+
+    let analysis_pass = pass.is_some_and(|pass| pass.number == 1);
+    if !mode.keeps_audio() || analysis_pass {
+        command.flag("-an");
+    } else if params.audio_copy {
+        command.option("-c:a", "copy");
+    } else {
+        command.option("-c:a", encoder.audio_encoder());
     }
+    if analysis_pass {
+        command.flag("-sn");
+    } else if let Some(codec) = tracks.subtitle_codec {
+        command.option("-c:s", codec);
+    }
+
+    command.flag("-y");
+    command.option("-progress", "pipe:1");
+    command.flag("-nostats");
+    if analysis_pass {
+        command.option("-f", "null");
+        command.output("-");
+    } else {
+        command.output(output_path);
+    }
+
+    Ok(command)
+}
 
-    args.push("-crf".to_string());
-    args.push(params.crf.to_string());
+/// Length of the part of `info` that the export will encode, in microseconds.
+fn expected_duration_us(input: &ExportInput, info: Option<&VideoInfo>) -> Option<u64> {
+    let info = info?;
+    let duration = info.container.duration_sec?;
+    let seconds = match trim_window_for(input, Some(info)).ok().flatten() {
+        Some(trim) => {
+            let remaining = duration - trim.start_sec;
+            trim.duration_sec.map_or(remaining, |d| d.min(remaining))
+        }
+        None => duration,
+    };
+    Some((seconds.max(0.0) * 1_000_000.0) as u64)
+}
 
-    if params.audio_copy {
-        args.push("-c:a".to_string());
-        args.push("copy".to_string());
+/// Number of frames the export will take from `info`, if known up front.
+fn expected_frames(input: &ExportInput, info: Option<&VideoInfo>) -> Option<u64> {
+    match trim_window_for(input, info).ok().flatten() {
+        Some(trim) => trim.frame_count,
+        None => info
+            .and_then(|info| info.video.as_ref())
+            .and_then(|video| video.frame_count),
+    }
+}
+
+fn export_progress_target(
+    params: &ExportParams,
+    infos: &[Option<&VideoInfo>],
+    output_path: &str,
+) -> ProgressTarget {
+    let mode = ExportMode::parse(&params.export_mode).unwrap_or(ExportMode::Single(0));
+    let sources = export_sources(params, mode).unwrap_or_default();
+    let per_input = |measure: fn(&ExportInput, Option<&VideoInfo>) -> Option<u64>| {
+        sources
+            .iter()
+            .enumerate()
+            .map(|(index, source)| measure(source, infos.get(index).copied().flatten()))
+            .collect::<Vec<_>>()
+    };
+    let durations = per_input(expected_duration_us);
+    let total_duration_us = match mode {
+        // Concatenated inputs play back to back; the total is only known if
+        // every part is.
+        ExportMode::Sequence => durations.iter().copied().sum(),
+        // Other composite layouts run until the longest input ends.
+        _ if mode.is_composite() => durations.iter().flatten().copied().max(),
+        _ => durations.first().copied().flatten(),
+    };
+    let total_frames = if params.fps.is_some() {
+        None
     } else {
-        args.push("-c:a".to_string());
-        args.push("aac".to_string());
+        let frames = per_input(expected_frames);
+        match mode {
+            ExportMode::Sequence => frames.iter().copied().sum(),
+            _ => frames.first().copied().flatten(),
+        }
+    };
+    ProgressTarget {
+        total_duration_us,
+        total_frames,
+        output_path: Some(PathBuf::from(output_path)),
+        passes: if params.two_pass { 2 } else { 1 },
     }
+}
 
-    args.push("-y".to_string());
-    args.push("-progress".to_string());
-    args.push("pipe:1".to_string());
-    args.push("-nostats".to_string());
-    args.push(output_path.clone());
+/// Everything needed to run an export, shared by the GUI commands and the CLI.
+struct PreparedExport {
+    /// The de-duplicated output path.
+    output_path: String,
+    /// ffmpeg runs in order, one per encoding pass.
+    commands: Vec<FfmpegCommand>,
+    /// Stats file prefix of two-pass exports, removed once they finish.
+    pass_log: PathBuf,
+    target: ProgressTarget,
+    /// Non-fatal notes about the inputs, e.g. differing color spaces.
+    warnings: Vec<String>,
+}
 
-    let command_string = build_command_string(&ffmpeg, &args);
+/// Probes what the export needs and builds its ffmpeg commands. `probe` is only
+/// called for the inputs the export mode uses.
+fn prepare_export(
+    ffmpeg: &Path,
+    probe: impl Fn(&str) -> AppResult<VideoInfo>,
+    export_id: &str,
+    params: &ExportParams,
+) -> AppResult<PreparedExport> {
+    // Probe the used inputs for frame rate, duration and color. Trimming and
+    // HDR handling cannot work without them; otherwise they only size
+    // composite layouts and feed the progress percentage.
+    let mode = ExportMode::parse(&params.export_mode)?;
+    let mut infos = Vec::new();
+    for source in export_sources(params, mode)? {
+        match probe(&source.path) {
+            Ok(info) => infos.push(Some(info)),
+            Err(error) if trim_requested(&source) || HdrMode::from_params(params)?.is_some() => {
+                return Err(error)
+            }
+            Err(_) => infos.push(None),
+        }
+    }
+    let infos: Vec<Option<&VideoInfo>> = infos.iter().map(Option::as_ref).collect();
 
-    let mut child = Command::new(ffmpeg)
-        .args(&args)
-        .creation_flags(CREATE_NO_WINDOW)
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()
-        .map_err(|e| format!("Failed to start ffmpeg: {}", e))?;
+    let output_path = unique_output_path(&params.output_path);
+    let pass_log = encoder::pass_log_path(export_id);
+    let commands = build_export_commands(ffmpeg, params, &output_path, &infos, &pass_log)?;
+    let warnings = if mode.is_composite() {
+        color::color_warnings(&infos, HdrMode::from_params(params)?)
+    } else {
+        Vec::new()
+    };
+    let target = export_progress_target(params, &infos, &output_path);
 
-    let stdout = child
-        .stdout
-        .take()
-        .ok_or_else(|| "Failed to capture ffmpeg stdout".to_string())?;
-    let stderr = child
-        .stderr
-        .take()
-        .ok_or_else(|| "Failed to capture ffmpeg stderr".to_string())?;
+    Ok(PreparedExport {
+        output_path,
+        commands,
+        pass_log,
+        target,
+        warnings,
+    })
+}
 
-    let export_id = Uuid::new_v4().to_string();
+impl PreparedExport {
+    /// The commands as typed into a shell, joined with `&&` for multi-pass exports.
+    fn command_line(&self) -> String {
+        self.commands
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(" && ")
+    }
+}
+
+/// Probes what the export needs, builds the command and spawns ffmpeg under `export_id`.
+/// Prepares and spawns an export whose id is already registered with the
+/// `ExportManager`; the id is released again if the export cannot start.
+fn start_export(
+    app: &AppHandle,
+    export_manager: &ExportManager,
+    export_id: String,
+    params: &ExportParams,
+    on_finish: Option<FinishHook>,
+) -> AppResult<ExportStarted> {
+    let cache = app.state::<ProbeCache>();
+    let probe = |path: &str| {
+        resolve_bundled_binary(app, "ffprobe").and_then(|ffprobe| cache.probe(&ffprobe, path))
+    };
+    let prepared = match resolve_bundled_binary(app, "ffmpeg")
+        .and_then(|ffmpeg| prepare_export(&ffmpeg, probe, &export_id, params))
     {
-        let mut children = export_manager.children.lock().map_err(|_| "Lock error")?;
-        children.insert(export_id.clone(), child);
+        Ok(prepared) => prepared,
+        Err(error) => {
+            export_manager.release(&export_id);
+            return Err(error);
+        }
+    };
+    let command = prepared.command_line();
+    let PreparedExport {
+        output_path,
+        commands,
+        pass_log,
+        target,
+        warnings,
+    } = prepared;
+
+    // Two-pass stats files are only needed until the second pass exits.
+    let on_finish: Option<FinishHook> = if commands.len() > 1 {
+        let pass_log = pass_log.clone();
+        Some(Box::new(move |app: &AppHandle, outcome: &ExportOutcome| {
+            encoder::remove_pass_logs(&pass_log);
+            if let Some(on_finish) = on_finish {
+                on_finish(app, outcome);
+            }
+        }))
+    } else {
+        on_finish
+    };
+    if let Err(error) = spawn_tracked_ffmpeg(
+        app,
+        export_manager,
+        "export-progress",
+        export_id.clone(),
+        &commands,
+        target,
+        on_finish,
+    ) {
+        encoder::remove_pass_logs(&pass_log);
+        return Err(error);
     }
 
-    let app_handle = app.clone();
+    Ok(ExportStarted {
+        export_id,
+        command,
+        output_path,
+        warnings,
+    })
+}
 
-    let export_id_for_thread = export_id.clone();
-    let children = export_manager.children.clone();
-    let stderr_buffer = Arc::new(Mutex::new(String::new()));
-    let stderr_clone = stderr_buffer.clone();
-    thread::spawn(move || {
-        let mut reader = BufReader::new(stderr);
-        let mut buffer = String::new();
-        let _ = reader.read_to_string(&mut buffer);
-        if let Ok(mut slot) = stderr_clone.lock() {
-            *slot = buffer;
+#[tauri::command]
+fn export_video(
+    app: AppHandle,
+    export_manager: State<'_, ExportManager>,
+    params: ExportParams,
+) -> AppResult<ExportStarted> {
+    let export_id = Uuid::new_v4().to_string();
+    export_manager.register(&export_id)?;
+    start_export(&app, &export_manager, export_id, &params, None)
+}
+
+/// Called once a tracked ffmpeg process exits. Not called when the process
+/// could not be spawned; the caller gets that error directly.
+type FinishHook = Box<dyn FnOnce(&AppHandle, &ExportOutcome) + Send>;
+
+/// A running ffmpeg process with its output pipes taken.
+struct SpawnedFfmpeg {
+    child: Child,
+    stdout: ChildStdout,
+    stderr: ChildStderr,
+}
+
+fn spawn_ffmpeg(command: &FfmpegCommand) -> Result<SpawnedFfmpeg, String> {
+    let mut child = tool_command(command.program())
+        .args(command.args())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to start ffmpeg: {}", e))?;
+    let stdout = child.stdout.take();
+    let stderr = child.stderr.take();
+    match (stdout, stderr) {
+        (Some(stdout), Some(stderr)) => Ok(SpawnedFfmpeg {
+            child,
+            stdout,
+            stderr,
+        }),
+        _ => {
+            let _ = child.kill();
+            Err("Failed to capture ffmpeg output".to_string())
         }
-    });
+    }
+}
 
-    thread::spawn(move || {
-        let reader = BufReader::new(stdout);
-        let mut out_time_ms: Option<u64> = None;
+/// Spawns ffmpeg with `-progress pipe:1` output, registers the child in the
+/// `ExportManager` so it can be cancelled, and forwards progress to `event`.
+/// `commands` run one after another (e.g. the passes of a two-pass encode),
+/// stopping at the first failure. The last event on `event` always carries the
+/// run's `ExportOutcome`. `job_id` must be registered with the `ExportManager`;
+/// it is released once the run ends or fails to start.
+fn spawn_tracked_ffmpeg(
+    app: &AppHandle,
+    export_manager: &ExportManager,
+    event: &'static str,
+    job_id: String,
+    commands: &[FfmpegCommand],
+    target: ProgressTarget,
+    on_finish: Option<FinishHook>,
+) -> AppResult<()> {
+    let mut tracker = ProgressTracker::new(job_id.clone(), target);
+    let Some((first, rest)) = commands.split_first() else {
+        export_manager.release(&job_id);
+        return Err(AppError::invalid("Nothing to run."));
+    };
+    let spawned = match spawn_ffmpeg(first) {
+        Ok(spawned) => spawned,
+        Err(message) => {
+            export_manager.release(&job_id);
+            let outcome = ExportOutcome::SpawnFailed {
+                message: message.clone(),
+            };
+            let _ = app.emit(event, tracker.finish(outcome));
+            return Err(AppError::spawn(message));
+        }
+    };
 
-        for line in reader.lines().flatten() {
-            if let Some((key, value)) = line.split_once('=') {
-                if key == "out_time_ms" {
-                    out_time_ms = value.parse::<u64>().ok();
+    let app_handle = app.clone();
+    let manager = export_manager.clone();
+    let children = export_manager.children.clone();
+    let cancelled = export_manager.cancelled.clone();
+    let rest = rest.to_vec();
+
+    // Registers the child and forwards its progress until it exits.
+    let run = {
+        let children = children.clone();
+        let cancelled = cancelled.clone();
+        let app_handle = app_handle.clone();
+        let job_id = job_id.clone();
+        move |spawned: SpawnedFfmpeg, tracker: &mut ProgressTracker| {
+            let SpawnedFfmpeg {
+                child,
+                stdout,
+                stderr,
+            } = spawned;
+            if let Ok(mut children) = children.lock() {
+                let mut child = child;
+                // Cancelled while this pass was being spawned.
+                if is_cancelled(&cancelled, &job_id) {
+                    let _ = child.kill();
                 }
-                if key == "progress" {
-                    let payload = ExportProgress {
-                        export_id: export_id_for_thread.clone(),
-                        progress: value.to_string(),
-                        out_time_ms,
-                        message: None,
-                    };
-                    let _ = app_handle.emit("export-progress", payload);
+                children.insert(job_id.clone(), child);
+            }
+            let stderr_reader = thread::spawn(move || {
+                let mut reader = BufReader::new(stderr);
+                let mut buffer = String::new();
+                let _ = reader.read_to_string(&mut buffer);
+                buffer
+            });
+
+            let reader = BufReader::new(stdout);
+            for line in reader.lines().map_while(Result::ok) {
+                if let Some(payload) = tracker.push_line(&line) {
+                    let _ = app_handle.emit(event, payload);
                 }
             }
+
+            let child = children.lock().ok().and_then(|mut map| map.remove(&job_id));
+            let status = child.and_then(|mut child| child.wait().ok());
+            (status, stderr_reader.join().unwrap_or_default())
         }
+    };
 
-        if let Ok(mut map) = children.lock() {
-            if let Some(mut child) = map.remove(&export_id_for_thread) {
-                let status = child.wait();
-                if let Ok(status) = status {
-                    if !status.success() {
-                        let message = stderr_buffer.lock().ok().and_then(|data| {
-                            let trimmed = data.trim();
-                            if trimmed.is_empty() {
-                                None
-                            } else {
-                                let lines: Vec<&str> = trimmed.lines().collect();
-                                let start = lines.len().saturating_sub(8);
-                                Some(lines[start..].join("\n"))
-                            }
-                        });
-                        let payload = ExportProgress {
-                            export_id: export_id_for_thread.clone(),
-                            progress: "error".to_string(),
-                            out_time_ms: None,
-                            message,
-                        };
-                        let _ = app_handle.emit("export-progress", payload);
-                    }
+    thread::spawn(move || {
+        let mut result = run(spawned, &mut tracker);
+        for command in &rest {
+            if !result.0.is_some_and(|status| status.success()) {
+                break;
+            }
+            // Checked right before spawning: a cancel may arrive between passes.
+            if is_cancelled(&cancelled, &job_id) {
+                break;
+            }
+            tracker.next_pass();
+            match spawn_ffmpeg(command) {
+                Ok(spawned) => result = run(spawned, &mut tracker),
+                Err(message) => {
+                    result = (None, message);
+                    break;
                 }
             }
         }
+        let (status, stderr) = result;
+        let was_cancelled = manager.release(&job_id);
+
+        let outcome = match status {
+            _ if was_cancelled => ExportOutcome::Cancelled,
+            Some(status) if status.success() => ExportOutcome::Succeeded {
+                output_size_bytes: tracker
+                    .output_path()
+                    .and_then(|path| fs::metadata(path).ok())
+                    .map(|m| m.len()),
+                elapsed_sec: tracker.elapsed_sec(),
+            },
+            status => ExportOutcome::Failed {
+                exit_code: status.and_then(|status| status.code()),
+                stderr_tail: stderr_tail(&stderr),
+            },
+        };
+        let _ = app_handle.emit(event, tracker.finish(outcome.clone()));
+
+        if let Some(on_finish) = on_finish {
+            on_finish(&app_handle, &outcome);
+        }
     });
 
-    Ok(ExportStarted {
-        export_id,
-        command: command_string,
-        output_path,
-    })
+    Ok(())
 }
 
-#[tauri::command]
-fn cancel_export(
-    export_manager: State<'_, ExportManager>,
-    export_id: String,
-) -> Result<(), String> {
-    let mut children = export_manager.children.lock().map_err(|_| "Lock error")?;
-    if let Some(child) = children.get_mut(&export_id) {
-        child
-            .kill()
-            .map_err(|e| format!("Failed to cancel export: {}", e))?;
+fn is_cancelled(cancelled: &Mutex<HashSet<String>>, id: &str) -> bool {
+    cancelled
+        .lock()
+        .map(|cancelled| cancelled.contains(id))
+        .unwrap_or(false)
+}
+
+fn stderr_tail(data: &str) -> Option<String> {
+    let trimmed = data.trim();
+    if trimmed.is_empty() {
+        return None;
     }
-    Ok(())
+    let lines: Vec<&str> = trimmed.lines().collect();
+    let start = lines.len().saturating_sub(8);
+    Some(lines[start..].join("\n"))
+}
+
+#[tauri::command]
+fn cancel_export(export_manager: State<'_, ExportManager>, export_id: String) -> AppResult<()> {
+    export_manager.kill(&export_id)
 }
 
 pub fn run() {
@@ -658,11 +1204,362 @@ pub fn run() {
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_opener::init())
         .manage(ExportManager::default())
+        .manage(queue::JobQueue::default())
+        .manage(presets::PresetStore::default())
+        .setup(|app| {
+            // Before the queue restarts anything that probes its inputs.
+            app.manage(ProbeCache::open(app.handle()));
+            queue::restore(app.handle());
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
-            probe_video,
+            probe::probe_video,
+            probe::probe_videos,
+            timeline::deep_probe,
+            cache::probe_cache_stats,
+            cache::clear_probe_cache,
+            diff::diff_video_info,
             export_video,
-            cancel_export
+            cancel_export,
+            metrics::compute_quality_metrics,
+            analysis::analyze_frames,
+            report::export_report,
+            frames::extract_frame,
+            frames::extract_frame_pair,
+            frames::contact_sheet,
+            queue::list_jobs,
+            queue::enqueue_export,
+            batch::batch_export,
+            batch::batch_status,
+            pairs::compare_folders,
+            queue::reorder_job,
+            queue::pause_job,
+            queue::resume_job,
+            queue::cancel_job,
+            queue::remove_job,
+            queue::clear_finished_jobs,
+            queue::set_queue_concurrency,
+            queue::set_queue_paused,
+            presets::list_presets,
+            presets::save_preset,
+            presets::load_preset,
+            presets::rename_preset,
+            presets::delete_preset,
+            presets::export_preset,
+            presets::import_preset
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    /// Export params with both inputs set; `overrides` replaces top-level fields.
+    fn params(overrides: Value) -> ExportParams {
+        let mut value = json!({
+            "inputPathA": "a.mp4",
+            "inputPathB": "b.mp4",
+            "exportMode": "side-by-side",
+            "outputPath": "out.mp4",
+            "codec": "h264",
+            "crf": 23,
+            "keepAspect": false,
+            "audioCopy": false,
+        });
+        for (key, field) in overrides.as_object().unwrap() {
+            value[key] = field.clone();
+        }
+        serde_json::from_value(value).unwrap()
+    }
+
+    /// A probed 1920x1080 video at `fps` without audio.
+    fn info(fps: f64) -> VideoInfo {
+        serde_json::from_value(json!({
+            "file": "a.mp4",
+            "size_bytes": 1000,
+            "container": { "format_name": "mov,mp4", "duration_sec": 10.0 },
+            "video": {
+                "index": 0,
+                "codec_name": "h264",
+                "width": 1920,
+                "height": 1080,
+                "pix_fmt": "yuv420p",
+                "fps": fps,
+            },
+            "audio": null,
+            "subtitles": [],
+            "attachments": [],
+            "streams": [{ "index": 0, "codec_type": "video", "disposition": [] }],
+        }))
+        .unwrap()
+    }
+
+    fn command_lines(params: &ExportParams, infos: &[Option<&VideoInfo>]) -> Vec<String> {
+        build_export_commands(
+            Path::new("ffmpeg"),
+            params,
+            "out.mp4",
+            infos,
+            Path::new("pass"),
+        )
+        .unwrap()
+        .iter()
+        .map(|command| command.args().join(" "))
+        .collect()
+    }
+
+    /// What every export run ends with: CRF 23 H.264, AAC audio and progress.
+    const ENCODE: &str = "-c:v libx264 -crf 23 -c:a aac -y -progress pipe:1 -nostats out.mp4";
+    /// Letterboxes an input to the 1920x1080 tile of the overlapping layouts.
+    const FIT: &str = "scale=1920:1080:flags=lanczos:force_original_aspect_ratio=decrease,\
+                       pad=1920:1080:(ow-iw)/2:(oh-ih)/2:color=black,setsar=1";
+
+    #[test]
+    fn command_line_per_export_mode() {
+        let a = info(25.0);
+        let b = info(25.0);
+        let infos = [Some(&a), Some(&b)];
+        let inputs = "-i a.mp4 -i b.mp4 -filter_complex";
+        let cases = [
+            ("input-a", format!("-i a.mp4 {ENCODE}")),
+            ("input-b", format!("-i b.mp4 {ENCODE}")),
+            (
+                "side-by-side",
+                format!(
+                    "{inputs} [0:v]scale=-2:1080:flags=lanczos[in0];\
+                     [1:v]scale=-2:1080:flags=lanczos[in1];[in0][in1]hstack=inputs=2[vout] \
+                     -map [vout] {ENCODE}"
+                ),
+            ),
+            (
+                "vertical-stack",
+                format!(
+                    "{inputs} [0:v]scale=1920:-2:flags=lanczos[in0];\
+                     [1:v]scale=1920:-2:flags=lanczos[in1];[in0][in1]vstack=inputs=2[vout] \
+                     -map [vout] {ENCODE}"
+                ),
+            ),
+            (
+                "wipe",
+                format!(
+                    "{inputs} [0:v]{FIT},format=yuv444p[in0];[1:v]{FIT},format=yuv444p[in1];\
+                     [in0][in1]blend=all_expr='if(lt(X,W*0.5),A,B)',format=yuv420p,\
+                     drawbox=x=iw*0.5-1:y=0:w=2:h=ih:color=white@0.8:t=fill[vout] \
+                     -map [vout] {ENCODE}"
+                ),
+            ),
+            (
+                "blend",
+                format!(
+                    "{inputs} [0:v]{FIT},format=yuv444p[in0];[1:v]{FIT},format=yuv444p[in1];\
+                     [in0][in1]blend=all_mode=difference,format=yuv420p[vout] \
+                     -map [vout] {ENCODE}"
+                ),
+            ),
+            (
+                "grid",
+                format!(
+                    "{inputs} [0:v]{FIT}[in0];[1:v]{FIT}[in1];\
+                     [in0][in1]xstack=inputs=2:layout=0_0|w0_0:fill=black[vout] \
+                     -map [vout] {ENCODE}"
+                ),
+            ),
+            (
+                "difference",
+                format!(
+                    "{inputs} [0:v]{FIT},format=gray[in0];[1:v]{FIT},format=gray[in1];\
+                     [in0][in1]blend=all_mode=difference,lut=c0='min(val*4,255)',\
+                     format=yuv420p[vout] -map [vout] {ENCODE}"
+                ),
+            ),
+            (
+                "sequence",
+                format!(
+                    "{inputs} [0:v]{FIT}[in0];[1:v]{FIT}[in1];\
+                     [in0][in1]concat=n=2:v=1:a=0[vout] -map [vout] {}",
+                    ENCODE.replace("-c:a aac", "-an")
+                ),
+            ),
+        ];
+        for (mode, expected) in cases {
+            let params = params(json!({ "exportMode": mode }));
+            assert_eq!(command_lines(&params, &infos), [expected], "{mode}");
+        }
+    }
+
+    #[test]
+    fn two_pass_export_discards_the_first_pass() {
+        let params = params(json!({
+            "exportMode": "input-a",
+            "rateControl": "abr",
+            "bitrateKbps": 4000,
+            "twoPass": true,
+        }));
+        assert_eq!(
+            command_lines(&params, &[None, None]),
+            [
+                "-i a.mp4 -c:v libx264 -b:v 4000k -pass 1 -passlogfile pass -an -sn -y \
+                 -progress pipe:1 -nostats -f null -",
+                "-i a.mp4 -c:v libx264 -b:v 4000k -pass 2 -passlogfile pass -c:a aac -y \
+                 -progress pipe:1 -nostats out.mp4",
+            ]
+        );
+    }
+
+    #[test]
+    fn trim_frames_cut_halfway_between_frames() {
+        let trim = TrimWindow::from_frames(Some(1), None, 30000.0 / 1001.0).unwrap();
+        // Frame 1 starts at 0.0333667s; the cut is half a frame earlier.
+        assert_eq!(format!("{:.6}", trim.start_sec), "0.016683");
+        assert!(trim.duration_sec.is_none());
+        assert!(trim.frame_count.is_none());
+
+        let trim = TrimWindow::from_frames(Some(10), Some(19), 25.0).unwrap();
+        assert!((trim.start_sec - 0.38).abs() < 1e-9);
+        assert!((trim.duration_sec.unwrap() - 0.4).abs() < 1e-9);
+        assert_eq!(trim.frame_count, Some(10));
+
+        let trim = TrimWindow::from_frames(None, Some(0), 25.0).unwrap();
+        assert_eq!(trim.start_sec, 0.0);
+        assert!((trim.duration_sec.unwrap() - 0.02).abs() < 1e-9);
+        assert_eq!(trim.frame_count, Some(1));
+
+        assert!(TrimWindow::from_frames(Some(0), None, 25.0).is_none());
+    }
+
+    #[test]
+    fn trimmed_export_pins_the_inclusive_frame_count() {
+        let a = info(25.0);
+        let params = params(json!({
+            "exportMode": "input-a",
+            "trimStartFrame": 10,
+            "trimEndFrame": 19,
+        }));
+        assert_eq!(
+            command_lines(&params, &[Some(&a)]),
+            [format!(
+                "-ss 0.380000 -t 0.400000 -i a.mp4 -frames:v 10 {ENCODE}"
+            )]
+        );
+
+        // A changed frame rate makes the source frame count meaningless.
+        let params = ExportParams {
+            fps: Some(50.0),
+            ..params
+        };
+        assert!(!command_lines(&params, &[Some(&a)])[0].contains("-frames:v"));
+    }
+
+    #[test]
+    fn extra_input_paths_still_add_grid_tiles() {
+        let params = params(json!({
+            "exportMode": "grid",
+            "extraInputPaths": ["c.mp4", ""],
+        }));
+        let paths: Vec<String> = params
+            .resolved_inputs()
+            .into_iter()
+            .map(|input| input.path)
+            .collect();
+        assert_eq!(paths, ["a.mp4", "b.mp4", "c.mp4", ""]);
+        let sources = export_sources(&params, ExportMode::Grid).unwrap();
+        assert_eq!(sources.len(), 3);
+
+        let params = ExportParams {
+            export_mode: "side-by-side".to_string(),
+            ..params
+        };
+        assert_eq!(params.resolved_inputs().len(), 2);
+    }
+
+    #[test]
+    fn overlapping_layouts_need_a_frame_size() {
+        let params = params(json!({ "exportMode": "blend" }));
+        let error = build_export_commands(
+            Path::new("ffmpeg"),
+            &params,
+            "out.mp4",
+            &[None, None],
+            Path::new("pass"),
+        )
+        .err()
+        .unwrap();
+        assert!(matches!(error, AppError::InvalidParams { .. }));
+    }
+
+    #[test]
+    fn preserving_hdr_is_rejected_for_8bit_layouts() {
+        let a = info(25.0);
+        let b = info(25.0);
+        for mode in ["wipe", "blend", "difference"] {
+            let params = params(json!({
+                "exportMode": mode,
+                "codec": "h265",
+                "hdrMode": "preserve",
+            }));
+            let error = build_export_commands(
+                Path::new("ffmpeg"),
+                &params,
+                "out.mp4",
+                &[Some(&a), Some(&b)],
+                Path::new("pass"),
+            )
+            .err()
+            .unwrap();
+            assert!(matches!(error, AppError::InvalidParams { .. }), "{mode}");
+        }
+    }
+
+    #[test]
+    fn probe_failures_are_tolerated_without_an_hdr_mode() {
+        let unprobed = |_: &str| Err(AppError::probe("unreadable"));
+        for hdr_mode in [json!(null), json!(""), json!("none")] {
+            let params = params(json!({ "exportMode": "input-a", "hdrMode": hdr_mode }));
+            assert!(prepare_export(Path::new("ffmpeg"), unprobed, "test", &params).is_ok());
+        }
+        let params = params(json!({ "exportMode": "input-a", "hdrMode": "tonemap" }));
+        assert!(prepare_export(Path::new("ffmpeg"), unprobed, "test", &params).is_err());
+    }
+
+    #[test]
+    fn cancelling_between_passes_is_remembered() {
+        let manager = ExportManager::default();
+        manager.register("export").unwrap();
+        manager.kill("export").unwrap();
+        assert!(is_cancelled(&manager.cancelled, "export"));
+        assert!(!is_cancelled(&manager.cancelled, "other"));
+        assert!(manager.release("export"));
+        assert!(!is_cancelled(&manager.cancelled, "export"));
+    }
+
+    #[test]
+    fn cancelling_before_the_first_spawn_is_kept() {
+        let manager = ExportManager::default();
+        manager.register("export").unwrap();
+        // E.g. while the inputs are still being probed.
+        manager.kill("export").unwrap();
+        assert!(is_cancelled(&manager.cancelled, "export"));
+    }
+
+    #[test]
+    fn unregistered_ids_are_not_marked() {
+        let manager = ExportManager::default();
+        manager.kill("unknown").unwrap();
+        manager.register("finished").unwrap();
+        assert!(!manager.release("finished"));
+        manager.kill("finished").unwrap();
+        assert!(manager.cancelled.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn registering_again_clears_an_old_mark() {
+        let manager = ExportManager::default();
+        manager.register("job").unwrap();
+        manager.kill("job").unwrap();
+        manager.register("job").unwrap();
+        assert!(!is_cancelled(&manager.cancelled, "job"));
+    }
+}
@@ -0,0 +1,452 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, State};
+use uuid::Uuid;
+
+use crate::cache::ProbeCache;
+use crate::error::{AppError, AppResult, ExportOutcome};
+use crate::ffmpeg::{FfmpegCommand, FilterChain, FilterGraph};
+use crate::progress::ProgressTarget;
+use crate::{escape_filter_value, resolve_bundled_binary, spawn_tracked_ffmpeg, ExportManager};
+
+/// Score given to frames the psnr filter reports as `inf`, i.e. identical to
+/// the reference. Well above any lossy encode (8-bit video rarely passes 60 dB),
+/// so lossless frames stay in the summary instead of being dropped.
+const PSNR_CEILING_DB: f64 = 100.0;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct MetricsParams {
+    /// Reference input (usually the source or the previous encode).
+    input_path_a: String,
+    /// Distorted input compared against the reference.
+    input_path_b: String,
+    /// Any of `psnr`, `ssim` and `vmaf`. Empty selects all three.
+    #[serde(default)]
+    metrics: Vec<String>,
+    /// Both inputs are scaled to this size before comparison when set.
+    scale_width: Option<u32>,
+    scale_height: Option<u32>,
+    /// libvmaf model version, e.g. `vmaf_v0.6.1` or `vmaf_4k_v0.6.1`.
+    vmaf_model: Option<String>,
+}
+
+#[derive(Serialize)]
+pub(crate) struct MetricsStarted {
+    metrics_id: String,
+    command: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub(crate) struct MetricSummary {
+    pub(crate) count: usize,
+    pub(crate) mean: f64,
+    pub(crate) min: f64,
+    pub(crate) max: f64,
+    pub(crate) p5: f64,
+    pub(crate) p50: f64,
+    pub(crate) p95: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub(crate) struct MetricSeries {
+    pub(crate) name: String,
+    pub(crate) summary: Option<MetricSummary>,
+    /// Per-frame values in presentation order. `None` marks frames without a
+    /// score; identical frames score `PSNR_CEILING_DB` for PSNR.
+    pub(crate) frames: Vec<Option<f64>>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub(crate) struct QualityReport {
+    pub(crate) reference: String,
+    pub(crate) distorted: String,
+    pub(crate) metrics: Vec<MetricSeries>,
+}
+
+#[derive(Serialize, Clone)]
+struct MetricsResult {
+    metrics_id: String,
+    report: Option<QualityReport>,
+    message: Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Metric {
+    Psnr,
+    Ssim,
+    Vmaf,
+}
+
+impl Metric {
+    fn parse(value: &str) -> AppResult<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "psnr" => Ok(Metric::Psnr),
+            "ssim" => Ok(Metric::Ssim),
+            "vmaf" => Ok(Metric::Vmaf),
+            other => Err(AppError::invalid(format!("Unknown metric: {other}"))),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Metric::Psnr => "psnr",
+            Metric::Ssim => "ssim",
+            Metric::Vmaf => "vmaf",
+        }
+    }
+
+    fn log_file(self) -> &'static str {
+        match self {
+            Metric::Psnr => "psnr.log",
+            Metric::Ssim => "ssim.log",
+            Metric::Vmaf => "vmaf.json",
+        }
+    }
+}
+
+fn filter_path(path: &Path) -> String {
+    escape_filter_value(&path.to_string_lossy().replace('\\', "/"))
+}
+
+fn build_metrics_filter(metrics: &[Metric], log_dir: &Path, params: &MetricsParams) -> FilterGraph {
+    let mut prepare: Vec<String> = Vec::new();
+    if params.scale_width.is_some() || params.scale_height.is_some() {
+        let width = params
+            .scale_width
+            .map(|v| v.to_string())
+            .unwrap_or_else(|| "-2".into());
+        let height = params
+            .scale_height
+            .map(|v| v.to_string())
+            .unwrap_or_else(|| "-2".into());
+        prepare.push(format!("scale={width}:{height}:flags=bicubic"));
+    }
+    prepare.push("settb=AVTB".to_string());
+    prepare.push("setpts=PTS-STARTPTS".to_string());
+
+    let split = format!("split={}", metrics.len());
+    let mut reference = FilterChain::new()
+        .input("0:v")
+        .filters(prepare.clone())
+        .filter(split.clone());
+    let mut distorted = FilterChain::new()
+        .input("1:v")
+        .filters(prepare)
+        .filter(split);
+    for index in 0..metrics.len() {
+        reference = reference.output(format!("ref{index}"));
+        distorted = distorted.output(format!("dist{index}"));
+    }
+
+    let mut graph = FilterGraph::new();
+    graph.push(reference);
+    graph.push(distorted);
+
+    for (index, metric) in metrics.iter().enumerate() {
+        let log = filter_path(&log_dir.join(metric.log_file()));
+        let filter = match metric {
+            Metric::Psnr => format!("psnr=stats_file='{log}'"),
+            Metric::Ssim => format!("ssim=stats_file='{log}'"),
+            Metric::Vmaf => {
+                let mut filter = format!("libvmaf=log_fmt=json:log_path='{log}'");
+                if let Some(model) = params.vmaf_model.as_deref() {
+                    let model = escape_filter_value(model);
+                    filter.push_str(&format!(":model='version={model}'"));
+                }
+                filter
+            }
+        };
+        // libvmaf expects the distorted stream first and the reference second.
+        graph.push(
+            FilterChain::new()
+                .input(format!("dist{index}"))
+                .input(format!("ref{index}"))
+                .filter(filter),
+        );
+    }
+
+    graph
+}
+
+/// Parses `key:value` tokens from a psnr/ssim stats line. `inf`, which only
+/// PSNR reports, becomes `PSNR_CEILING_DB`.
+fn stats_value(line: &str, key: &str) -> Option<f64> {
+    line.split_whitespace().find_map(|token| {
+        let (name, value) = token.split_once(':')?;
+        if name != key {
+            return None;
+        }
+        match value.parse::<f64>().ok()? {
+            f64::INFINITY => Some(PSNR_CEILING_DB),
+            value => Some(value).filter(|v| v.is_finite()),
+        }
+    })
+}
+
+fn parse_stats_log(contents: &str, key: &str) -> Vec<Option<f64>> {
+    contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| stats_value(line, key))
+        .collect()
+}
+
+fn parse_vmaf_log(contents: &str) -> AppResult<Vec<Option<f64>>> {
+    let json: serde_json::Value = serde_json::from_str(contents)
+        .map_err(|e| AppError::probe(format!("Failed to parse VMAF log: {}", e)))?;
+    let frames = json
+        .get("frames")
+        .and_then(|v| v.as_array())
+        .cloned()
+        .unwrap_or_default();
+    Ok(frames
+        .iter()
+        .map(|frame| {
+            frame
+                .get("metrics")
+                .and_then(|m| m.get("vmaf"))
+                .and_then(|v| v.as_f64())
+        })
+        .collect())
+}
+
+fn percentile(sorted: &[f64], fraction: f64) -> f64 {
+    let rank = fraction * (sorted.len() - 1) as f64;
+    let lower = rank.floor() as usize;
+    let upper = rank.ceil() as usize;
+    let weight = rank - lower as f64;
+    sorted[lower] + (sorted[upper] - sorted[lower]) * weight
+}
+
+fn summarize(frames: &[Option<f64>]) -> Option<MetricSummary> {
+    let mut values: Vec<f64> = frames.iter().flatten().copied().collect();
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(|a, b| a.total_cmp(b));
+    let mean = values.iter().sum::<f64>() / values.len() as f64;
+    Some(MetricSummary {
+        count: values.len(),
+        mean,
+        min: values[0],
+        max: values[values.len() - 1],
+        p5: percentile(&values, 0.05),
+        p50: percentile(&values, 0.5),
+        p95: percentile(&values, 0.95),
+    })
+}
+
+fn read_report(
+    metrics: &[Metric],
+    log_dir: &Path,
+    reference: String,
+    distorted: String,
+) -> AppResult<QualityReport> {
+    let mut series = Vec::new();
+    for metric in metrics {
+        let path = log_dir.join(metric.log_file());
+        let contents = fs::read_to_string(&path)
+            .map_err(|e| AppError::io(format!("Failed to read {} log: {}", metric.name(), e)))?;
+        let frames = match metric {
+            Metric::Psnr => parse_stats_log(&contents, "psnr_avg"),
+            Metric::Ssim => parse_stats_log(&contents, "All"),
+            Metric::Vmaf => parse_vmaf_log(&contents)?,
+        };
+        series.push(MetricSeries {
+            name: metric.name().to_string(),
+            summary: summarize(&frames),
+            frames,
+        });
+    }
+    Ok(QualityReport {
+        reference,
+        distorted,
+        metrics: series,
+    })
+}
+
+#[tauri::command]
+pub(crate) fn compute_quality_metrics(
+    app: AppHandle,
+    export_manager: State<'_, ExportManager>,
+    params: MetricsParams,
+) -> AppResult<MetricsStarted> {
+    let ffmpeg = resolve_bundled_binary(&app, "ffmpeg")?;
+    if params.input_path_a.is_empty() {
+        return Err(AppError::invalid("Input A is required."));
+    }
+    if params.input_path_b.is_empty() {
+        return Err(AppError::invalid("Input B is required."));
+    }
+
+    let mut metrics: Vec<Metric> = Vec::new();
+    for name in &params.metrics {
+        let metric = Metric::parse(name)?;
+        if !metrics.contains(&metric) {
+            metrics.push(metric);
+        }
+    }
+    if metrics.is_empty() {
+        metrics = vec![Metric::Psnr, Metric::Ssim, Metric::Vmaf];
+    }
+
+    let metrics_id = Uuid::new_v4().to_string();
+    let log_dir: PathBuf = std::env::temp_dir().join(format!("pixelduel-metrics-{metrics_id}"));
+    fs::create_dir_all(&log_dir)
+        .map_err(|e| AppError::io(format!("Failed to create metrics log directory: {}", e)))?;
+
+    let mut command = FfmpegCommand::new(&ffmpeg);
+    command.global("-hide_banner");
+    command.input(params.input_path_a.as_str());
+    command.input(params.input_path_b.as_str());
+    command.filter_graph(build_metrics_filter(&metrics, &log_dir, &params));
+    command.flag("-an");
+    command.option("-f", "null");
+    command.option("-progress", "pipe:1");
+    command.flag("-nostats");
+    command.output("-");
+
+    let reference = params.input_path_a.clone();
+    let distorted = params.input_path_b.clone();
+    let id_for_hook = metrics_id.clone();
+    let dir_for_hook = log_dir.clone();
+    let on_finish = Box::new(move |app: &AppHandle, outcome: &ExportOutcome| {
+        let payload = if outcome.succeeded() {
+            match read_report(&metrics, &dir_for_hook, reference, distorted) {
+                Ok(report) => MetricsResult {
+                    metrics_id: id_for_hook,
+                    report: Some(report),
+                    message: None,
+                },
+                Err(error) => MetricsResult {
+                    metrics_id: id_for_hook,
+                    report: None,
+                    message: Some(error.to_string()),
+                },
+            }
+        } else {
+            MetricsResult {
+                metrics_id: id_for_hook,
+                report: None,
+                message: outcome.summary(),
+            }
+        };
+        let _ = fs::remove_dir_all(&dir_for_hook);
+        let _ = app.emit("metrics-complete", payload);
+    });
+
+    // The metric filters run until the shorter input ends; the reference
+    // duration is close enough for a progress estimate.
+    let target = resolve_bundled_binary(&app, "ffprobe")
+        .and_then(|ffprobe| {
+            app.state::<ProbeCache>()
+                .probe(&ffprobe, &params.input_path_a)
+        })
+        .ok()
+        .and_then(|info| info.container.duration_sec)
+        .map(|duration| ProgressTarget {
+            total_duration_us: Some((duration * 1_000_000.0) as u64),
+            ..ProgressTarget::default()
+        })
+        .unwrap_or_default();
+
+    if let Err(error) = export_manager.register(&metrics_id).and_then(|()| {
+        spawn_tracked_ffmpeg(
+            &app,
+            &export_manager,
+            "metrics-progress",
+            metrics_id.clone(),
+            std::slice::from_ref(&command),
+            target,
+            Some(on_finish),
+        )
+    }) {
+        let _ = fs::remove_dir_all(&log_dir);
+        return Err(error);
+    }
+
+    Ok(MetricsStarted {
+        metrics_id,
+        command: command.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stats_log_reads_the_requested_key_per_frame() {
+        let log = "n:1 mse_avg:0.52 mse_y:0.61 psnr_avg:50.97 psnr_y:50.27\n\
+                   n:2 mse_avg:0.00 mse_y:0.00 psnr_avg:inf psnr_y:inf\n\
+                   \n\
+                   n:3 mse_avg:1.04 psnr_avg:47.96\n";
+        assert_eq!(
+            parse_stats_log(log, "psnr_avg"),
+            vec![Some(50.97), Some(PSNR_CEILING_DB), Some(47.96)]
+        );
+        let ssim = "n:1 Y:0.995 U:0.998 V:0.997 All:0.996 (23.979)\n";
+        assert_eq!(parse_stats_log(ssim, "All"), vec![Some(0.996)]);
+        assert_eq!(parse_stats_log(ssim, "missing"), vec![None]);
+    }
+
+    #[test]
+    fn lossless_psnr_logs_are_summarized_at_the_ceiling() {
+        let log = "n:1 mse_avg:0.00 psnr_avg:inf\n\
+                   n:2 mse_avg:0.00 psnr_avg:inf\n";
+        let frames = parse_stats_log(log, "psnr_avg");
+        assert_eq!(frames, vec![Some(PSNR_CEILING_DB); 2]);
+        let summary = summarize(&frames).unwrap();
+        assert_eq!(summary.count, 2);
+        assert_eq!(summary.min, PSNR_CEILING_DB);
+        assert_eq!(summary.mean, PSNR_CEILING_DB);
+        assert_eq!(
+            parse_stats_log("n:1 psnr_avg:nan\n", "psnr_avg"),
+            vec![None]
+        );
+    }
+
+    #[test]
+    fn vmaf_log_reads_frame_scores() {
+        let log = r#"{"frames": [
+            {"frameNum": 0, "metrics": {"vmaf": 95.5}},
+            {"frameNum": 1, "metrics": {"psnr": 40.0}},
+            {"frameNum": 2, "metrics": {"vmaf": 91}}
+        ]}"#;
+        assert_eq!(
+            parse_vmaf_log(log).unwrap(),
+            vec![Some(95.5), None, Some(91.0)]
+        );
+        assert!(parse_vmaf_log("{}").unwrap().is_empty());
+        assert!(matches!(
+            parse_vmaf_log("not json"),
+            Err(AppError::Probe { .. })
+        ));
+    }
+
+    #[test]
+    fn percentile_interpolates_between_ranks() {
+        let sorted = [10.0, 20.0, 30.0, 40.0, 50.0];
+        assert_eq!(percentile(&sorted, 0.0), 10.0);
+        assert_eq!(percentile(&sorted, 0.5), 30.0);
+        assert_eq!(percentile(&sorted, 1.0), 50.0);
+        assert!((percentile(&sorted, 0.05) - 12.0).abs() < 1e-9);
+        assert!((percentile(&sorted, 0.95) - 48.0).abs() < 1e-9);
+        assert_eq!(percentile(&[7.0], 0.95), 7.0);
+    }
+
+    #[test]
+    fn summarize_skips_missing_frames() {
+        assert!(summarize(&[]).is_none());
+        assert!(summarize(&[None, None]).is_none());
+        let summary = summarize(&[Some(3.0), None, Some(1.0), Some(2.0)]).unwrap();
+        assert_eq!(summary.count, 3);
+        assert_eq!(summary.mean, 2.0);
+        assert_eq!(summary.min, 1.0);
+        assert_eq!(summary.max, 3.0);
+        assert_eq!(summary.p50, 2.0);
+    }
+}
@@ -0,0 +1,432 @@
+use std::collections::btree_map::Entry;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use uuid::Uuid;
+
+use crate::batch::{pending_outputs, render_template, resolve_source};
+use crate::cache::ProbeCache;
+use crate::diff::{field_diffs, FieldDiff, Severity};
+use crate::encoder::{EncoderSettings, VideoCodec};
+use crate::error::{AppError, AppResult};
+use crate::layout::ExportMode;
+use crate::queue;
+use crate::{resolve_bundled_binary, unique_output_path_by, ExportInput, ExportParams};
+
+const DEFAULT_PAIR_TEMPLATE: &str = "{key}_{mode}.{ext}";
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PairExportOptions {
+    output_dir: String,
+    /// Output file name with `{key}`, `{ext}`, `{codec}`, `{crf}`, `{mode}` and
+    /// `{index}` fields. Defaults to `{key}_{mode}.{ext}`.
+    filename_template: Option<String>,
+    /// Settings for every pair's export; the reference becomes input A and the
+    /// candidate input B. Layout sizes left unset are derived from the probes.
+    params: ExportParams,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PairCompareParams {
+    reference_dir: String,
+    candidate_dir: String,
+    /// Suffixes removed from file stems before matching, e.g. `_ref` or `_x265`.
+    #[serde(default)]
+    strip_suffixes: Vec<String>,
+    /// Also require the extensions to match; by default `clip.mov` pairs with `clip.mp4`.
+    #[serde(default)]
+    match_extension: bool,
+    /// Probe both sides and list the fields that differ.
+    #[serde(default = "default_true")]
+    metadata_diff: bool,
+    /// Queue one comparison export per pair.
+    export: Option<PairExportOptions>,
+    /// Also write the summary as JSON to this file; ignored when blank.
+    summary_path: Option<String>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Serialize)]
+pub(crate) struct PairResult {
+    key: String,
+    reference: String,
+    candidate: String,
+    /// Fields that differ, with the reference as A and the candidate as B.
+    differences: Vec<FieldDiff>,
+    /// Why one side could not be probed; the pair is still exported.
+    probe_error: Option<String>,
+    job_id: Option<String>,
+    output_path: Option<String>,
+}
+
+#[derive(Serialize)]
+pub(crate) struct PairCompareSummary {
+    reference_dir: String,
+    candidate_dir: String,
+    pairs: Vec<PairResult>,
+    /// Reference files without a candidate.
+    missing_candidates: Vec<String>,
+    /// Candidate files without a reference.
+    missing_references: Vec<String>,
+    /// Files skipped because an earlier file on the same side had the same key.
+    duplicates: Vec<String>,
+    /// Pairs with a difference above `Info`; file size and bitrate always differ.
+    pairs_with_differences: usize,
+    /// Batch of the queued exports, for `batch_status` and `batch-progress`.
+    batch_id: Option<String>,
+}
+
+/// The name files are paired by: the stem without the first matching suffix,
+/// plus the lowercased extension when extensions have to match.
+fn pair_key(path: &Path, strip_suffixes: &[String], match_extension: bool) -> String {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let stem = strip_suffixes
+        .iter()
+        .map(|suffix| suffix.trim())
+        .filter(|suffix| !suffix.is_empty())
+        .find_map(|suffix| stem.strip_suffix(suffix))
+        .unwrap_or(&stem)
+        .to_string();
+    match path.extension().filter(|_| match_extension) {
+        Some(ext) => format!("{stem}.{}", ext.to_string_lossy().to_ascii_lowercase()),
+        None => stem,
+    }
+}
+
+/// Files of `dir` by key, in name order; later files with a taken key go to
+/// `duplicates`.
+fn keyed_files(
+    dir: &str,
+    params: &PairCompareParams,
+    duplicates: &mut Vec<String>,
+) -> AppResult<BTreeMap<String, PathBuf>> {
+    if !Path::new(dir.trim()).is_dir() {
+        return Err(AppError::not_found(format!(
+            "Folder not found: {}",
+            dir.trim()
+        )));
+    }
+    let mut files = BTreeMap::new();
+    for file in resolve_source(dir)? {
+        let key = pair_key(&file, &params.strip_suffixes, params.match_extension);
+        match files.entry(key) {
+            Entry::Vacant(entry) => {
+                entry.insert(file);
+            }
+            Entry::Occupied(_) => duplicates.push(file.to_string_lossy().to_string()),
+        }
+    }
+    Ok(files)
+}
+
+/// The export settings for one pair.
+fn pair_params(
+    params: &ExportParams,
+    reference: &Path,
+    candidate: &Path,
+    output_path: String,
+) -> ExportParams {
+    let mut params = params.clone();
+    let template = params.resolved_inputs();
+    let input = |index: usize, path: &Path| ExportInput {
+        path: path.to_string_lossy().to_string(),
+        ..template.get(index).cloned().unwrap_or_default()
+    };
+    params.inputs = vec![input(0, reference), input(1, candidate)];
+    params.input_path_a = None;
+    params.input_path_b = None;
+    params.output_path = output_path;
+    params
+}
+
+/// Pairs the files of two folders by name, probes both sides and optionally
+/// queues a comparison export per pair.
+#[tauri::command(async)]
+pub(crate) fn compare_folders(
+    app: AppHandle,
+    params: PairCompareParams,
+) -> AppResult<PairCompareSummary> {
+    let mut duplicates = Vec::new();
+    let references = keyed_files(&params.reference_dir, &params, &mut duplicates)?;
+    let mut candidates = keyed_files(&params.candidate_dir, &params, &mut duplicates)?;
+
+    let export = params.export.as_ref();
+    let mut taken = Default::default();
+    let mut output_dir = PathBuf::new();
+    let mut output_ext = String::new();
+    if let Some(export) = export {
+        let settings = &export.params;
+        ExportMode::parse(&settings.export_mode)?;
+        if export.output_dir.trim().is_empty() {
+            return Err(AppError::invalid("An output folder is required."));
+        }
+        // `{ext}` is the container of the output path, or the codec's default one.
+        output_ext = match Path::new(&settings.output_path)
+            .extension()
+            .and_then(|ext| ext.to_str())
+        {
+            Some(ext) => ext.to_string(),
+            None => VideoCodec::parse(&settings.codec)?
+                .default_container()
+                .to_string(),
+        };
+        EncoderSettings::from_params(settings, &format!("output.{output_ext}"))?;
+        output_dir = PathBuf::from(export.output_dir.trim());
+        fs::create_dir_all(&output_dir)
+            .map_err(|e| AppError::io(format!("Failed to create output folder: {}", e)))?;
+        taken = pending_outputs(&app)?;
+    }
+    let ffprobe = if params.metadata_diff || export.is_some() {
+        Some(resolve_bundled_binary(&app, "ffprobe")?)
+    } else {
+        None
+    };
+
+    let cache = app.state::<ProbeCache>();
+    let mut pairs = Vec::new();
+    let mut planned = Vec::new();
+    let mut missing_candidates = Vec::new();
+    for (key, reference) in &references {
+        let Some(candidate) = candidates.remove(key) else {
+            missing_candidates.push(reference.to_string_lossy().to_string());
+            continue;
+        };
+        let mut probe_error = None;
+        let mut probe = |path: &Path| {
+            let ffprobe = ffprobe.as_ref()?;
+            cache
+                .probe(ffprobe, &path.to_string_lossy())
+                .map_err(|error| {
+                    probe_error
+                        .get_or_insert_with(|| format!("{}: {}", path.display(), error.message()));
+                })
+                .ok()
+        };
+        let reference_info = probe(reference);
+        let candidate_info = probe(&candidate);
+        let differences = match (&reference_info, &candidate_info) {
+            (Some(a), Some(b)) if params.metadata_diff => field_diffs(a, b),
+            _ => Vec::new(),
+        };
+
+        let mut output_path = None;
+        if let Some(export) = export {
+            let template = export
+                .filename_template
+                .as_deref()
+                .unwrap_or(DEFAULT_PAIR_TEMPLATE);
+            let name = render_template(template, |field| match field {
+                "key" | "stem" => Some(key.clone()),
+                "ext" => Some(output_ext.clone()),
+                "codec" => Some(export.params.codec.clone()),
+                "crf" => Some(export.params.crf.to_string()),
+                "mode" => Some(export.params.export_mode.clone()),
+                "index" => Some((pairs.len() + 1).to_string()),
+                _ => None,
+            })?;
+            let path = output_dir.join(name).to_string_lossy().to_string();
+            // The template may name another container through a literal extension.
+            EncoderSettings::from_params(&export.params, &path)?;
+            let path = unique_output_path_by(&path, |candidate| {
+                candidate.exists() || taken.contains(candidate.to_string_lossy().as_ref())
+            });
+            taken.insert(path.clone());
+            planned.push(pair_params(
+                &export.params,
+                reference,
+                &candidate,
+                path.clone(),
+            ));
+            output_path = Some(path);
+        }
+
+        pairs.push(PairResult {
+            key: key.clone(),
+            reference: reference.to_string_lossy().to_string(),
+            candidate: candidate.to_string_lossy().to_string(),
+            differences,
+            probe_error,
+            job_id: None,
+            output_path,
+        });
+    }
+    let missing_references = candidates
+        .into_values()
+        .map(|path| path.to_string_lossy().to_string())
+        .collect();
+
+    let mut batch_id = None;
+    if !planned.is_empty() {
+        let id = Uuid::new_v4().to_string();
+        let jobs = queue::enqueue_jobs(&app, planned, Some(&id))?;
+        for (pair, job) in pairs.iter_mut().zip(jobs) {
+            pair.job_id = Some(job.id);
+        }
+        batch_id = Some(id);
+    }
+
+    let summary = PairCompareSummary {
+        reference_dir: params.reference_dir.trim().to_string(),
+        candidate_dir: params.candidate_dir.trim().to_string(),
+        pairs_with_differences: pairs
+            .iter()
+            .filter(|pair| {
+                pair.differences
+                    .iter()
+                    .any(|diff| diff.severity > Severity::Info)
+            })
+            .count(),
+        pairs,
+        missing_candidates,
+        missing_references,
+        duplicates,
+        batch_id,
+    };
+    if let Some(path) = params
+        .summary_path
+        .as_deref()
+        .map(str::trim)
+        .filter(|path| !path.is_empty())
+    {
+        let json = serde_json::to_string_pretty(&summary)
+            .map_err(|e| AppError::io(format!("Failed to serialize summary: {}", e)))?;
+        fs::write(path, json)
+            .map_err(|e| AppError::io(format!("Failed to write summary: {}", e)))?;
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn compare_params(strip_suffixes: &[&str], match_extension: bool) -> PairCompareParams {
+        serde_json::from_value(json!({
+            "referenceDir": "ref",
+            "candidateDir": "cand",
+            "stripSuffixes": strip_suffixes,
+            "matchExtension": match_extension,
+        }))
+        .unwrap()
+    }
+
+    fn suffixes(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn pair_key_strips_the_first_matching_suffix() {
+        let strip = suffixes(&["_ref", " ", "_x265"]);
+        assert_eq!(
+            pair_key(Path::new("dir/clip_ref.mov"), &strip, false),
+            "clip"
+        );
+        assert_eq!(pair_key(Path::new("clip_x265.mp4"), &strip, false), "clip");
+        assert_eq!(
+            pair_key(Path::new("clip_ref_x265.mp4"), &strip, false),
+            "clip_ref"
+        );
+        assert_eq!(pair_key(Path::new("clip.mp4"), &[], false), "clip");
+    }
+
+    #[test]
+    fn pair_key_keeps_the_lowercased_extension_when_asked() {
+        let strip = suffixes(&["_ref"]);
+        assert_eq!(
+            pair_key(Path::new("clip_ref.MOV"), &strip, true),
+            "clip.mov"
+        );
+        assert_eq!(pair_key(Path::new("clip"), &strip, true), "clip");
+    }
+
+    #[test]
+    fn keyed_files_reports_duplicates_and_leaves_unmatched_keys() {
+        let root = std::env::temp_dir().join(format!("pixelduel-pairs-{}", Uuid::new_v4()));
+        let reference_dir = root.join("ref");
+        let candidate_dir = root.join("cand");
+        fs::create_dir_all(&reference_dir).unwrap();
+        fs::create_dir_all(&candidate_dir).unwrap();
+        for name in ["a_ref.mp4", "b_ref.mov", "c.mov", "c.mp4", "notes.txt"] {
+            fs::write(reference_dir.join(name), b"").unwrap();
+        }
+        for name in ["a.mp4", "b_x265.mkv", "d.mp4"] {
+            fs::write(candidate_dir.join(name), b"").unwrap();
+        }
+
+        let params = compare_params(&["_ref", "_x265"], false);
+        let mut duplicates = Vec::new();
+        let references =
+            keyed_files(&reference_dir.to_string_lossy(), &params, &mut duplicates).unwrap();
+        let candidates =
+            keyed_files(&candidate_dir.to_string_lossy(), &params, &mut duplicates).unwrap();
+        let keys = |files: &BTreeMap<String, PathBuf>| files.keys().cloned().collect::<Vec<_>>();
+        assert_eq!(keys(&references), ["a", "b", "c"]);
+        assert_eq!(keys(&candidates), ["a", "b", "d"]);
+        assert_eq!(references["c"], reference_dir.join("c.mov"));
+        assert_eq!(
+            duplicates,
+            [reference_dir.join("c.mp4").to_string_lossy().to_string()]
+        );
+
+        // With extensions, `c.mov` and `c.mp4` no longer collide.
+        let params = compare_params(&["_ref"], true);
+        let mut duplicates = Vec::new();
+        let references =
+            keyed_files(&reference_dir.to_string_lossy(), &params, &mut duplicates).unwrap();
+        assert_eq!(keys(&references), ["a.mp4", "b.mov", "c.mov", "c.mp4"]);
+        assert!(duplicates.is_empty());
+
+        let missing = root.join("missing").to_string_lossy().to_string();
+        assert!(matches!(
+            keyed_files(&missing, &params, &mut duplicates),
+            Err(AppError::NotFound { .. })
+        ));
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn pair_params_puts_the_reference_first_and_keeps_input_settings() {
+        let params: ExportParams = serde_json::from_value(json!({
+            "exportMode": "side-by-side",
+            "outputPath": "template.mp4",
+            "codec": "h264",
+            "crf": 23,
+            "keepAspect": true,
+            "audioCopy": false,
+            "inputPathA": "old_a.mp4",
+            "inputPathB": "old_b.mp4",
+            "labelA": "Reference",
+            "labelB": "Candidate",
+            "trimStartFrame": 10,
+        }))
+        .unwrap();
+        let paired = pair_params(
+            &params,
+            Path::new("ref/clip_ref.mp4"),
+            Path::new("cand/clip.mp4"),
+            "out/clip_side-by-side.mp4".to_string(),
+        );
+        assert_eq!(paired.inputs.len(), 2);
+        assert_eq!(paired.inputs[0].path, "ref/clip_ref.mp4");
+        assert_eq!(paired.inputs[1].path, "cand/clip.mp4");
+        assert_eq!(paired.inputs[0].label.as_deref(), Some("Reference"));
+        assert_eq!(paired.inputs[1].label.as_deref(), Some("Candidate"));
+        assert_eq!(paired.inputs[1].trim_start_frame, Some(10));
+        assert!(paired.input_path_a.is_none() && paired.input_path_b.is_none());
+        assert_eq!(paired.output_path, "out/clip_side-by-side.mp4");
+        // Layout sizes are left to the export, which scales to the probed size.
+        assert_eq!(paired.stack_height, None);
+    }
+}
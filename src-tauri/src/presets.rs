@@ -0,0 +1,376 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::{AppHandle, Manager, State};
+
+use crate::error::{AppError, AppResult};
+use crate::queue::now;
+use crate::ExportParams;
+
+const PRESETS_FILE: &str = "presets.json";
+/// Version written to `presets.json` and exported preset files. Bump it and
+/// extend `migrate` when a change to `ExportParams` needs old values rewritten;
+/// added fields with defaults load without a bump.
+const PRESET_VERSION: u32 = 1;
+
+/// A named set of export settings. Input and output paths and trim frames are
+/// not stored, so a preset can be applied to any files.
+#[derive(Serialize, Deserialize, Clone)]
+pub(crate) struct Preset {
+    name: String,
+    created_at: u64,
+    updated_at: u64,
+    params: ExportParams,
+}
+
+#[derive(Serialize)]
+pub(crate) struct PresetSummary {
+    name: String,
+    created_at: u64,
+    updated_at: u64,
+    codec: String,
+    export_mode: String,
+}
+
+impl From<&Preset> for PresetSummary {
+    fn from(preset: &Preset) -> Self {
+        Self {
+            name: preset.name.clone(),
+            created_at: preset.created_at,
+            updated_at: preset.updated_at,
+            codec: preset.params.codec.clone(),
+            export_mode: preset.params.export_mode.clone(),
+        }
+    }
+}
+
+/// `presets.json` as written to disk.
+#[derive(Serialize)]
+struct PresetStoreFile<'a> {
+    version: u32,
+    presets: &'a [Preset],
+}
+
+/// A single preset shared between machines with `export_preset`/`import_preset`.
+#[derive(Serialize)]
+struct PresetExportFile<'a> {
+    version: u32,
+    #[serde(flatten)]
+    preset: &'a Preset,
+}
+
+/// Serializes access to `presets.json`; commands read and rewrite the whole file.
+#[derive(Default)]
+pub(crate) struct PresetStore {
+    lock: Mutex<()>,
+}
+
+impl PresetStore {
+    fn lock(&self) -> AppResult<MutexGuard<'_, ()>> {
+        self.lock.lock().map_err(|_| AppError::lock())
+    }
+}
+
+fn presets_file(app: &AppHandle) -> AppResult<PathBuf> {
+    app.path()
+        .app_config_dir()
+        .map(|dir| dir.join(PRESETS_FILE))
+        .map_err(|e| AppError::io(format!("Failed to locate the config directory: {}", e)))
+}
+
+/// Rewrites a preset written by an older version into the current shape.
+fn migrate(_version: u32, preset: Value) -> Value {
+    // Version 1 is the first format; later versions add their steps here.
+    preset
+}
+
+/// The file's format version; files without one predate versioning and are
+/// read as version 1.
+fn version_of(value: &Value) -> AppResult<u32> {
+    let version = value
+        .get("version")
+        .and_then(Value::as_u64)
+        .map_or(1, |version| version as u32);
+    if version > PRESET_VERSION {
+        return Err(AppError::invalid(format!(
+            "Preset version {version} is newer than this app supports ({PRESET_VERSION}); update the app to load it."
+        )));
+    }
+    Ok(version)
+}
+
+fn parse_preset(version: u32, value: Value) -> AppResult<Preset> {
+    serde_json::from_value(migrate(version, value))
+        .map_err(|e| AppError::invalid(format!("Invalid preset: {}", e)))
+}
+
+fn read_presets(file: &Path) -> AppResult<Vec<Preset>> {
+    let json = match fs::read_to_string(file) {
+        Ok(json) => json,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(AppError::io(format!("Failed to read presets: {}", e))),
+    };
+    let value: Value = serde_json::from_str(&json)
+        .map_err(|e| AppError::io(format!("Failed to parse presets: {}", e)))?;
+    let version = version_of(&value)?;
+    let presets = match value.get("presets") {
+        Some(Value::Array(presets)) => presets.clone(),
+        _ => Vec::new(),
+    };
+    presets
+        .into_iter()
+        .map(|preset| parse_preset(version, preset))
+        .collect()
+}
+
+fn write_presets(file: &Path, presets: &[Preset]) -> AppResult<()> {
+    if let Some(parent) = file.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| AppError::io(format!("Failed to create config directory: {}", e)))?;
+    }
+    let json = serde_json::to_string_pretty(&PresetStoreFile {
+        version: PRESET_VERSION,
+        presets,
+    })
+    .map_err(|e| AppError::io(format!("Failed to serialize presets: {}", e)))?;
+    let temp = file.with_extension("json.tmp");
+    let write_error = |e: std::io::Error| AppError::io(format!("Failed to write presets: {}", e));
+    fs::write(&temp, json).map_err(write_error)?;
+    fs::rename(&temp, file).map_err(write_error)
+}
+
+fn validate_name(name: &str) -> AppResult<String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(AppError::invalid("Preset name is required."));
+    }
+    Ok(name.to_string())
+}
+
+fn position_of(presets: &[Preset], name: &str) -> Option<usize> {
+    presets
+        .iter()
+        .position(|preset| preset.name.eq_ignore_ascii_case(name.trim()))
+}
+
+fn find<'a>(presets: &'a [Preset], name: &str) -> AppResult<&'a Preset> {
+    position_of(presets, name)
+        .map(|index| &presets[index])
+        .ok_or_else(|| AppError::not_found(format!("Unknown preset: {}", name.trim())))
+}
+
+/// Drops the parts of `params` tied to particular files: input and output
+/// paths, and trim frames, which only make sense for the clips they were
+/// picked on.
+fn strip_paths(mut params: ExportParams) -> ExportParams {
+    params.input_path_a = None;
+    params.input_path_b = None;
+    params.extra_input_paths.clear();
+    params.output_path = String::new();
+    params.trim_start_frame = None;
+    params.trim_end_frame = None;
+    for input in &mut params.inputs {
+        input.path = String::new();
+        input.trim_start_frame = None;
+        input.trim_end_frame = None;
+    }
+    params
+}
+
+#[tauri::command]
+pub(crate) fn list_presets(
+    app: AppHandle,
+    store: State<'_, PresetStore>,
+) -> AppResult<Vec<PresetSummary>> {
+    let _guard = store.lock()?;
+    let presets = read_presets(&presets_file(&app)?)?;
+    Ok(presets.iter().map(PresetSummary::from).collect())
+}
+
+/// Saves `params` under `name`, replacing a preset with the same name.
+#[tauri::command]
+pub(crate) fn save_preset(
+    app: AppHandle,
+    store: State<'_, PresetStore>,
+    name: String,
+    params: ExportParams,
+) -> AppResult<PresetSummary> {
+    let name = validate_name(&name)?;
+    let _guard = store.lock()?;
+    let file = presets_file(&app)?;
+    let mut presets = read_presets(&file)?;
+    let params = strip_paths(params);
+    let timestamp = now();
+    let index = match position_of(&presets, &name) {
+        Some(index) => {
+            let preset = &mut presets[index];
+            preset.name = name;
+            preset.params = params;
+            preset.updated_at = timestamp;
+            index
+        }
+        None => {
+            presets.push(Preset {
+                name,
+                created_at: timestamp,
+                updated_at: timestamp,
+                params,
+            });
+            presets.len() - 1
+        }
+    };
+    write_presets(&file, &presets)?;
+    Ok(PresetSummary::from(&presets[index]))
+}
+
+/// Returns the stored settings; input and output paths are left empty.
+#[tauri::command]
+pub(crate) fn load_preset(
+    app: AppHandle,
+    store: State<'_, PresetStore>,
+    name: String,
+) -> AppResult<ExportParams> {
+    let _guard = store.lock()?;
+    let presets = read_presets(&presets_file(&app)?)?;
+    Ok(find(&presets, &name)?.params.clone())
+}
+
+#[tauri::command]
+pub(crate) fn rename_preset(
+    app: AppHandle,
+    store: State<'_, PresetStore>,
+    name: String,
+    new_name: String,
+) -> AppResult<PresetSummary> {
+    let new_name = validate_name(&new_name)?;
+    let _guard = store.lock()?;
+    let file = presets_file(&app)?;
+    let mut presets = read_presets(&file)?;
+    let index = position_of(&presets, &name)
+        .ok_or_else(|| AppError::not_found(format!("Unknown preset: {}", name.trim())))?;
+    if position_of(&presets, &new_name).is_some_and(|other| other != index) {
+        return Err(AppError::invalid_state(format!(
+            "A preset named {new_name} already exists."
+        )));
+    }
+    presets[index].name = new_name;
+    presets[index].updated_at = now();
+    write_presets(&file, &presets)?;
+    Ok(PresetSummary::from(&presets[index]))
+}
+
+#[tauri::command]
+pub(crate) fn delete_preset(
+    app: AppHandle,
+    store: State<'_, PresetStore>,
+    name: String,
+) -> AppResult<()> {
+    let _guard = store.lock()?;
+    let file = presets_file(&app)?;
+    let mut presets = read_presets(&file)?;
+    let index = position_of(&presets, &name)
+        .ok_or_else(|| AppError::not_found(format!("Unknown preset: {}", name.trim())))?;
+    presets.remove(index);
+    write_presets(&file, &presets)
+}
+
+/// Writes one preset to `path` as a standalone, versioned JSON file.
+#[tauri::command]
+pub(crate) fn export_preset(
+    app: AppHandle,
+    store: State<'_, PresetStore>,
+    name: String,
+    path: String,
+) -> AppResult<()> {
+    let _guard = store.lock()?;
+    let presets = read_presets(&presets_file(&app)?)?;
+    let preset = find(&presets, &name)?;
+    let json = serde_json::to_string_pretty(&PresetExportFile {
+        version: PRESET_VERSION,
+        preset,
+    })
+    .map_err(|e| AppError::io(format!("Failed to serialize preset: {}", e)))?;
+    fs::write(&path, json).map_err(|e| AppError::io(format!("Failed to write preset: {}", e)))
+}
+
+/// Adds the preset stored in `path`. A name that is already taken gets a
+/// ` (1)`, ` (2)`, ... suffix instead of replacing the existing preset.
+#[tauri::command]
+pub(crate) fn import_preset(
+    app: AppHandle,
+    store: State<'_, PresetStore>,
+    path: String,
+) -> AppResult<PresetSummary> {
+    let json = fs::read_to_string(&path).map_err(|e| {
+        if e.kind() == std::io::ErrorKind::NotFound {
+            AppError::not_found(format!("Preset file not found: {path}"))
+        } else {
+            AppError::io(format!("Failed to read preset file: {}", e))
+        }
+    })?;
+    let mut value: Value = serde_json::from_str(&json)
+        .map_err(|e| AppError::invalid(format!("Invalid preset file: {}", e)))?;
+    let version = version_of(&value)?;
+    if let Value::Object(fields) = &mut value {
+        fields.remove("version");
+    }
+    let mut preset = parse_preset(version, value)?;
+    preset.name = validate_name(&preset.name)?;
+    preset.params = strip_paths(preset.params);
+
+    let _guard = store.lock()?;
+    let file = presets_file(&app)?;
+    let mut presets = read_presets(&file)?;
+    let base = preset.name.clone();
+    let mut suffix = 1;
+    while position_of(&presets, &preset.name).is_some() {
+        preset.name = format!("{base} ({suffix})");
+        suffix += 1;
+    }
+    presets.push(preset);
+    write_presets(&file, &presets)?;
+    Ok(PresetSummary::from(&presets[presets.len() - 1]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn strip_paths_keeps_only_portable_settings() {
+        let params: ExportParams = serde_json::from_value(json!({
+            "inputs": [
+                { "path": "/clips/a.mp4", "label": "A", "trimStartFrame": 10, "trimEndFrame": 20 },
+                { "path": "/clips/b.mp4", "resizeWidth": 1280 },
+            ],
+            "inputPathA": "/clips/a.mp4",
+            "extraInputPaths": ["/clips/c.mp4"],
+            "exportMode": "side-by-side",
+            "outputPath": "/exports/out.mp4",
+            "codec": "h265",
+            "crf": 20,
+            "keepAspect": true,
+            "audioCopy": false,
+            "trimStartFrame": 5,
+            "trimEndFrame": 50,
+        }))
+        .unwrap();
+        let stripped = strip_paths(params);
+        assert!(stripped.input_path_a.is_none());
+        assert!(stripped.extra_input_paths.is_empty());
+        assert!(stripped.output_path.is_empty());
+        assert_eq!(stripped.trim_start_frame, None);
+        assert_eq!(stripped.trim_end_frame, None);
+        for input in &stripped.inputs {
+            assert!(input.path.is_empty());
+            assert_eq!((input.trim_start_frame, input.trim_end_frame), (None, None));
+        }
+        assert_eq!(stripped.inputs[0].label.as_deref(), Some("A"));
+        assert_eq!(stripped.inputs[1].resize_width, Some(1280));
+        assert_eq!(stripped.codec, "h265");
+    }
+}
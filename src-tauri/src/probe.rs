@@ -0,0 +1,856 @@
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, State};
+
+use crate::cache::ProbeCache;
+use crate::error::{AppError, AppResult};
+use crate::timeline::FrameTimeline;
+use crate::{resolve_bundled_binary, tool_command};
+
+#[derive(Serialize, Deserialize, Clone)]
+pub(crate) struct ContainerInfo {
+    pub(crate) format_name: Option<String>,
+    pub(crate) duration_sec: Option<f64>,
+    pub(crate) bitrate: Option<u64>,
+}
+
+/// Summary of the first video stream, as shown in the comparison table.
+#[derive(Serialize, Deserialize, Clone)]
+pub(crate) struct VideoStreamInfo {
+    pub(crate) index: u32,
+    pub(crate) codec_name: Option<String>,
+    pub(crate) profile: Option<String>,
+    pub(crate) width: Option<u32>,
+    pub(crate) height: Option<u32>,
+    pub(crate) pix_fmt: Option<String>,
+    pub(crate) color_space: Option<String>,
+    pub(crate) color_range: Option<String>,
+    pub(crate) color_transfer: Option<String>,
+    pub(crate) color_primaries: Option<String>,
+    pub(crate) bit_rate: Option<u64>,
+    pub(crate) avg_frame_rate: Option<String>,
+    pub(crate) r_frame_rate: Option<String>,
+    pub(crate) fps: Option<f64>,
+    pub(crate) frame_count: Option<u64>,
+    /// Set for PQ, HLG and Dolby Vision streams.
+    pub(crate) hdr: Option<HdrInfo>,
+}
+
+/// SMPTE ST 2086 mastering display color volume. Chromaticities are CIE 1931
+/// xy coordinates, luminances in cd/m².
+#[derive(Serialize, Deserialize, Clone)]
+pub(crate) struct MasteringDisplay {
+    pub(crate) red_x: f64,
+    pub(crate) red_y: f64,
+    pub(crate) green_x: f64,
+    pub(crate) green_y: f64,
+    pub(crate) blue_x: f64,
+    pub(crate) blue_y: f64,
+    pub(crate) white_point_x: f64,
+    pub(crate) white_point_y: f64,
+    pub(crate) min_luminance: f64,
+    pub(crate) max_luminance: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub(crate) struct DolbyVisionInfo {
+    pub(crate) profile: Option<u32>,
+    pub(crate) level: Option<u32>,
+    /// 1 = HDR10, 2 = SDR, 4 = HLG compatible base layer; 0 = none.
+    pub(crate) bl_signal_compatibility_id: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub(crate) struct HdrInfo {
+    /// `hdr10`, `hdr10+`, `hlg` or `dolby_vision`.
+    pub(crate) format: String,
+    pub(crate) mastering_display: Option<MasteringDisplay>,
+    /// Content light level: MaxCLL and MaxFALL in cd/m².
+    pub(crate) max_cll: Option<u32>,
+    pub(crate) max_fall: Option<u32>,
+    pub(crate) hdr10_plus: bool,
+    pub(crate) dolby_vision: Option<DolbyVisionInfo>,
+}
+
+/// Summary of the first audio stream.
+#[derive(Serialize, Deserialize, Clone)]
+pub(crate) struct AudioStreamInfo {
+    pub(crate) index: u32,
+    pub(crate) codec_name: Option<String>,
+    pub(crate) channels: Option<u32>,
+    pub(crate) sample_rate: Option<u32>,
+    pub(crate) bit_rate: Option<u64>,
+    pub(crate) language: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub(crate) struct SubtitleStreamInfo {
+    pub(crate) index: u32,
+    pub(crate) codec_name: Option<String>,
+    pub(crate) language: Option<String>,
+    pub(crate) title: Option<String>,
+    pub(crate) default: bool,
+    pub(crate) forced: bool,
+    /// Image-based subtitles (PGS, VobSub, DVB) can only be burned in, not
+    /// converted to text formats.
+    pub(crate) bitmap: bool,
+}
+
+/// A file embedded in the container, typically a font for ASS subtitles.
+#[derive(Serialize, Deserialize, Clone)]
+pub(crate) struct AttachmentInfo {
+    pub(crate) index: u32,
+    pub(crate) filename: Option<String>,
+    pub(crate) mimetype: Option<String>,
+}
+
+/// One entry of ffprobe's stream list, whatever its type. Type-specific fields
+/// are `None` for other types.
+#[derive(Serialize, Deserialize, Clone)]
+pub(crate) struct StreamInfo {
+    pub(crate) index: u32,
+    /// `video`, `audio`, `subtitle`, `data` or `attachment`.
+    pub(crate) codec_type: String,
+    pub(crate) codec_name: Option<String>,
+    pub(crate) codec_long_name: Option<String>,
+    pub(crate) codec_tag_string: Option<String>,
+    pub(crate) profile: Option<String>,
+    pub(crate) language: Option<String>,
+    pub(crate) title: Option<String>,
+    /// Disposition flags that are set, e.g. `default`, `forced`, `attached_pic`.
+    pub(crate) disposition: Vec<String>,
+    pub(crate) bit_rate: Option<u64>,
+    pub(crate) duration_sec: Option<f64>,
+    pub(crate) width: Option<u32>,
+    pub(crate) height: Option<u32>,
+    pub(crate) pix_fmt: Option<String>,
+    pub(crate) fps: Option<f64>,
+    pub(crate) channels: Option<u32>,
+    pub(crate) channel_layout: Option<String>,
+    pub(crate) sample_rate: Option<u32>,
+    /// `timecode` tag of tmcd data streams.
+    pub(crate) timecode: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub(crate) struct VideoInfo {
+    pub(crate) file: String,
+    pub(crate) size_bytes: Option<u64>,
+    pub(crate) container: ContainerInfo,
+    pub(crate) video: Option<VideoStreamInfo>,
+    pub(crate) audio: Option<AudioStreamInfo>,
+    pub(crate) subtitles: Vec<SubtitleStreamInfo>,
+    pub(crate) attachments: Vec<AttachmentInfo>,
+    /// Every stream in file order, including data streams such as timecode tracks.
+    pub(crate) streams: Vec<StreamInfo>,
+    /// Exact frame timestamps once `deep_probe` has read the file.
+    #[serde(skip)]
+    pub(crate) timeline: Option<Arc<FrameTimeline>>,
+}
+
+fn text(value: &Option<String>) -> String {
+    value.clone().unwrap_or_else(|| "-".to_string())
+}
+
+fn number<T: ToString>(value: Option<T>) -> String {
+    value.map_or_else(|| "-".to_string(), |value| value.to_string())
+}
+
+impl VideoInfo {
+    /// The fields of the GUI comparison table as text, in the same order.
+    pub(crate) fn summary_rows(&self) -> Vec<(&'static str, String)> {
+        let video = self.video.as_ref();
+        let audio = self.audio.as_ref();
+        let resolution = video
+            .and_then(|video| video.width.zip(video.height))
+            .map_or_else(|| "-".to_string(), |(w, h)| format!("{w}x{h}"));
+        vec![
+            ("File", self.file.clone()),
+            ("Size (bytes)", number(self.size_bytes)),
+            ("Container", text(&self.container.format_name)),
+            (
+                "Duration (s)",
+                number(self.container.duration_sec.map(|d| format!("{d:.3}"))),
+            ),
+            ("Bitrate", number(self.container.bitrate)),
+            (
+                "Video codec",
+                text(&video.and_then(|v| v.codec_name.clone())),
+            ),
+            ("Profile", text(&video.and_then(|v| v.profile.clone()))),
+            ("Resolution", resolution),
+            (
+                "FPS",
+                number(video.and_then(|v| v.fps).map(|fps| format!("{fps:.3}"))),
+            ),
+            ("Frames", number(video.and_then(|v| v.frame_count))),
+            ("Pixel format", text(&video.and_then(|v| v.pix_fmt.clone()))),
+            (
+                "Color space",
+                text(&video.and_then(|v| v.color_space.clone())),
+            ),
+            (
+                "Color range",
+                text(&video.and_then(|v| v.color_range.clone())),
+            ),
+            (
+                "Transfer",
+                text(&video.and_then(|v| v.color_transfer.clone())),
+            ),
+            (
+                "Primaries",
+                text(&video.and_then(|v| v.color_primaries.clone())),
+            ),
+            (
+                "HDR",
+                text(&video.and_then(|v| v.hdr.as_ref().map(|hdr| hdr.format.clone()))),
+            ),
+            ("Video bitrate", number(video.and_then(|v| v.bit_rate))),
+            (
+                "Audio codec",
+                text(&audio.and_then(|a| a.codec_name.clone())),
+            ),
+            ("Channels", number(audio.and_then(|a| a.channels))),
+            ("Sample rate", number(audio.and_then(|a| a.sample_rate))),
+            ("Subtitles", self.subtitles.len().to_string()),
+        ]
+    }
+}
+
+fn parse_u64(value: &serde_json::Value) -> Option<u64> {
+    match value {
+        serde_json::Value::String(text) => text.parse::<u64>().ok(),
+        serde_json::Value::Number(num) => num.as_u64(),
+        _ => None,
+    }
+}
+
+fn parse_f64(value: &serde_json::Value) -> Option<f64> {
+    match value {
+        serde_json::Value::String(text) => text.parse::<f64>().ok(),
+        serde_json::Value::Number(num) => num.as_f64(),
+        _ => None,
+    }
+}
+
+fn parse_fraction(value: &str) -> Option<f64> {
+    if value == "0/0" {
+        return None;
+    }
+    let mut parts = value.split('/');
+    let num = parts.next()?.parse::<f64>().ok()?;
+    let den = parts.next()?.parse::<f64>().ok()?;
+    if den == 0.0 {
+        return None;
+    }
+    Some(num / den)
+}
+
+fn string_field(value: &serde_json::Value, key: &str) -> Option<String> {
+    value
+        .get(key)
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string())
+}
+
+fn u32_field(value: &serde_json::Value, key: &str) -> Option<u32> {
+    value.get(key).and_then(parse_u64).map(|v| v as u32)
+}
+
+/// Reads a stream tag. Matroska writes tags in upper case, MP4 in lower case.
+fn tag(stream: &serde_json::Value, key: &str) -> Option<String> {
+    let tags = stream.get("tags")?.as_object()?;
+    tags.iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(key))
+        .and_then(|(_, value)| value.as_str())
+        .map(|value| value.to_string())
+        .filter(|value| !value.is_empty())
+}
+
+fn disposition(stream: &serde_json::Value) -> Vec<String> {
+    let Some(flags) = stream.get("disposition").and_then(|v| v.as_object()) else {
+        return Vec::new();
+    };
+    flags
+        .iter()
+        .filter(|(_, value)| value.as_u64() == Some(1))
+        .map(|(name, _)| name.clone())
+        .collect()
+}
+
+fn codec_type(stream: &serde_json::Value) -> Option<&str> {
+    stream.get("codec_type").and_then(|v| v.as_str())
+}
+
+fn stream_index(stream: &serde_json::Value) -> u32 {
+    u32_field(stream, "index").unwrap_or_default()
+}
+
+fn side_data_of<'a>(entries: &'a [serde_json::Value], kind: &str) -> Option<&'a serde_json::Value> {
+    entries.iter().find(|entry| {
+        entry
+            .get("side_data_type")
+            .and_then(|v| v.as_str())
+            .is_some_and(|name| name.starts_with(kind))
+    })
+}
+
+fn fraction_field(value: &serde_json::Value, key: &str) -> Option<f64> {
+    match value.get(key)? {
+        serde_json::Value::String(text) => parse_fraction(text).or_else(|| text.parse().ok()),
+        other => other.as_f64(),
+    }
+}
+
+fn mastering_display(entry: &serde_json::Value) -> Option<MasteringDisplay> {
+    Some(MasteringDisplay {
+        red_x: fraction_field(entry, "red_x")?,
+        red_y: fraction_field(entry, "red_y")?,
+        green_x: fraction_field(entry, "green_x")?,
+        green_y: fraction_field(entry, "green_y")?,
+        blue_x: fraction_field(entry, "blue_x")?,
+        blue_y: fraction_field(entry, "blue_y")?,
+        white_point_x: fraction_field(entry, "white_point_x")?,
+        white_point_y: fraction_field(entry, "white_point_y")?,
+        min_luminance: fraction_field(entry, "min_luminance")?,
+        max_luminance: fraction_field(entry, "max_luminance")?,
+    })
+}
+
+/// Side data attached to the first decoded frame of `stream_index`. Mastering
+/// display and light level metadata usually live here rather than on the
+/// stream. Best effort: failures yield an empty list.
+fn first_frame_side_data(ffprobe: &Path, path: &str, stream_index: u32) -> Vec<serde_json::Value> {
+    let output = tool_command(ffprobe)
+        .args([
+            "-v",
+            "error",
+            "-select_streams",
+            &stream_index.to_string(),
+            "-read_intervals",
+            "%+#1",
+            "-show_frames",
+            "-show_entries",
+            "frame=side_data_list",
+            "-print_format",
+            "json",
+            path,
+        ])
+        .output();
+    let Some(output) = output.ok().filter(|output| output.status.success()) else {
+        return Vec::new();
+    };
+    serde_json::from_slice::<serde_json::Value>(&output.stdout)
+        .ok()
+        .and_then(|json| {
+            json.get("frames")?
+                .get(0)?
+                .get("side_data_list")?
+                .as_array()
+                .cloned()
+        })
+        .unwrap_or_default()
+}
+
+impl HdrInfo {
+    /// Fills in metadata from `side_data`, replacing what an earlier list set.
+    fn apply_side_data(&mut self, side_data: &[serde_json::Value]) {
+        if side_data_of(side_data, "HDR Dynamic Metadata SMPTE2094-40").is_some() {
+            self.hdr10_plus = true;
+            if self.dolby_vision.is_none() {
+                self.format = "hdr10+".to_string();
+            }
+        }
+        if let Some(display) =
+            side_data_of(side_data, "Mastering display").and_then(mastering_display)
+        {
+            self.mastering_display = Some(display);
+        }
+        if let Some(light_level) = side_data_of(side_data, "Content light level") {
+            self.max_cll = u32_field(light_level, "max_content").or(self.max_cll);
+            self.max_fall = u32_field(light_level, "max_average").or(self.max_fall);
+        }
+    }
+}
+
+/// HDR metadata from the stream entry alone. Newer ffprobe versions report
+/// static metadata there; `probe_file` adds the first frame's side data.
+fn hdr_info(stream: &serde_json::Value) -> Option<HdrInfo> {
+    let side_data = stream
+        .get("side_data_list")
+        .and_then(|v| v.as_array())
+        .cloned()
+        .unwrap_or_default();
+    let dovi = side_data_of(&side_data, "DOVI configuration record");
+    let transfer = string_field(stream, "color_transfer");
+    let is_pq = transfer.as_deref() == Some("smpte2084");
+    let is_hlg = transfer.as_deref() == Some("arib-std-b67");
+    if !is_pq && !is_hlg && dovi.is_none() {
+        return None;
+    }
+
+    let dolby_vision = dovi.map(|record| DolbyVisionInfo {
+        profile: u32_field(record, "dv_profile"),
+        level: u32_field(record, "dv_level"),
+        bl_signal_compatibility_id: u32_field(record, "dv_bl_signal_compatibility_id"),
+    });
+    let format = if dolby_vision.is_some() {
+        "dolby_vision"
+    } else if is_hlg {
+        "hlg"
+    } else {
+        "hdr10"
+    };
+    let mut hdr = HdrInfo {
+        format: format.to_string(),
+        mastering_display: None,
+        max_cll: None,
+        max_fall: None,
+        hdr10_plus: false,
+        dolby_vision,
+    };
+    hdr.apply_side_data(&side_data);
+    Some(hdr)
+}
+
+const BITMAP_SUBTITLE_CODECS: &[&str] =
+    &["hdmv_pgs_subtitle", "dvd_subtitle", "dvb_subtitle", "xsub"];
+
+fn stream_info(stream: &serde_json::Value) -> StreamInfo {
+    let fps = string_field(stream, "avg_frame_rate")
+        .as_deref()
+        .and_then(parse_fraction)
+        .or_else(|| {
+            string_field(stream, "r_frame_rate")
+                .as_deref()
+                .and_then(parse_fraction)
+        });
+    let is_video = codec_type(stream) == Some("video");
+    StreamInfo {
+        index: stream_index(stream),
+        codec_type: codec_type(stream).unwrap_or("unknown").to_string(),
+        codec_name: string_field(stream, "codec_name"),
+        codec_long_name: string_field(stream, "codec_long_name"),
+        codec_tag_string: string_field(stream, "codec_tag_string"),
+        profile: string_field(stream, "profile"),
+        language: tag(stream, "language"),
+        title: tag(stream, "title"),
+        disposition: disposition(stream),
+        bit_rate: stream.get("bit_rate").and_then(parse_u64),
+        duration_sec: stream.get("duration").and_then(parse_f64),
+        width: u32_field(stream, "width"),
+        height: u32_field(stream, "height"),
+        pix_fmt: string_field(stream, "pix_fmt"),
+        fps: fps.filter(|_| is_video),
+        channels: u32_field(stream, "channels"),
+        channel_layout: string_field(stream, "channel_layout"),
+        sample_rate: u32_field(stream, "sample_rate"),
+        timecode: tag(stream, "timecode"),
+    }
+}
+
+fn subtitle_info(stream: &serde_json::Value) -> SubtitleStreamInfo {
+    let flags = disposition(stream);
+    let codec_name = string_field(stream, "codec_name");
+    SubtitleStreamInfo {
+        index: stream_index(stream),
+        bitmap: codec_name
+            .as_deref()
+            .is_some_and(|codec| BITMAP_SUBTITLE_CODECS.contains(&codec)),
+        codec_name,
+        language: tag(stream, "language"),
+        title: tag(stream, "title"),
+        default: flags.iter().any(|flag| flag == "default"),
+        forced: flags.iter().any(|flag| flag == "forced"),
+    }
+}
+
+fn attachment_info(stream: &serde_json::Value) -> AttachmentInfo {
+    AttachmentInfo {
+        index: stream_index(stream),
+        filename: tag(stream, "filename"),
+        mimetype: tag(stream, "mimetype"),
+    }
+}
+
+/// Frame counts are exact for files `deep_probe` has read, estimates otherwise.
+/// Unchanged files are answered from the probe cache.
+#[tauri::command]
+pub(crate) fn probe_video(
+    app: AppHandle,
+    cache: State<'_, ProbeCache>,
+    path: String,
+) -> AppResult<VideoInfo> {
+    let ffprobe = resolve_bundled_binary(&app, "ffprobe")?;
+    cache.probe(&ffprobe, &path)
+}
+
+/// Probes several inputs at once, e.g. every tile of a grid, in the given order.
+#[tauri::command]
+pub(crate) fn probe_videos(
+    app: AppHandle,
+    cache: State<'_, ProbeCache>,
+    paths: Vec<String>,
+) -> AppResult<Vec<VideoInfo>> {
+    let ffprobe = resolve_bundled_binary(&app, "ffprobe")?;
+    paths
+        .iter()
+        .map(|path| cache.probe(&ffprobe, path))
+        .collect()
+}
+
+pub(crate) fn probe_file(ffprobe: &Path, path: &str) -> AppResult<VideoInfo> {
+    let output = tool_command(ffprobe)
+        .args([
+            "-hide_banner",
+            "-print_format",
+            "json",
+            "-show_format",
+            "-show_streams",
+            path,
+        ])
+        .output()
+        .map_err(|e| AppError::spawn(format!("Failed to run ffprobe: {}", e)))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+        return Err(AppError::probe(format!("ffprobe error:\n{}", stderr)));
+    }
+
+    let mut info = parse_probe(&String::from_utf8_lossy(&output.stdout))?;
+    info.file = path.to_string();
+    if info.size_bytes.is_none() {
+        info.size_bytes = fs::metadata(path).map(|m| m.len()).ok();
+    }
+    // Frame side data takes precedence over the stream's.
+    if let Some(video) = info.video.as_mut() {
+        if let Some(hdr) = video.hdr.as_mut() {
+            hdr.apply_side_data(&first_frame_side_data(ffprobe, path, video.index));
+        }
+    }
+    Ok(info)
+}
+
+/// Builds a `VideoInfo` from ffprobe's `-show_format -show_streams` JSON. The
+/// `file` is left empty, the size comes from the format section only and HDR
+/// metadata from the stream entries only; `probe_file` fills in the rest.
+pub(crate) fn parse_probe(json: &str) -> AppResult<VideoInfo> {
+    let json: serde_json::Value = serde_json::from_str(json)
+        .map_err(|e| AppError::probe(format!("Failed to parse ffprobe output: {}", e)))?;
+    let format = json.get("format").cloned().unwrap_or_default();
+    let streams = json
+        .get("streams")
+        .and_then(|v| v.as_array())
+        .cloned()
+        .unwrap_or_default();
+
+    // Cover art is stored as a video stream; skip it when picking the main one.
+    let video_stream = streams.iter().find(|s| {
+        codec_type(s) == Some("video") && !disposition(s).iter().any(|flag| flag == "attached_pic")
+    });
+    let audio_stream = streams.iter().find(|s| codec_type(s) == Some("audio"));
+
+    let avg_frame_rate = video_stream.and_then(|s| string_field(s, "avg_frame_rate"));
+    let r_frame_rate = video_stream.and_then(|s| string_field(s, "r_frame_rate"));
+
+    let fps = avg_frame_rate
+        .as_deref()
+        .and_then(parse_fraction)
+        .or_else(|| r_frame_rate.as_deref().and_then(parse_fraction));
+
+    let duration_sec = format.get("duration").and_then(parse_f64);
+
+    let frame_count = video_stream
+        .and_then(|s| s.get("nb_frames"))
+        .and_then(parse_u64)
+        .or_else(|| {
+            if let (Some(duration), Some(fps_value)) = (duration_sec, fps) {
+                Some((duration * fps_value).round() as u64)
+            } else {
+                None
+            }
+        });
+
+    let size_bytes = format.get("size").and_then(parse_u64);
+
+    let container = ContainerInfo {
+        format_name: string_field(&format, "format_name"),
+        duration_sec,
+        bitrate: format.get("bit_rate").and_then(parse_u64),
+    };
+
+    let video = video_stream.map(|stream| VideoStreamInfo {
+        index: stream_index(stream),
+        codec_name: string_field(stream, "codec_name"),
+        profile: string_field(stream, "profile"),
+        width: u32_field(stream, "width"),
+        height: u32_field(stream, "height"),
+        pix_fmt: string_field(stream, "pix_fmt"),
+        color_space: string_field(stream, "color_space"),
+        color_range: string_field(stream, "color_range"),
+        color_transfer: string_field(stream, "color_transfer"),
+        color_primaries: string_field(stream, "color_primaries"),
+        bit_rate: stream.get("bit_rate").and_then(parse_u64),
+        avg_frame_rate,
+        r_frame_rate,
+        fps,
+        frame_count,
+        hdr: hdr_info(stream),
+    });
+
+    let audio = audio_stream.map(|stream| AudioStreamInfo {
+        index: stream_index(stream),
+        codec_name: string_field(stream, "codec_name"),
+        channels: u32_field(stream, "channels"),
+        sample_rate: u32_field(stream, "sample_rate"),
+        bit_rate: stream.get("bit_rate").and_then(parse_u64),
+        language: tag(stream, "language"),
+    });
+
+    let subtitles = streams
+        .iter()
+        .filter(|s| codec_type(s) == Some("subtitle"))
+        .map(subtitle_info)
+        .collect();
+    let attachments = streams
+        .iter()
+        .filter(|s| codec_type(s) == Some("attachment"))
+        .map(attachment_info)
+        .collect();
+
+    Ok(VideoInfo {
+        file: String::new(),
+        size_bytes,
+        container,
+        video,
+        audio,
+        subtitles,
+        attachments,
+        streams: streams.iter().map(stream_info).collect(),
+        timeline: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Trimmed `ffprobe -show_format -show_streams` output of a Matroska file
+    /// with cover art, HDR10 video, a tagged audio track, subtitles, a font and
+    /// a timecode track.
+    const FIXTURE: &str = r#"{
+        "streams": [
+            {
+                "index": 0,
+                "codec_name": "mjpeg",
+                "codec_type": "video",
+                "width": 600,
+                "height": 600,
+                "disposition": { "default": 0, "attached_pic": 1 }
+            },
+            {
+                "index": 1,
+                "codec_name": "hevc",
+                "profile": "Main 10",
+                "codec_type": "video",
+                "width": 3840,
+                "height": 2160,
+                "pix_fmt": "yuv420p10le",
+                "color_range": "tv",
+                "color_space": "bt2020nc",
+                "color_transfer": "smpte2084",
+                "color_primaries": "bt2020",
+                "r_frame_rate": "24000/1001",
+                "avg_frame_rate": "0/0",
+                "disposition": { "default": 1, "forced": 0 },
+                "tags": { "LANGUAGE": "und" },
+                "side_data_list": [
+                    {
+                        "side_data_type": "Mastering display metadata",
+                        "red_x": "34000/50000",
+                        "red_y": "16000/50000",
+                        "green_x": "13250/50000",
+                        "green_y": "34500/50000",
+                        "blue_x": "7500/50000",
+                        "blue_y": "3000/50000",
+                        "white_point_x": "15635/50000",
+                        "white_point_y": "16450/50000",
+                        "min_luminance": "50/10000",
+                        "max_luminance": "10000000/10000"
+                    },
+                    {
+                        "side_data_type": "Content light level metadata",
+                        "max_content": 1000,
+                        "max_average": 400
+                    }
+                ]
+            },
+            {
+                "index": 2,
+                "codec_name": "eac3",
+                "codec_type": "audio",
+                "sample_rate": "48000",
+                "channels": 6,
+                "channel_layout": "5.1(side)",
+                "bit_rate": "640000",
+                "disposition": { "default": 1 },
+                "tags": { "LANGUAGE": "eng", "title": "Surround" }
+            },
+            {
+                "index": 3,
+                "codec_name": "hdmv_pgs_subtitle",
+                "codec_type": "subtitle",
+                "disposition": { "default": 0, "forced": 1 },
+                "tags": { "language": "ger", "title": "" }
+            },
+            {
+                "index": 4,
+                "codec_name": "ttf",
+                "codec_type": "attachment",
+                "disposition": {},
+                "tags": { "filename": "font.ttf", "mimetype": "font/ttf" }
+            },
+            {
+                "index": 5,
+                "codec_type": "data",
+                "codec_tag_string": "tmcd",
+                "tags": { "timecode": "01:00:00:00" }
+            }
+        ],
+        "format": {
+            "format_name": "matroska,webm",
+            "duration": "10.010000",
+            "size": "12345678",
+            "bit_rate": "9866000"
+        }
+    }"#;
+
+    #[test]
+    fn parse_probe_reads_format_and_main_streams() {
+        let info = parse_probe(FIXTURE).unwrap();
+        assert_eq!(info.file, "");
+        assert_eq!(info.size_bytes, Some(12_345_678));
+        assert_eq!(info.container.format_name.as_deref(), Some("matroska,webm"));
+        assert_eq!(info.container.duration_sec, Some(10.01));
+        assert_eq!(info.container.bitrate, Some(9_866_000));
+
+        // The cover art is skipped when picking the video stream.
+        let video = info.video.unwrap();
+        assert_eq!(video.index, 1);
+        assert_eq!(video.codec_name.as_deref(), Some("hevc"));
+        assert_eq!((video.width, video.height), (Some(3840), Some(2160)));
+        // `0/0` falls back to the real frame rate.
+        assert!((video.fps.unwrap() - 23.976).abs() < 0.001);
+        assert_eq!(video.frame_count, Some(240));
+
+        let audio = info.audio.unwrap();
+        assert_eq!(audio.index, 2);
+        assert_eq!(audio.channels, Some(6));
+        assert_eq!(audio.sample_rate, Some(48_000));
+        assert_eq!(audio.bit_rate, Some(640_000));
+        assert_eq!(audio.language.as_deref(), Some("eng"));
+    }
+
+    #[test]
+    fn parse_probe_reads_hdr_side_data_from_the_stream() {
+        let hdr = parse_probe(FIXTURE).unwrap().video.unwrap().hdr.unwrap();
+        assert_eq!(hdr.format, "hdr10");
+        assert!(!hdr.hdr10_plus);
+        assert_eq!((hdr.max_cll, hdr.max_fall), (Some(1000), Some(400)));
+        let display = hdr.mastering_display.unwrap();
+        assert_eq!(display.red_x, 0.68);
+        assert_eq!(display.min_luminance, 0.005);
+        assert_eq!(display.max_luminance, 1000.0);
+    }
+
+    #[test]
+    fn frame_side_data_overrides_the_stream() {
+        let mut hdr = parse_probe(FIXTURE).unwrap().video.unwrap().hdr.unwrap();
+        let frame: Vec<serde_json::Value> = serde_json::from_str(
+            r#"[
+                { "side_data_type": "HDR Dynamic Metadata SMPTE2094-40 (HDR10+)" },
+                { "side_data_type": "Content light level metadata", "max_content": 4000 }
+            ]"#,
+        )
+        .unwrap();
+        hdr.apply_side_data(&frame);
+        assert_eq!(hdr.format, "hdr10+");
+        assert!(hdr.hdr10_plus);
+        assert_eq!((hdr.max_cll, hdr.max_fall), (Some(4000), Some(400)));
+        assert!(hdr.mastering_display.is_some());
+    }
+
+    #[test]
+    fn dolby_vision_wins_over_other_hdr_formats() {
+        let json = r#"{
+            "streams": [{
+                "index": 0,
+                "codec_type": "video",
+                "color_transfer": "arib-std-b67",
+                "side_data_list": [{
+                    "side_data_type": "DOVI configuration record",
+                    "dv_profile": 8,
+                    "dv_level": 6,
+                    "dv_bl_signal_compatibility_id": 4
+                }]
+            }],
+            "format": {}
+        }"#;
+        let mut hdr = parse_probe(json).unwrap().video.unwrap().hdr.unwrap();
+        assert_eq!(hdr.format, "dolby_vision");
+        let dolby_vision = hdr.dolby_vision.as_ref().unwrap();
+        assert_eq!(dolby_vision.profile, Some(8));
+        assert_eq!(dolby_vision.bl_signal_compatibility_id, Some(4));
+        let frame: Vec<serde_json::Value> =
+            serde_json::from_str(r#"[{ "side_data_type": "HDR Dynamic Metadata SMPTE2094-40" }]"#)
+                .unwrap();
+        hdr.apply_side_data(&frame);
+        assert_eq!(hdr.format, "dolby_vision");
+        assert!(hdr.hdr10_plus);
+    }
+
+    #[test]
+    fn parse_probe_lists_every_stream_with_tags_and_disposition() {
+        let info = parse_probe(FIXTURE).unwrap();
+        let streams = &info.streams;
+        assert_eq!(streams.len(), 6);
+        assert_eq!(streams[0].disposition, ["attached_pic"]);
+        assert_eq!(streams[1].disposition, ["default"]);
+        assert_eq!(streams[1].language.as_deref(), Some("und"));
+        assert!(streams[1].fps.is_some());
+        assert_eq!(streams[2].title.as_deref(), Some("Surround"));
+        assert_eq!(streams[2].channel_layout.as_deref(), Some("5.1(side)"));
+        assert_eq!(streams[2].fps, None);
+        // Empty tags count as missing.
+        assert_eq!(streams[3].title, None);
+        assert!(streams[4].disposition.is_empty());
+        assert_eq!(streams[5].codec_type, "data");
+        assert_eq!(streams[5].codec_tag_string.as_deref(), Some("tmcd"));
+        assert_eq!(streams[5].timecode.as_deref(), Some("01:00:00:00"));
+
+        let subtitle = &info.subtitles[0];
+        assert_eq!(info.subtitles.len(), 1);
+        assert_eq!(subtitle.language.as_deref(), Some("ger"));
+        assert!(subtitle.bitmap && subtitle.forced && !subtitle.default);
+
+        let attachment = &info.attachments[0];
+        assert_eq!(info.attachments.len(), 1);
+        assert_eq!(attachment.filename.as_deref(), Some("font.ttf"));
+        assert_eq!(attachment.mimetype.as_deref(), Some("font/ttf"));
+    }
+
+    #[test]
+    fn parse_probe_handles_files_without_streams() {
+        let info = parse_probe(r#"{ "format": { "format_name": "tty" } }"#).unwrap();
+        assert!(info.video.is_none() && info.audio.is_none());
+        assert!(info.streams.is_empty());
+        assert_eq!(info.size_bytes, None);
+        assert!(matches!(
+            parse_probe("ffprobe: not json"),
+            Err(AppError::Probe { .. })
+        ));
+    }
+}
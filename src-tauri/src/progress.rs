@@ -0,0 +1,406 @@
+use std::path::PathBuf;
+use std::time::Instant;
+
+use serde::Serialize;
+
+use crate::error::ExportOutcome;
+
+/// What a tracked ffmpeg run is expected to produce, used to turn the raw
+/// `-progress` counters into a percentage and ETA.
+#[derive(Clone, Default)]
+pub(crate) struct ProgressTarget {
+    pub(crate) total_duration_us: Option<u64>,
+    pub(crate) total_frames: Option<u64>,
+    /// Checked for its size once ffmpeg exits successfully.
+    pub(crate) output_path: Option<PathBuf>,
+    /// Number of ffmpeg runs over the same input, e.g. 2 for a two-pass encode.
+    /// Zero counts as one.
+    pub(crate) passes: u32,
+}
+
+#[derive(Serialize, Clone, Default)]
+pub(crate) struct ExportProgress {
+    pub(crate) export_id: String,
+    /// `continue` while running, then a final `end`, `cancelled` or `error`.
+    pub(crate) progress: String,
+    /// Output timestamp in microseconds (ffmpeg's `out_time_ms` is misnamed).
+    pub(crate) out_time_ms: Option<u64>,
+    pub(crate) frame: Option<u64>,
+    pub(crate) fps: Option<f64>,
+    pub(crate) bitrate_kbps: Option<f64>,
+    pub(crate) total_size: Option<u64>,
+    /// Encoding speed relative to realtime, e.g. `2.5` for `2.5x`.
+    pub(crate) speed: Option<f64>,
+    pub(crate) dup_frames: Option<u64>,
+    pub(crate) drop_frames: Option<u64>,
+    pub(crate) percent: Option<f64>,
+    pub(crate) eta_sec: Option<f64>,
+    /// 1-based pass number, only set for multi-pass runs.
+    pub(crate) pass: Option<u32>,
+    pub(crate) elapsed_sec: f64,
+    /// Size of the finished output file, only set on the final `end` event.
+    pub(crate) output_size_bytes: Option<u64>,
+    pub(crate) message: Option<String>,
+    /// Only set on the final event.
+    pub(crate) outcome: Option<ExportOutcome>,
+}
+
+/// Accumulates `key=value` lines from `-progress pipe:1`; ffmpeg ends every
+/// block with a `progress=` line, at which point a snapshot is emitted.
+pub(crate) struct ProgressTracker {
+    target: ProgressTarget,
+    started: Instant,
+    current: ExportProgress,
+    /// 0-based index of the running pass.
+    pass: u32,
+}
+
+fn parse_number<T: std::str::FromStr>(value: &str) -> Option<T> {
+    value.trim().parse::<T>().ok()
+}
+
+impl ProgressTracker {
+    pub(crate) fn new(export_id: String, target: ProgressTarget) -> Self {
+        Self {
+            current: ExportProgress {
+                export_id,
+                ..ExportProgress::default()
+            },
+            target,
+            started: Instant::now(),
+            pass: 0,
+        }
+    }
+
+    fn passes(&self) -> u32 {
+        self.target.passes.max(1)
+    }
+
+    /// Starts counting the next pass from zero.
+    pub(crate) fn next_pass(&mut self) {
+        self.pass = (self.pass + 1).min(self.passes() - 1);
+        self.current = ExportProgress {
+            export_id: std::mem::take(&mut self.current.export_id),
+            ..ExportProgress::default()
+        };
+    }
+
+    pub(crate) fn output_path(&self) -> Option<&PathBuf> {
+        self.target.output_path.as_ref()
+    }
+
+    /// Feeds one line of progress output and returns a snapshot at the end of a block.
+    pub(crate) fn push_line(&mut self, line: &str) -> Option<ExportProgress> {
+        let (key, value) = line.split_once('=')?;
+        let value = value.trim();
+        match key.trim() {
+            "frame" => self.current.frame = parse_number(value),
+            "fps" => self.current.fps = parse_number(value),
+            "bitrate" => {
+                self.current.bitrate_kbps = value.strip_suffix("kbits/s").and_then(parse_number);
+            }
+            "total_size" => self.current.total_size = parse_number(value),
+            "out_time_us" | "out_time_ms" => {
+                if let Some(us) = parse_number(value) {
+                    self.current.out_time_ms = Some(us);
+                }
+            }
+            "dup_frames" => self.current.dup_frames = parse_number(value),
+            "drop_frames" => self.current.drop_frames = parse_number(value),
+            "speed" => {
+                self.current.speed = value.strip_suffix('x').and_then(parse_number);
+            }
+            "progress" => {
+                // The final `end` is reported once the process has exited successfully.
+                if value == "end" {
+                    return None;
+                }
+                return Some(self.snapshot(value));
+            }
+            _ => {}
+        }
+        None
+    }
+
+    fn fraction(&self) -> Option<f64> {
+        let by_time = match (self.target.total_duration_us, self.current.out_time_ms) {
+            (Some(total), Some(done)) if total > 0 => Some(done as f64 / total as f64),
+            _ => None,
+        };
+        let by_frames = match (self.target.total_frames, self.current.frame) {
+            (Some(total), Some(done)) if total > 0 => Some(done as f64 / total as f64),
+            _ => None,
+        };
+        let passes = f64::from(self.passes());
+        by_time
+            .or(by_frames)
+            .map(|fraction| (f64::from(self.pass) + fraction.clamp(0.0, 1.0)) / passes)
+    }
+
+    fn snapshot(&mut self, state: &str) -> ExportProgress {
+        let elapsed_sec = self.elapsed_sec();
+        let fraction = self.fraction();
+
+        let eta_from_speed = match (
+            self.target.total_duration_us,
+            self.current.out_time_ms,
+            self.current.speed,
+        ) {
+            (Some(total), Some(done), Some(speed)) if speed > 0.0 => {
+                // Later passes are assumed to run at the current speed.
+                let later_passes = u64::from(self.passes() - 1 - self.pass);
+                let remaining = total.saturating_sub(done) + total * later_passes;
+                Some(remaining as f64 / 1_000_000.0 / speed)
+            }
+            _ => None,
+        };
+        let eta_from_elapsed = fraction
+            .filter(|fraction| *fraction > 0.0)
+            .map(|fraction| elapsed_sec * (1.0 - fraction) / fraction);
+
+        self.current.progress = state.to_string();
+        self.current.elapsed_sec = elapsed_sec;
+        self.current.percent = fraction.map(|fraction| fraction * 100.0);
+        self.current.eta_sec = eta_from_speed.or(eta_from_elapsed);
+        self.current.pass = (self.passes() > 1).then_some(self.pass + 1);
+        self.current.clone()
+    }
+
+    pub(crate) fn elapsed_sec(&self) -> f64 {
+        self.started.elapsed().as_secs_f64()
+    }
+
+    /// Builds the final event for a run: `end`, `cancelled` or `error`.
+    pub(crate) fn finish(&mut self, outcome: ExportOutcome) -> ExportProgress {
+        let state = match outcome {
+            ExportOutcome::Succeeded { .. } => "end",
+            ExportOutcome::Cancelled => "cancelled",
+            ExportOutcome::Failed { .. } | ExportOutcome::SpawnFailed { .. } => "error",
+        };
+        let mut payload = self.snapshot(state);
+        if let ExportOutcome::Succeeded {
+            output_size_bytes, ..
+        } = outcome
+        {
+            payload.percent = Some(100.0);
+            payload.eta_sec = Some(0.0);
+            payload.output_size_bytes = output_size_bytes;
+        } else {
+            payload.eta_sec = None;
+        }
+        payload.message = outcome.summary();
+        payload.outcome = Some(outcome);
+        payload
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND_US: u64 = 1_000_000;
+
+    fn tracker(target: ProgressTarget) -> ProgressTracker {
+        ProgressTracker::new("export".to_string(), target)
+    }
+
+    fn by_time(seconds: u64, passes: u32) -> ProgressTarget {
+        ProgressTarget {
+            total_duration_us: Some(seconds * SECOND_US),
+            passes,
+            ..ProgressTarget::default()
+        }
+    }
+
+    fn push_block(tracker: &mut ProgressTracker, block: &str) -> Option<ExportProgress> {
+        let mut snapshot = None;
+        for line in block.lines() {
+            snapshot = tracker.push_line(line).or(snapshot);
+        }
+        snapshot
+    }
+
+    #[test]
+    fn a_block_is_reported_on_its_progress_line() {
+        let mut tracker = tracker(ProgressTarget::default());
+        assert!(tracker.push_line("frame=48").is_none());
+        let progress = push_block(
+            &mut tracker,
+            "fps=23.5\nbitrate= 812.4kbits/s\ntotal_size=1048576\nout_time_us=2000000\n\
+             out_time=00:00:02.000000\ndup_frames=1\ndrop_frames=0\nspeed=1.5x\nprogress=continue",
+        )
+        .unwrap();
+        assert_eq!(progress.export_id, "export");
+        assert_eq!(progress.progress, "continue");
+        assert_eq!(progress.frame, Some(48));
+        assert_eq!(progress.fps, Some(23.5));
+        assert_eq!(progress.bitrate_kbps, Some(812.4));
+        assert_eq!(progress.total_size, Some(1_048_576));
+        assert_eq!(progress.out_time_ms, Some(2 * SECOND_US));
+        assert_eq!(progress.dup_frames, Some(1));
+        assert_eq!(progress.drop_frames, Some(0));
+        assert_eq!(progress.speed, Some(1.5));
+        assert_eq!(progress.pass, None);
+    }
+
+    #[test]
+    fn unavailable_values_are_cleared_except_the_output_time() {
+        let mut tracker = tracker(ProgressTarget::default());
+        push_block(
+            &mut tracker,
+            "frame=10\nbitrate=100.0kbits/s\nout_time_us=500000\nspeed=2x\nprogress=continue",
+        );
+        let progress = push_block(
+            &mut tracker,
+            "frame=N/A\nbitrate=N/A\nout_time_us=N/A\nout_time=N/A\nspeed=N/A\nprogress=continue",
+        )
+        .unwrap();
+        assert_eq!(progress.frame, None);
+        assert_eq!(progress.bitrate_kbps, None);
+        assert_eq!(progress.speed, None);
+        assert_eq!(progress.out_time_ms, Some(500_000));
+    }
+
+    #[test]
+    fn output_time_is_read_in_microseconds_only() {
+        let mut tracker = tracker(ProgressTarget::default());
+        let progress = push_block(
+            &mut tracker,
+            "out_time_ms=1500000\nout_time=00:00:09.000000\nprogress=continue",
+        )
+        .unwrap();
+        assert_eq!(progress.out_time_ms, Some(1_500_000));
+        let progress = push_block(&mut tracker, "out_time_us=2500000\nprogress=continue").unwrap();
+        assert_eq!(progress.out_time_ms, Some(2_500_000));
+    }
+
+    #[test]
+    fn values_without_their_unit_suffix_are_ignored() {
+        let mut tracker = tracker(ProgressTarget::default());
+        let progress =
+            push_block(&mut tracker, "bitrate=812.4\nspeed=1.5\nprogress=continue").unwrap();
+        assert_eq!(progress.bitrate_kbps, None);
+        assert_eq!(progress.speed, None);
+    }
+
+    #[test]
+    fn the_end_line_is_left_to_finish() {
+        let mut tracker = tracker(ProgressTarget::default());
+        assert!(push_block(&mut tracker, "frame=10\nprogress=end").is_none());
+        assert!(tracker.push_line("not a key value line").is_none());
+    }
+
+    #[test]
+    fn percent_prefers_time_over_frames() {
+        let mut tracker = tracker(ProgressTarget {
+            total_frames: Some(100),
+            ..by_time(10, 1)
+        });
+        let progress = push_block(&mut tracker, "frame=80\nprogress=continue").unwrap();
+        assert_eq!(progress.percent, Some(80.0));
+        let progress = push_block(&mut tracker, "out_time_us=2500000\nprogress=continue").unwrap();
+        assert_eq!(progress.percent, Some(25.0));
+        let progress = push_block(&mut tracker, "out_time_us=12000000\nprogress=continue").unwrap();
+        assert_eq!(progress.percent, Some(100.0));
+    }
+
+    #[test]
+    fn two_pass_runs_weight_each_pass_equally() {
+        let mut tracker = tracker(by_time(10, 2));
+        let progress = push_block(&mut tracker, "out_time_us=5000000\nprogress=continue").unwrap();
+        assert_eq!(progress.percent, Some(25.0));
+        assert_eq!(progress.pass, Some(1));
+
+        tracker.next_pass();
+        let progress = push_block(&mut tracker, "frame=1\nprogress=continue").unwrap();
+        assert_eq!(progress.out_time_ms, None);
+        assert_eq!(progress.percent, None);
+        let progress = push_block(&mut tracker, "out_time_us=5000000\nprogress=continue").unwrap();
+        assert_eq!(progress.percent, Some(75.0));
+        assert_eq!(progress.pass, Some(2));
+
+        tracker.next_pass();
+        let progress = push_block(&mut tracker, "out_time_us=10000000\nprogress=continue").unwrap();
+        assert_eq!(progress.percent, Some(100.0));
+        assert_eq!(progress.pass, Some(2));
+    }
+
+    #[test]
+    fn eta_uses_the_encoding_speed_when_known() {
+        let mut tracker = tracker(by_time(10, 1));
+        let progress = push_block(
+            &mut tracker,
+            "out_time_us=4000000\nspeed=2x\nprogress=continue",
+        )
+        .unwrap();
+        assert_eq!(progress.eta_sec, Some(3.0));
+
+        let mut tracker = self::tracker(by_time(10, 2));
+        let progress = push_block(
+            &mut tracker,
+            "out_time_us=4000000\nspeed=2x\nprogress=continue",
+        )
+        .unwrap();
+        assert_eq!(progress.eta_sec, Some(8.0));
+    }
+
+    #[test]
+    fn eta_falls_back_to_elapsed_time() {
+        let mut tracker = tracker(ProgressTarget {
+            total_frames: Some(100),
+            ..ProgressTarget::default()
+        });
+        let progress = push_block(&mut tracker, "frame=50\nspeed=2x\nprogress=continue").unwrap();
+        let eta = progress.eta_sec.unwrap();
+        assert!(eta >= 0.0 && eta <= progress.elapsed_sec * 1.01 + 0.01);
+
+        let progress = push_block(&mut tracker, "frame=0\nprogress=continue").unwrap();
+        assert_eq!(progress.percent, Some(0.0));
+        assert_eq!(progress.eta_sec, None);
+    }
+
+    #[test]
+    fn eta_is_unknown_without_a_target() {
+        let mut tracker = tracker(ProgressTarget::default());
+        let progress = push_block(
+            &mut tracker,
+            "frame=50\nout_time_us=1000\nspeed=2x\nprogress=continue",
+        )
+        .unwrap();
+        assert_eq!(progress.percent, None);
+        assert_eq!(progress.eta_sec, None);
+    }
+
+    #[test]
+    fn finishing_reports_the_outcome() {
+        let mut tracker = tracker(by_time(10, 1));
+        push_block(
+            &mut tracker,
+            "out_time_us=9000000\nspeed=1x\nprogress=continue",
+        );
+        let end = tracker.finish(ExportOutcome::Succeeded {
+            output_size_bytes: Some(4096),
+            elapsed_sec: 1.0,
+        });
+        assert_eq!(end.progress, "end");
+        assert_eq!(end.percent, Some(100.0));
+        assert_eq!(end.eta_sec, Some(0.0));
+        assert_eq!(end.output_size_bytes, Some(4096));
+        assert_eq!(end.message, None);
+        assert!(end.outcome.is_some_and(|outcome| outcome.succeeded()));
+
+        let cancelled = tracker.finish(ExportOutcome::Cancelled);
+        assert_eq!(cancelled.progress, "cancelled");
+        assert_eq!(cancelled.percent, Some(90.0));
+        assert_eq!(cancelled.eta_sec, None);
+        assert_eq!(cancelled.output_size_bytes, None);
+
+        let failed = tracker.finish(ExportOutcome::Failed {
+            exit_code: Some(1),
+            stderr_tail: Some("Conversion failed!".to_string()),
+        });
+        assert_eq!(failed.progress, "error");
+        assert!(failed.message.is_some());
+        assert!(matches!(failed.outcome, Some(ExportOutcome::Failed { .. })));
+    }
+}
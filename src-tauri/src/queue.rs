@@ -0,0 +1,752 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, State};
+use uuid::Uuid;
+
+use crate::batch;
+use crate::error::{AppError, AppResult, ExportOutcome};
+use crate::{start_export, ExportManager, ExportParams, ExportStarted, FinishHook};
+
+const QUEUE_FILE: &str = "jobs.json";
+/// Where an unreadable `jobs.json` is moved, so the next save does not
+/// overwrite it.
+const QUEUE_BACKUP_FILE: &str = "jobs.json.bak";
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum JobStatus {
+    Queued,
+    Paused,
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+impl JobStatus {
+    pub(crate) fn is_finished(self) -> bool {
+        matches!(
+            self,
+            JobStatus::Completed | JobStatus::Failed | JobStatus::Cancelled
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub(crate) struct ExportJob {
+    /// Also used as the `export_id` of the job's `export-progress` events.
+    pub(crate) id: String,
+    pub(crate) params: ExportParams,
+    pub(crate) status: JobStatus,
+    created_at: u64,
+    started_at: Option<u64>,
+    finished_at: Option<u64>,
+    /// The de-duplicated output path, known once the job has started.
+    pub(crate) output_path: Option<String>,
+    command: Option<String>,
+    pub(crate) message: Option<String>,
+    /// How the job's last run ended.
+    #[serde(default)]
+    outcome: Option<ExportOutcome>,
+    /// Set for jobs enqueued together by `batch_export`.
+    #[serde(default)]
+    pub(crate) batch_id: Option<String>,
+}
+
+impl ExportJob {
+    fn queued(params: ExportParams, batch_id: Option<&str>) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            params,
+            status: JobStatus::Queued,
+            created_at: now(),
+            started_at: None,
+            finished_at: None,
+            output_path: None,
+            command: None,
+            message: None,
+            outcome: None,
+            batch_id: batch_id.map(str::to_string),
+        }
+    }
+
+    /// Records how starting the job went. Warnings only fill an empty message,
+    /// as the job may already have finished and set its own.
+    /// Returns whether the job failed to start.
+    fn record_start(&mut self, result: AppResult<ExportStarted>) -> bool {
+        match result {
+            Ok(started) => {
+                self.output_path = Some(started.output_path);
+                self.command = Some(started.command);
+                if !started.warnings.is_empty() && self.message.is_none() {
+                    self.message = Some(started.warnings.join(" "));
+                }
+                false
+            }
+            Err(error) => {
+                self.status = JobStatus::Failed;
+                self.finished_at = Some(now());
+                self.message = Some(error.to_string());
+                self.outcome = Some(ExportOutcome::SpawnFailed {
+                    message: error.to_string(),
+                });
+                true
+            }
+        }
+    }
+
+    fn finish(&mut self, outcome: &ExportOutcome) {
+        self.status = match outcome {
+            ExportOutcome::Succeeded { .. } => JobStatus::Completed,
+            ExportOutcome::Cancelled => JobStatus::Cancelled,
+            ExportOutcome::Failed { .. } | ExportOutcome::SpawnFailed { .. } => JobStatus::Failed,
+        };
+        self.finished_at = Some(now());
+        self.message = outcome.summary();
+        self.outcome = Some(outcome.clone());
+    }
+
+    /// Queues a job that was running when the app closed again and deletes its
+    /// partial output.
+    fn requeue_interrupted(&mut self) {
+        if self.status != JobStatus::Running {
+            return;
+        }
+        if let Some(partial) = self.output_path.take() {
+            let _ = fs::remove_file(partial);
+        }
+        self.command = None;
+        self.status = JobStatus::Queued;
+        self.started_at = None;
+        self.message = Some("Restarted after the app was closed.".to_string());
+    }
+}
+
+/// Queue state as persisted to `jobs.json` and sent with `queue-changed` events.
+#[derive(Serialize, Deserialize, Clone)]
+pub(crate) struct QueueSnapshot {
+    concurrency: usize,
+    paused: bool,
+    jobs: Vec<ExportJob>,
+    /// Problem found while restoring the queue, e.g. an unreadable `jobs.json`
+    /// that was moved aside. Not read back from disk.
+    #[serde(default, skip_deserializing, skip_serializing_if = "Option::is_none")]
+    notice: Option<String>,
+}
+
+impl Default for QueueSnapshot {
+    fn default() -> Self {
+        Self {
+            concurrency: 1,
+            paused: false,
+            jobs: Vec::new(),
+            notice: None,
+        }
+    }
+}
+
+#[derive(Default)]
+struct QueueInner {
+    snapshot: QueueSnapshot,
+    file: Option<PathBuf>,
+}
+
+#[derive(Default)]
+pub(crate) struct JobQueue {
+    inner: Mutex<QueueInner>,
+}
+
+impl JobQueue {
+    fn lock(&self) -> AppResult<MutexGuard<'_, QueueInner>> {
+        self.inner.lock().map_err(|_| AppError::lock())
+    }
+}
+
+impl QueueInner {
+    /// Marks queued jobs as running, in queue order, until the concurrency
+    /// limit is reached, and returns them. Nothing starts while paused.
+    fn pick_jobs(&mut self) -> Vec<(String, ExportParams)> {
+        if self.snapshot.paused {
+            return Vec::new();
+        }
+        let running = self
+            .snapshot
+            .jobs
+            .iter()
+            .filter(|job| job.status == JobStatus::Running)
+            .count();
+        let slots = self.snapshot.concurrency.saturating_sub(running);
+        let mut picked = Vec::new();
+        for job in self.snapshot.jobs.iter_mut() {
+            if picked.len() >= slots {
+                break;
+            }
+            if job.status == JobStatus::Queued {
+                job.status = JobStatus::Running;
+                job.started_at = Some(now());
+                job.message = None;
+                picked.push((job.id.clone(), job.params.clone()));
+            }
+        }
+        picked
+    }
+
+    fn job_mut(&mut self, job_id: &str) -> AppResult<&mut ExportJob> {
+        self.snapshot
+            .jobs
+            .iter_mut()
+            .find(|job| job.id == job_id)
+            .ok_or_else(|| AppError::not_found(format!("Unknown job: {job_id}")))
+    }
+
+    fn save(&self) -> AppResult<()> {
+        let Some(file) = &self.file else {
+            return Ok(());
+        };
+        if let Some(parent) = file.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| AppError::io(format!("Failed to create queue directory: {}", e)))?;
+        }
+        let json = serde_json::to_string_pretty(&self.snapshot)
+            .map_err(|e| AppError::io(format!("Failed to serialize queue: {}", e)))?;
+        let temp = file.with_extension("json.tmp");
+        let write_error = |e: std::io::Error| AppError::io(format!("Failed to write queue: {}", e));
+        fs::write(&temp, json).map_err(write_error)?;
+        fs::rename(&temp, file).map_err(write_error)
+    }
+}
+
+pub(crate) fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Persists the queue and notifies the frontend with the full snapshot.
+fn publish(app: &AppHandle, inner: &QueueInner) -> AppResult<()> {
+    let _ = app.emit("queue-changed", inner.snapshot.clone());
+    inner.save()
+}
+
+/// Sends the aggregated `batch-progress` event for the batch `job_id` belongs
+/// to, if any.
+fn publish_batch(app: &AppHandle, inner: &QueueInner, job_id: &str) {
+    let batch_id = inner
+        .snapshot
+        .jobs
+        .iter()
+        .find(|job| job.id == job_id)
+        .and_then(|job| job.batch_id.as_deref());
+    if let Some(batch_id) = batch_id {
+        let progress = batch::batch_progress(&inner.snapshot.jobs, batch_id);
+        let _ = app.emit("batch-progress", progress);
+    }
+}
+
+/// Reads a saved queue. A file that cannot be parsed is renamed to
+/// `jobs.json.bak` and reported, instead of being overwritten by the next save.
+fn read_snapshot(file: &Path) -> Result<Option<QueueSnapshot>, String> {
+    let Ok(json) = fs::read_to_string(file) else {
+        return Ok(None);
+    };
+    match serde_json::from_str::<QueueSnapshot>(&json) {
+        Ok(snapshot) => Ok(Some(snapshot)),
+        Err(error) => {
+            let backup = file.with_file_name(QUEUE_BACKUP_FILE);
+            let kept = match fs::rename(file, &backup) {
+                Ok(()) => format!("it was moved to {}", backup.display()),
+                Err(e) => format!("moving it aside failed: {}", e),
+            };
+            Err(format!(
+                "The saved export queue could not be read ({error}); {kept}."
+            ))
+        }
+    }
+}
+
+/// Loads `jobs.json` from the app data dir. Jobs that were running when the app
+/// closed are queued again and restarted from the beginning; their partial
+/// output is deleted so the restart writes to the same path instead of a
+/// de-duplicated `name (1)` next to a truncated file.
+pub(crate) fn restore(app: &AppHandle) {
+    let file = app
+        .path()
+        .app_data_dir()
+        .ok()
+        .map(|dir| dir.join(QUEUE_FILE));
+    let queue = app.state::<JobQueue>();
+    if let Ok(mut inner) = queue.lock() {
+        match file.as_deref().map(read_snapshot) {
+            Some(Ok(Some(snapshot))) => inner.snapshot = snapshot,
+            Some(Err(notice)) => {
+                eprintln!("warning: {notice}");
+                inner.snapshot.notice = Some(notice);
+            }
+            Some(Ok(None)) | None => {}
+        }
+        inner.file = file;
+        for job in inner.snapshot.jobs.iter_mut() {
+            job.requeue_interrupted();
+        }
+    }
+    pump(app);
+}
+
+/// Starts queued jobs in order until the concurrency limit is reached.
+fn pump(app: &AppHandle) {
+    let queue = app.state::<JobQueue>();
+    let export_manager = app.state::<ExportManager>();
+
+    loop {
+        let picked = {
+            let Ok(mut inner) = queue.lock() else {
+                return;
+            };
+            let picked = inner.pick_jobs();
+            // Registered while the queue is still locked, so a `cancel_job`
+            // for a job that is running but not yet spawned is kept.
+            for (job_id, _) in &picked {
+                let _ = export_manager.register(job_id);
+            }
+            picked
+        };
+        if picked.is_empty() {
+            return;
+        }
+
+        let mut start_failed = false;
+        for (job_id, params) in picked {
+            let hook_id = job_id.clone();
+            let on_finish: FinishHook =
+                Box::new(move |app: &AppHandle, outcome: &ExportOutcome| {
+                    finish_job(app, &hook_id, outcome)
+                });
+            let result = start_export(
+                app,
+                &export_manager,
+                job_id.clone(),
+                &params,
+                Some(on_finish),
+            );
+
+            let Ok(mut inner) = queue.lock() else {
+                return;
+            };
+            if let Ok(job) = inner.job_mut(&job_id) {
+                start_failed |= job.record_start(result);
+            }
+            let _ = publish(app, &inner);
+            publish_batch(app, &inner, &job_id);
+        }
+
+        // A job that failed to start frees its slot for the next one.
+        if !start_failed {
+            return;
+        }
+    }
+}
+
+fn finish_job(app: &AppHandle, job_id: &str, outcome: &ExportOutcome) {
+    let queue = app.state::<JobQueue>();
+    if let Ok(mut inner) = queue.lock() {
+        if let Ok(job) = inner.job_mut(job_id) {
+            job.finish(outcome);
+        }
+        let _ = publish(app, &inner);
+        publish_batch(app, &inner, job_id);
+    }
+    pump(app);
+}
+
+#[tauri::command]
+pub(crate) fn list_jobs(queue: State<'_, JobQueue>) -> AppResult<QueueSnapshot> {
+    Ok(queue.lock()?.snapshot.clone())
+}
+
+/// Appends one queued job per entry of `params` and starts as many as the
+/// concurrency limit allows.
+pub(crate) fn enqueue_jobs(
+    app: &AppHandle,
+    params: Vec<ExportParams>,
+    batch_id: Option<&str>,
+) -> AppResult<Vec<ExportJob>> {
+    let jobs: Vec<ExportJob> = params
+        .into_iter()
+        .map(|params| ExportJob::queued(params, batch_id))
+        .collect();
+    {
+        let queue = app.state::<JobQueue>();
+        let mut inner = queue.lock()?;
+        inner.snapshot.jobs.extend(jobs.iter().cloned());
+        publish(app, &inner)?;
+    }
+    pump(app);
+    Ok(jobs)
+}
+
+/// A copy of every job in the queue.
+pub(crate) fn jobs(app: &AppHandle) -> AppResult<Vec<ExportJob>> {
+    let queue = app.state::<JobQueue>();
+    let inner = queue.lock()?;
+    Ok(inner.snapshot.jobs.clone())
+}
+
+#[tauri::command]
+pub(crate) fn enqueue_export(app: AppHandle, params: ExportParams) -> AppResult<ExportJob> {
+    let mut jobs = enqueue_jobs(&app, vec![params], None)?;
+    jobs.pop()
+        .ok_or_else(|| AppError::invalid_state("No job was queued."))
+}
+
+/// Moves a job to `position` in the queue; later positions start later.
+#[tauri::command]
+pub(crate) fn reorder_job(
+    app: AppHandle,
+    queue: State<'_, JobQueue>,
+    job_id: String,
+    position: usize,
+) -> AppResult<()> {
+    let mut inner = queue.lock()?;
+    let jobs = &mut inner.snapshot.jobs;
+    let index = jobs
+        .iter()
+        .position(|job| job.id == job_id)
+        .ok_or_else(|| AppError::not_found(format!("Unknown job: {job_id}")))?;
+    let job = jobs.remove(index);
+    let position = position.min(jobs.len());
+    jobs.insert(position, job);
+    publish(&app, &inner)
+}
+
+#[tauri::command]
+pub(crate) fn pause_job(
+    app: AppHandle,
+    queue: State<'_, JobQueue>,
+    job_id: String,
+) -> AppResult<()> {
+    let mut inner = queue.lock()?;
+    let job = inner.job_mut(&job_id)?;
+    if job.status != JobStatus::Queued {
+        return Err(AppError::invalid_state("Only queued jobs can be paused."));
+    }
+    job.status = JobStatus::Paused;
+    publish(&app, &inner)
+}
+
+#[tauri::command]
+pub(crate) fn resume_job(
+    app: AppHandle,
+    queue: State<'_, JobQueue>,
+    job_id: String,
+) -> AppResult<()> {
+    {
+        let mut inner = queue.lock()?;
+        let job = inner.job_mut(&job_id)?;
+        if job.status != JobStatus::Paused {
+            return Err(AppError::invalid_state("Only paused jobs can be resumed."));
+        }
+        job.status = JobStatus::Queued;
+        publish(&app, &inner)?;
+    }
+    pump(&app);
+    Ok(())
+}
+
+#[tauri::command]
+pub(crate) fn cancel_job(
+    app: AppHandle,
+    queue: State<'_, JobQueue>,
+    export_manager: State<'_, ExportManager>,
+    job_id: String,
+) -> AppResult<()> {
+    let mut inner = queue.lock()?;
+    let job = inner.job_mut(&job_id)?;
+    match job.status {
+        JobStatus::Queued | JobStatus::Paused => {
+            job.status = JobStatus::Cancelled;
+            job.finished_at = Some(now());
+            publish_batch(&app, &inner, &job_id);
+        }
+        JobStatus::Running => {
+            // The finish hook records the cancelled outcome and starts the next job.
+            export_manager.kill(&job_id)?;
+            return Ok(());
+        }
+        _ => return Err(AppError::invalid_state("Job has already finished.")),
+    }
+    publish(&app, &inner)
+}
+
+#[tauri::command]
+pub(crate) fn remove_job(
+    app: AppHandle,
+    queue: State<'_, JobQueue>,
+    job_id: String,
+) -> AppResult<()> {
+    let mut inner = queue.lock()?;
+    if inner.job_mut(&job_id)?.status == JobStatus::Running {
+        return Err(AppError::invalid_state(
+            "Cancel the job before removing it.",
+        ));
+    }
+    inner.snapshot.jobs.retain(|job| job.id != job_id);
+    publish(&app, &inner)
+}
+
+#[tauri::command]
+pub(crate) fn clear_finished_jobs(app: AppHandle, queue: State<'_, JobQueue>) -> AppResult<()> {
+    let mut inner = queue.lock()?;
+    inner.snapshot.jobs.retain(|job| !job.status.is_finished());
+    publish(&app, &inner)
+}
+
+#[tauri::command]
+pub(crate) fn set_queue_concurrency(
+    app: AppHandle,
+    queue: State<'_, JobQueue>,
+    limit: usize,
+) -> AppResult<()> {
+    if limit == 0 {
+        return Err(AppError::invalid("Concurrency must be at least 1."));
+    }
+    {
+        let mut inner = queue.lock()?;
+        inner.snapshot.concurrency = limit;
+        publish(&app, &inner)?;
+    }
+    pump(&app);
+    Ok(())
+}
+
+/// Pausing the queue keeps running jobs going but starts no new ones.
+#[tauri::command]
+pub(crate) fn set_queue_paused(
+    app: AppHandle,
+    queue: State<'_, JobQueue>,
+    paused: bool,
+) -> AppResult<()> {
+    {
+        let mut inner = queue.lock()?;
+        inner.snapshot.paused = paused;
+        publish(&app, &inner)?;
+    }
+    pump(&app);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn job() -> ExportJob {
+        let params = serde_json::from_value(json!({
+            "exportMode": "side_by_side",
+            "outputPath": "/tmp/out.mp4",
+            "codec": "libx264",
+            "crf": 23,
+            "keepAspect": true,
+            "audioCopy": false
+        }))
+        .unwrap();
+        ExportJob::queued(params, None)
+    }
+
+    fn queue(concurrency: usize, statuses: &[JobStatus]) -> QueueInner {
+        let jobs = statuses
+            .iter()
+            .map(|&status| ExportJob { status, ..job() })
+            .collect();
+        QueueInner {
+            snapshot: QueueSnapshot {
+                concurrency,
+                jobs,
+                ..QueueSnapshot::default()
+            },
+            file: None,
+        }
+    }
+
+    fn statuses(inner: &QueueInner) -> Vec<JobStatus> {
+        inner.snapshot.jobs.iter().map(|job| job.status).collect()
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("pixelduel-{name}-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn picking_fills_free_slots_in_queue_order() {
+        use JobStatus::*;
+        let mut inner = queue(2, &[Completed, Running, Queued, Queued]);
+        let picked = inner.pick_jobs();
+        assert_eq!(picked.len(), 1);
+        assert_eq!(picked[0].0, inner.snapshot.jobs[2].id);
+        assert_eq!(statuses(&inner), [Completed, Running, Running, Queued]);
+        assert!(inner.snapshot.jobs[2].started_at.is_some());
+
+        assert!(inner.pick_jobs().is_empty());
+    }
+
+    #[test]
+    fn nothing_is_picked_while_paused() {
+        let mut inner = queue(3, &[JobStatus::Queued, JobStatus::Paused]);
+        inner.snapshot.paused = true;
+        assert!(inner.pick_jobs().is_empty());
+        inner.snapshot.paused = false;
+        assert_eq!(inner.pick_jobs().len(), 1);
+        assert_eq!(statuses(&inner), [JobStatus::Running, JobStatus::Paused]);
+    }
+
+    #[test]
+    fn finishing_sets_status_from_the_outcome() {
+        let cases = [
+            (
+                ExportOutcome::Succeeded {
+                    output_size_bytes: Some(1),
+                    elapsed_sec: 1.0,
+                },
+                JobStatus::Completed,
+            ),
+            (ExportOutcome::Cancelled, JobStatus::Cancelled),
+            (
+                ExportOutcome::Failed {
+                    exit_code: Some(1),
+                    stderr_tail: None,
+                },
+                JobStatus::Failed,
+            ),
+            (
+                ExportOutcome::SpawnFailed {
+                    message: "no ffmpeg".to_string(),
+                },
+                JobStatus::Failed,
+            ),
+        ];
+        for (outcome, status) in cases {
+            let mut job = ExportJob {
+                status: JobStatus::Running,
+                ..job()
+            };
+            job.finish(&outcome);
+            assert_eq!(job.status, status);
+            assert!(job.finished_at.is_some());
+            assert_eq!(job.message, outcome.summary());
+        }
+    }
+
+    #[test]
+    fn start_warnings_do_not_replace_a_finished_message() {
+        let started = || ExportStarted {
+            export_id: "id".to_string(),
+            command: "ffmpeg".to_string(),
+            output_path: "/tmp/out.mp4".to_string(),
+            warnings: vec!["Audio is dropped.".to_string()],
+        };
+
+        let mut running = ExportJob {
+            status: JobStatus::Running,
+            ..job()
+        };
+        assert!(!running.record_start(Ok(started())));
+        assert_eq!(running.message.as_deref(), Some("Audio is dropped."));
+
+        let mut failed = ExportJob {
+            status: JobStatus::Running,
+            ..job()
+        };
+        failed.finish(&ExportOutcome::Failed {
+            exit_code: Some(1),
+            stderr_tail: None,
+        });
+        let message = failed.message.clone();
+        assert!(message.is_some());
+        assert!(!failed.record_start(Ok(started())));
+        assert_eq!(failed.message, message);
+        assert_eq!(failed.status, JobStatus::Failed);
+    }
+
+    #[test]
+    fn a_failed_start_marks_the_job_failed() {
+        let mut job = ExportJob {
+            status: JobStatus::Running,
+            ..job()
+        };
+        assert!(job.record_start(Err(AppError::invalid("bad params"))));
+        assert_eq!(job.status, JobStatus::Failed);
+        assert!(matches!(
+            job.outcome,
+            Some(ExportOutcome::SpawnFailed { .. })
+        ));
+    }
+
+    #[test]
+    fn interrupted_jobs_are_queued_again_without_their_partial_output() {
+        let dir = temp_dir("queue-requeue");
+        let partial = dir.join("out.mp4");
+        fs::write(&partial, b"partial").unwrap();
+        let mut running = ExportJob {
+            status: JobStatus::Running,
+            started_at: Some(1),
+            output_path: Some(partial.to_string_lossy().into_owned()),
+            command: Some("ffmpeg".to_string()),
+            ..job()
+        };
+        running.requeue_interrupted();
+        assert_eq!(running.status, JobStatus::Queued);
+        assert!(running.started_at.is_none() && running.command.is_none());
+        assert!(running.output_path.is_none());
+        assert!(!partial.exists());
+
+        let mut completed = ExportJob {
+            status: JobStatus::Completed,
+            ..job()
+        };
+        completed.requeue_interrupted();
+        assert_eq!(completed.status, JobStatus::Completed);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn a_saved_queue_is_read_back() {
+        let dir = temp_dir("queue-restore");
+        let file = dir.join(QUEUE_FILE);
+        assert!(matches!(read_snapshot(&file), Ok(None)));
+
+        let inner = QueueInner {
+            file: Some(file.clone()),
+            ..queue(2, &[JobStatus::Running, JobStatus::Queued])
+        };
+        inner.save().unwrap();
+        let snapshot = read_snapshot(&file).unwrap().unwrap();
+        assert_eq!(snapshot.concurrency, 2);
+        assert_eq!(snapshot.jobs.len(), 2);
+        assert!(snapshot.jobs[0].status == JobStatus::Running);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn a_corrupt_queue_file_is_moved_aside_and_reported() {
+        let dir = temp_dir("queue-corrupt");
+        let file = dir.join(QUEUE_FILE);
+        fs::write(&file, "{ not json").unwrap();
+        let notice = match read_snapshot(&file) {
+            Err(notice) => notice,
+            Ok(_) => panic!("corrupt file was accepted"),
+        };
+        assert!(notice.contains(QUEUE_BACKUP_FILE));
+        assert!(!file.exists());
+        assert_eq!(
+            fs::read_to_string(dir.join(QUEUE_BACKUP_FILE)).unwrap(),
+            "{ not json"
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,582 @@
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::cache::ProbeCache;
+use crate::diff::{field_diffs, FieldDiff, Severity};
+use crate::error::{AppError, AppResult};
+use crate::frames::grab_frame;
+use crate::metrics::QualityReport;
+use crate::probe::VideoInfo;
+use crate::queue::now;
+use crate::resolve_bundled_binary;
+
+const DEFAULT_THUMBNAIL_COUNT: u32 = 4;
+const DEFAULT_THUMBNAIL_WIDTH: u32 = 480;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ReportParams {
+    input_path_a: String,
+    input_path_b: String,
+    /// Report path; every format is written next to it with its own extension.
+    output_path: String,
+    /// Any of `html`, `json` and `md`. Empty writes all three.
+    #[serde(default)]
+    formats: Vec<String>,
+    title: Option<String>,
+    /// A `compute_quality_metrics` result (the `report` of `metrics-complete`).
+    metrics: Option<QualityReport>,
+    /// Frame pairs taken at evenly spaced timestamps; 0 leaves them out.
+    /// Defaults to 4.
+    thumbnail_count: Option<u32>,
+    /// Thumbnail width in pixels. Defaults to 480.
+    thumbnail_width: Option<u32>,
+}
+
+#[derive(Serialize)]
+pub(crate) struct ReportWritten {
+    files: Vec<String>,
+    /// Thumbnails that could not be extracted; the report is written without them.
+    warnings: Vec<String>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ReportFormat {
+    Html,
+    Json,
+    Markdown,
+}
+
+impl ReportFormat {
+    fn parse(value: &str) -> AppResult<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "html" => Ok(Self::Html),
+            "json" => Ok(Self::Json),
+            "md" | "markdown" => Ok(Self::Markdown),
+            other => Err(AppError::invalid(format!(
+                "Unknown report format: {other}. Use html, json or md."
+            ))),
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Html => "html",
+            Self::Json => "json",
+            Self::Markdown => "md",
+        }
+    }
+}
+
+/// A frame of A and B at the same timestamp. `a` and `b` are PNG files
+/// relative to the report; the HTML report embeds them instead.
+#[derive(Serialize)]
+struct ThumbnailPair {
+    time_sec: f64,
+    a: Option<String>,
+    b: Option<String>,
+    #[serde(skip)]
+    png_a: Option<Vec<u8>>,
+    #[serde(skip)]
+    png_b: Option<Vec<u8>>,
+}
+
+/// Everything a report shows; the JSON report is this struct as is.
+#[derive(Serialize)]
+struct Report<'a> {
+    title: String,
+    created_at: u64,
+    a: &'a VideoInfo,
+    b: &'a VideoInfo,
+    differences: Vec<FieldDiff>,
+    metrics: Option<&'a QualityReport>,
+    thumbnails: Vec<ThumbnailPair>,
+}
+
+/// Timestamps in the middle of `count` equal slices of the shorter input.
+fn thumbnail_times(a: &VideoInfo, b: &VideoInfo, count: u32) -> Vec<f64> {
+    let duration = [a.container.duration_sec, b.container.duration_sec]
+        .into_iter()
+        .flatten()
+        .filter(|duration| *duration > 0.0)
+        .reduce(f64::min);
+    match duration {
+        Some(duration) => (0..count)
+            .map(|index| (index as f64 + 0.5) * duration / count as f64)
+            .collect(),
+        None => vec![0.0; count.min(1) as usize],
+    }
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let group = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for index in 0..4 {
+            if index <= chunk.len() {
+                encoded.push(ALPHABET[(group >> (18 - 6 * index) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Table cell text for Markdown; pipes and line breaks would end the cell.
+fn escape_cell(value: &str) -> String {
+    value.replace('|', "\\|").replace(['\r', '\n'], " ")
+}
+
+fn severity_name(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Significant => "significant",
+        Severity::Minor => "minor",
+        Severity::Info => "info",
+    }
+}
+
+fn side(value: &Option<String>) -> &str {
+    value.as_deref().unwrap_or("-")
+}
+
+fn delta(diff: &FieldDiff) -> String {
+    diff.delta_percent
+        .map(|delta| format!("{delta:+.1}%"))
+        .unwrap_or_default()
+}
+
+/// Per-frame scores as an SVG polyline, scaled to the series' own range.
+fn sparkline(frames: &[Option<f64>]) -> String {
+    let points: Vec<(usize, f64)> = frames
+        .iter()
+        .enumerate()
+        .filter_map(|(index, value)| value.map(|value| (index, value)))
+        .collect();
+    let (Some(min), Some(max)) = (
+        points.iter().map(|(_, value)| *value).reduce(f64::min),
+        points.iter().map(|(_, value)| *value).reduce(f64::max),
+    ) else {
+        return String::new();
+    };
+    let (width, height) = (240.0, 40.0);
+    let last = frames.len().saturating_sub(1).max(1) as f64;
+    let range = if max > min { max - min } else { 1.0 };
+    let path: Vec<String> = points
+        .iter()
+        .map(|(index, value)| {
+            let x = *index as f64 / last * width;
+            let y = height - (value - min) / range * height;
+            format!("{x:.1},{y:.1}")
+        })
+        .collect();
+    format!(
+        r##"<svg width="{width}" height="{height}" viewBox="0 0 {width} {height}"><polyline fill="none" stroke="#e07a2f" stroke-width="1" points="{}"/></svg>"##,
+        path.join(" ")
+    )
+}
+
+const HTML_STYLE: &str = "body{font-family:system-ui,sans-serif;margin:2rem;color:#222}\
+table{border-collapse:collapse;margin-bottom:1.5rem}\
+th,td{border:1px solid #ccc;padding:4px 8px;text-align:left;vertical-align:top}\
+th{background:#f2f2f2}td.differs{font-weight:600}\
+.significant{color:#b3261e}.minor{color:#9a6700}.info{color:#666}\
+img{max-width:100%}";
+
+fn render_html(report: &Report) -> String {
+    let mut html = String::new();
+    let title = escape_html(&report.title);
+    let _ = write!(
+        html,
+        "<!doctype html>\n<html lang=\"en\">\n<head>\n<meta charset=\"UTF-8\" />\n\
+         <title>{title}</title>\n<style>{HTML_STYLE}</style>\n</head>\n<body>\n<h1>{title}</h1>\n"
+    );
+
+    html.push_str("<h2>Probe</h2>\n<table>\n<tr><th>Field</th><th>A</th><th>B</th></tr>\n");
+    for ((field, a), (_, b)) in report.a.summary_rows().iter().zip(report.b.summary_rows()) {
+        let class = if *a == b { "" } else { " class=\"differs\"" };
+        let _ = writeln!(
+            html,
+            "<tr><td>{field}</td><td{class}>{}</td><td{class}>{}</td></tr>",
+            escape_html(a),
+            escape_html(&b)
+        );
+    }
+    html.push_str("</table>\n");
+
+    html.push_str("<h2>Differences</h2>\n");
+    if report.differences.is_empty() {
+        html.push_str("<p>No differences.</p>\n");
+    } else {
+        html.push_str(
+            "<table>\n<tr><th>Severity</th><th>Field</th><th>A</th><th>B</th><th>Change</th></tr>\n",
+        );
+        for diff in &report.differences {
+            let severity = severity_name(diff.severity);
+            let _ = writeln!(
+                html,
+                "<tr class=\"{severity}\"><td>{severity}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                diff.label,
+                escape_html(side(&diff.a)),
+                escape_html(side(&diff.b)),
+                delta(diff)
+            );
+        }
+        html.push_str("</table>\n");
+    }
+
+    if let Some(metrics) = report.metrics {
+        html.push_str(
+            "<h2>Quality metrics</h2>\n<table>\n<tr><th>Metric</th><th>Mean</th><th>Min</th>\
+             <th>P5</th><th>P50</th><th>P95</th><th>Max</th><th>Per frame</th></tr>\n",
+        );
+        for series in &metrics.metrics {
+            let _ = write!(html, "<tr><td>{}</td>", escape_html(&series.name));
+            match &series.summary {
+                Some(s) => {
+                    for value in [s.mean, s.min, s.p5, s.p50, s.p95, s.max] {
+                        let _ = write!(html, "<td>{value:.3}</td>");
+                    }
+                }
+                None => html.push_str("<td colspan=\"6\">No scores</td>"),
+            }
+            let _ = writeln!(html, "<td>{}</td></tr>", sparkline(&series.frames));
+        }
+        html.push_str("</table>\n");
+    }
+
+    if !report.thumbnails.is_empty() {
+        html.push_str("<h2>Frames</h2>\n<table>\n<tr><th>Time</th><th>A</th><th>B</th></tr>\n");
+        for pair in &report.thumbnails {
+            let image = |png: &Option<Vec<u8>>| match png {
+                Some(png) => format!("<img src=\"data:image/png;base64,{}\" />", base64(png)),
+                None => "-".to_string(),
+            };
+            let _ = writeln!(
+                html,
+                "<tr><td>{:.3} s</td><td>{}</td><td>{}</td></tr>",
+                pair.time_sec,
+                image(&pair.png_a),
+                image(&pair.png_b)
+            );
+        }
+        html.push_str("</table>\n");
+    }
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+fn render_markdown(report: &Report) -> String {
+    let mut md = String::new();
+    let _ = writeln!(md, "# {}\n", report.title);
+
+    md.push_str("## Probe\n\n| Field | A | B |\n| --- | --- | --- |\n");
+    for ((field, a), (_, b)) in report.a.summary_rows().iter().zip(report.b.summary_rows()) {
+        let marker = if *a == b { "" } else { " *" };
+        let _ = writeln!(
+            md,
+            "| {field}{marker} | {} | {} |",
+            escape_cell(a),
+            escape_cell(&b)
+        );
+    }
+
+    md.push_str("\n## Differences\n\n");
+    if report.differences.is_empty() {
+        md.push_str("No differences.\n");
+    } else {
+        md.push_str("| Severity | Field | A | B | Change |\n| --- | --- | --- | --- | --- |\n");
+        for diff in &report.differences {
+            let _ = writeln!(
+                md,
+                "| {} | {} | {} | {} | {} |",
+                severity_name(diff.severity),
+                diff.label,
+                escape_cell(side(&diff.a)),
+                escape_cell(side(&diff.b)),
+                delta(diff)
+            );
+        }
+    }
+
+    if let Some(metrics) = report.metrics {
+        md.push_str("\n## Quality metrics\n\n");
+        md.push_str("| Metric | Mean | Min | P5 | P50 | P95 | Max |\n");
+        md.push_str("| --- | --- | --- | --- | --- | --- | --- |\n");
+        for series in &metrics.metrics {
+            let values = match &series.summary {
+                Some(s) => [s.mean, s.min, s.p5, s.p50, s.p95, s.max]
+                    .map(|value| format!("{value:.3}"))
+                    .join(" | "),
+                None => ["-"; 6].join(" | "),
+            };
+            let _ = writeln!(md, "| {} | {values} |", escape_cell(&series.name));
+        }
+    }
+
+    if !report.thumbnails.is_empty() {
+        md.push_str("\n## Frames\n\n| Time | A | B |\n| --- | --- | --- |\n");
+        for pair in &report.thumbnails {
+            let image = |label: &str, file: &Option<String>| match file {
+                Some(file) => format!("![{label} {:.3}s]({})", pair.time_sec, file),
+                None => "-".to_string(),
+            };
+            let _ = writeln!(
+                md,
+                "| {:.3} s | {} | {} |",
+                pair.time_sec,
+                image("A", &pair.a),
+                image("B", &pair.b)
+            );
+        }
+    }
+    md
+}
+
+fn write_file(path: &Path, contents: &[u8]) -> AppResult<()> {
+    fs::write(path, contents)
+        .map_err(|e| AppError::io(format!("Failed to write {}: {}", path.display(), e)))
+}
+
+/// Writes a comparison report of two inputs: both probes, the field
+/// differences, optional quality metrics and matching frames of A and B.
+#[tauri::command(async)]
+pub(crate) fn export_report(app: AppHandle, params: ReportParams) -> AppResult<ReportWritten> {
+    if params.output_path.trim().is_empty() {
+        return Err(AppError::invalid("A report path is required."));
+    }
+    let mut formats = Vec::new();
+    for name in &params.formats {
+        let format = ReportFormat::parse(name)?;
+        if !formats.contains(&format) {
+            formats.push(format);
+        }
+    }
+    if formats.is_empty() {
+        formats = vec![
+            ReportFormat::Html,
+            ReportFormat::Json,
+            ReportFormat::Markdown,
+        ];
+    }
+
+    let ffprobe = resolve_bundled_binary(&app, "ffprobe")?;
+    let cache = app.state::<ProbeCache>();
+    let info_a = cache.probe(&ffprobe, &params.input_path_a)?;
+    let info_b = cache.probe(&ffprobe, &params.input_path_b)?;
+
+    let base = PathBuf::from(params.output_path.trim());
+    if let Some(parent) = base.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)
+            .map_err(|e| AppError::io(format!("Failed to create report folder: {}", e)))?;
+    }
+    let stem = base
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_else(|| "report".to_string());
+    // JSON and Markdown link to PNG files; only HTML embeds the frames.
+    let frames_dir_name = format!("{stem}_frames");
+    let frames_dir = base.with_file_name(&frames_dir_name);
+    let write_frames = formats.iter().any(|format| *format != ReportFormat::Html);
+
+    let mut warnings = Vec::new();
+    let mut thumbnails = Vec::new();
+    let count = params.thumbnail_count.unwrap_or(DEFAULT_THUMBNAIL_COUNT);
+    if count > 0 {
+        let ffmpeg = resolve_bundled_binary(&app, "ffmpeg")?;
+        let width = params.thumbnail_width.unwrap_or(DEFAULT_THUMBNAIL_WIDTH);
+        if write_frames {
+            fs::create_dir_all(&frames_dir)
+                .map_err(|e| AppError::io(format!("Failed to create frames folder: {}", e)))?;
+        }
+        for (index, time_sec) in thumbnail_times(&info_a, &info_b, count)
+            .into_iter()
+            .enumerate()
+        {
+            let mut grab = |letter: &str, path: &str| -> AppResult<Option<(String, Vec<u8>)>> {
+                let png = match grab_frame(&ffmpeg, path, time_sec, Some(width)) {
+                    Ok(png) => png,
+                    Err(error) => {
+                        warnings.push(error.message().to_string());
+                        return Ok(None);
+                    }
+                };
+                let name = format!("{letter}_{:02}.png", index + 1);
+                if write_frames {
+                    write_file(&frames_dir.join(&name), &png)?;
+                }
+                Ok(Some((format!("{frames_dir_name}/{name}"), png)))
+            };
+            let (a, png_a) = grab("a", &params.input_path_a)?.unzip();
+            let (b, png_b) = grab("b", &params.input_path_b)?.unzip();
+            thumbnails.push(ThumbnailPair {
+                time_sec,
+                a: a.filter(|_| write_frames),
+                b: b.filter(|_| write_frames),
+                png_a,
+                png_b,
+            });
+        }
+    }
+
+    let report = Report {
+        title: params
+            .title
+            .clone()
+            .filter(|title| !title.trim().is_empty())
+            .unwrap_or_else(|| {
+                let name = |info: &VideoInfo| {
+                    Path::new(&info.file)
+                        .file_name()
+                        .map(|name| name.to_string_lossy().to_string())
+                        .unwrap_or_else(|| info.file.clone())
+                };
+                format!("{} vs {}", name(&info_a), name(&info_b))
+            }),
+        created_at: now(),
+        differences: field_diffs(&info_a, &info_b),
+        a: &info_a,
+        b: &info_b,
+        metrics: params.metrics.as_ref(),
+        thumbnails,
+    };
+
+    let mut files = Vec::new();
+    for format in formats {
+        let path = base.with_extension(format.extension());
+        let contents = match format {
+            ReportFormat::Html => render_html(&report),
+            ReportFormat::Json => serde_json::to_string_pretty(&report)
+                .map_err(|e| AppError::io(format!("Failed to serialize report: {}", e)))?,
+            ReportFormat::Markdown => render_markdown(&report),
+        };
+        write_file(&path, contents.as_bytes())?;
+        files.push(path.to_string_lossy().to_string());
+    }
+    Ok(ReportWritten { files, warnings })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::{MetricSeries, MetricSummary};
+    use serde_json::json;
+
+    #[test]
+    fn base64_pads_partial_groups() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foob"), "Zm9vYg==");
+        assert_eq!(base64(b"fooba"), "Zm9vYmE=");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+        assert_eq!(base64(&[0xff, 0xfe, 0xfd]), "//79");
+    }
+
+    #[test]
+    fn escape_html_escapes_markup() {
+        assert_eq!(
+            escape_html(r#"<a href="x">Tom & Jerry</a>"#),
+            "&lt;a href=&quot;x&quot;&gt;Tom &amp; Jerry&lt;/a&gt;"
+        );
+        assert_eq!(escape_html("&lt;"), "&amp;lt;");
+        assert_eq!(escape_html("plain"), "plain");
+    }
+
+    fn info(file: &str, width: u32) -> VideoInfo {
+        serde_json::from_value(json!({
+            "file": file,
+            "container": {},
+            "video": { "index": 0, "width": width, "height": 720 },
+            "subtitles": [],
+            "attachments": [],
+            "streams": [],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn render_markdown_lists_probes_differences_and_frames() {
+        let a = info("a|b.mp4", 1280);
+        let b = info("c.mp4", 1920);
+        let metrics = QualityReport {
+            reference: "a|b.mp4".to_string(),
+            distorted: "c.mp4".to_string(),
+            metrics: vec![MetricSeries {
+                name: "psnr".to_string(),
+                summary: Some(MetricSummary {
+                    count: 2,
+                    mean: 40.0,
+                    min: 38.0,
+                    max: 42.0,
+                    p5: 38.2,
+                    p50: 40.0,
+                    p95: 41.8,
+                }),
+                frames: vec![Some(38.0), Some(42.0)],
+            }],
+        };
+        let report = Report {
+            title: "A vs B".to_string(),
+            created_at: 0,
+            a: &a,
+            b: &b,
+            differences: field_diffs(&a, &b),
+            metrics: Some(&metrics),
+            thumbnails: vec![ThumbnailPair {
+                time_sec: 1.5,
+                a: Some("a-0.png".to_string()),
+                b: None,
+                png_a: None,
+                png_b: None,
+            }],
+        };
+        let md = render_markdown(&report);
+        assert!(md.starts_with("# A vs B\n\n## Probe\n\n| Field | A | B |\n| --- | --- | --- |\n"));
+        for line in [
+            "| File * | a\\|b.mp4 | c.mp4 |",
+            "| Resolution * | 1280x720 | 1920x720 |",
+            "| significant | Resolution | 1280x720 | 1920x720 |  |",
+            "| psnr | 40.000 | 38.000 | 38.200 | 40.000 | 41.800 | 42.000 |",
+            "| 1.500 s | ![A 1.500s](a-0.png) | - |",
+        ] {
+            assert!(md.lines().any(|l| l == line), "missing {line:?} in\n{md}");
+        }
+    }
+
+    #[test]
+    fn render_markdown_reports_no_differences() {
+        let a = info("a.mp4", 1280);
+        let report = Report {
+            title: "Same".to_string(),
+            created_at: 0,
+            a: &a,
+            b: &a,
+            differences: Vec::new(),
+            metrics: None,
+            thumbnails: Vec::new(),
+        };
+        let md = render_markdown(&report);
+        assert!(md.ends_with("\n## Differences\n\nNo differences.\n"));
+        assert!(!md.contains("* |"));
+    }
+}
@@ -0,0 +1,343 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, State};
+
+use crate::cache::ProbeCache;
+use crate::error::{AppError, AppResult};
+use crate::probe::VideoInfo;
+use crate::{resolve_bundled_binary, tool_command};
+
+/// Frame intervals further than this (relative) from the median mark a
+/// variable frame rate. Millisecond time bases alone round 23.976 fps to
+/// alternating 41 and 42 ms, about 2.4% apart.
+const VFR_TOLERANCE: f64 = 0.1;
+
+/// Exact frame positions of the main video stream, read from its packets so
+/// nothing has to be decoded. Field-coded video, common for interlaced H.264 in
+/// .ts, .m2ts and .mxf, stores each field in its own packet; it is detected and
+/// read from decoded frames instead.
+#[derive(Serialize, Deserialize, Clone)]
+pub(crate) struct FrameTimeline {
+    pub(crate) stream_index: u32,
+    /// Presentation timestamps in seconds, in display order and relative to
+    /// the file's start time, the origin `-ss` seeks from.
+    pub(crate) pts: Vec<f64>,
+    /// Display-order indices of keyframes.
+    pub(crate) keyframes: Vec<u64>,
+    /// Median distance between frames; the nominal frame duration.
+    pub(crate) frame_interval_sec: Option<f64>,
+    pub(crate) min_frame_interval_sec: Option<f64>,
+    pub(crate) max_frame_interval_sec: Option<f64>,
+    pub(crate) variable_frame_rate: bool,
+}
+
+impl FrameTimeline {
+    pub(crate) fn frame_count(&self) -> u64 {
+        self.pts.len() as u64
+    }
+
+    /// The time between frame `index - 1` and frame `index`. Seeking or
+    /// cutting there is safe against rounding in either direction. `index`
+    /// may be `frame_count()` for the end of the last frame.
+    pub(crate) fn boundary_sec(&self, index: u64) -> f64 {
+        let index = index as usize;
+        let half_interval = self.frame_interval_sec.unwrap_or(0.0) / 2.0;
+        match (
+            index.checked_sub(1).and_then(|i| self.pts.get(i)),
+            self.pts.get(index),
+        ) {
+            (None, _) => 0.0,
+            (Some(previous), Some(next)) => (previous + next) / 2.0,
+            (Some(last), None) => last + half_interval,
+        }
+    }
+}
+
+/// What `deep_probe` returns: the timeline summary, plus the timestamps when
+/// they were asked for.
+#[derive(Serialize)]
+pub(crate) struct DeepProbe {
+    file: String,
+    stream_index: u32,
+    frame_count: u64,
+    keyframes: Vec<u64>,
+    /// Mean frame rate over the whole stream.
+    average_fps: Option<f64>,
+    frame_interval_sec: Option<f64>,
+    min_frame_interval_sec: Option<f64>,
+    max_frame_interval_sec: Option<f64>,
+    variable_frame_rate: bool,
+    pts: Option<Vec<f64>>,
+}
+
+impl DeepProbe {
+    fn new(file: &str, timeline: &FrameTimeline, include_timestamps: bool) -> Self {
+        let span = match (timeline.pts.first(), timeline.pts.last()) {
+            (Some(first), Some(last)) if timeline.pts.len() > 1 => Some(last - first),
+            _ => None,
+        };
+        Self {
+            file: file.to_string(),
+            stream_index: timeline.stream_index,
+            frame_count: timeline.frame_count(),
+            keyframes: timeline.keyframes.clone(),
+            average_fps: span
+                .filter(|span| *span > 0.0)
+                .map(|span| (timeline.pts.len() - 1) as f64 / span),
+            frame_interval_sec: timeline.frame_interval_sec,
+            min_frame_interval_sec: timeline.min_frame_interval_sec,
+            max_frame_interval_sec: timeline.max_frame_interval_sec,
+            variable_frame_rate: timeline.variable_frame_rate,
+            pts: include_timestamps.then(|| timeline.pts.clone()),
+        }
+    }
+}
+
+/// Value of `key=` in a `-of compact` line such as `packet|pts_time=0.041|flags=K__`.
+pub(crate) fn compact_field<'a>(line: &'a str, key: &str) -> Option<&'a str> {
+    line.split('|')
+        .find_map(|field| field.strip_prefix(key)?.strip_prefix('='))
+}
+
+fn parse_seconds(value: Option<&str>) -> Option<f64> {
+    value
+        .and_then(|value| value.parse::<f64>().ok())
+        .filter(|value| value.is_finite())
+}
+
+fn median(sorted: &[f64]) -> Option<f64> {
+    match sorted.len() {
+        0 => None,
+        len if len % 2 == 1 => Some(sorted[len / 2]),
+        len => Some((sorted[len / 2 - 1] + sorted[len / 2]) / 2.0),
+    }
+}
+
+/// Builds the timeline from ffprobe's compact packet or frame listing.
+/// Packets come in decode order; B-frames make that differ from display order.
+fn parse_timeline(output: &str, stream_index: u32) -> AppResult<FrameTimeline> {
+    let mut start_time = 0.0;
+    let mut frames: Vec<(f64, bool)> = Vec::new();
+    for line in output.lines() {
+        if line.starts_with("format|") {
+            start_time = parse_seconds(compact_field(line, "start_time")).unwrap_or(0.0);
+        } else if line.starts_with("frame|") {
+            let Some(pts) = parse_seconds(compact_field(line, "pts_time"))
+                .or_else(|| parse_seconds(compact_field(line, "best_effort_timestamp_time")))
+            else {
+                continue;
+            };
+            frames.push((pts, compact_field(line, "key_frame") == Some("1")));
+        } else if line.starts_with("packet|") {
+            let flags = compact_field(line, "flags").unwrap_or_default();
+            // `D` marks packets decoded only to prime the decoder, e.g. before
+            // an MP4 edit list starts; they are never shown.
+            if flags.contains('D') {
+                continue;
+            }
+            let Some(pts) = parse_seconds(compact_field(line, "pts_time"))
+                .or_else(|| parse_seconds(compact_field(line, "dts_time")))
+            else {
+                continue;
+            };
+            frames.push((pts, flags.contains('K')));
+        }
+    }
+    if frames.is_empty() {
+        return Err(AppError::probe(
+            "ffprobe listed no timestamped video frames.",
+        ));
+    }
+    frames.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let pts: Vec<f64> = frames.iter().map(|(pts, _)| pts - start_time).collect();
+    let keyframes = frames
+        .iter()
+        .enumerate()
+        .filter(|(_, (_, keyframe))| *keyframe)
+        .map(|(index, _)| index as u64)
+        .collect();
+    let mut intervals: Vec<f64> = pts
+        .windows(2)
+        .map(|pair| pair[1] - pair[0])
+        .filter(|interval| *interval > 0.0)
+        .collect();
+    intervals.sort_by(f64::total_cmp);
+    let interval = median(&intervals);
+    let min = intervals.first().copied();
+    let max = intervals.last().copied();
+    let variable_frame_rate = match (interval, min, max) {
+        (Some(interval), Some(min), Some(max)) => {
+            (interval - min) > interval * VFR_TOLERANCE
+                || (max - interval) > interval * VFR_TOLERANCE
+        }
+        _ => false,
+    };
+    Ok(FrameTimeline {
+        stream_index,
+        pts,
+        keyframes,
+        frame_interval_sec: interval,
+        min_frame_interval_sec: min,
+        max_frame_interval_sec: max,
+        variable_frame_rate,
+    })
+}
+
+fn list_entries(
+    ffprobe: &Path,
+    info: &VideoInfo,
+    stream_index: u32,
+    entries: &str,
+) -> AppResult<String> {
+    let output = tool_command(ffprobe)
+        .args([
+            "-v",
+            "error",
+            "-select_streams",
+            &stream_index.to_string(),
+            "-show_entries",
+            entries,
+            "-of",
+            "compact",
+            &info.file,
+        ])
+        .output()
+        .map_err(|e| AppError::spawn(format!("Failed to run ffprobe: {}", e)))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+        return Err(AppError::probe(format!("ffprobe error:\n{}", stderr)));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Whether packets come twice as often as the probed frame rate, i.e. each
+/// field of interlaced video is a packet of its own.
+fn is_field_coded(timeline: &FrameTimeline, info: &VideoInfo) -> bool {
+    let fps = info.video.as_ref().and_then(|video| video.fps);
+    match (timeline.frame_interval_sec, fps) {
+        (Some(interval), Some(fps)) if fps > 0.0 => interval < 0.75 / fps,
+        _ => false,
+    }
+}
+
+/// Lists every packet of the main video stream of `info`, or every decoded
+/// frame when the stream is field-coded.
+pub(crate) fn read_timeline(ffprobe: &Path, info: &VideoInfo) -> AppResult<FrameTimeline> {
+    let stream_index = info
+        .video
+        .as_ref()
+        .map(|video| video.index)
+        .ok_or_else(|| AppError::invalid(format!("{} has no video stream.", info.file)))?;
+    let packets = list_entries(
+        ffprobe,
+        info,
+        stream_index,
+        "packet=pts_time,dts_time,flags:format=start_time",
+    )?;
+    let timeline = parse_timeline(&packets, stream_index)?;
+    if !is_field_coded(&timeline, info) {
+        return Ok(timeline);
+    }
+    let frames = list_entries(
+        ffprobe,
+        info,
+        stream_index,
+        "frame=pts_time,best_effort_timestamp_time,key_frame:format=start_time",
+    )?;
+    parse_timeline(&frames, stream_index)
+}
+
+/// Reads the exact frame count, timestamps and keyframes of a file. This reads
+/// the whole file, so it is opt-in and runs off the main thread; the result is
+/// kept in the probe cache until the file changes.
+#[tauri::command(async)]
+pub(crate) fn deep_probe(
+    app: AppHandle,
+    cache: State<'_, ProbeCache>,
+    path: String,
+    include_timestamps: Option<bool>,
+) -> AppResult<DeepProbe> {
+    let include_timestamps = include_timestamps.unwrap_or(false);
+    if let Some(timeline) = cache.timeline(&path) {
+        return Ok(DeepProbe::new(&path, &timeline, include_timestamps));
+    }
+    let ffprobe = resolve_bundled_binary(&app, "ffprobe")?;
+    let info = cache.probe(&ffprobe, &path)?;
+    let timeline = Arc::new(read_timeline(&ffprobe, &info)?);
+    cache.store_timeline(&path, timeline.clone());
+    Ok(DeepProbe::new(&path, &timeline, include_timestamps))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn info(fps: f64) -> VideoInfo {
+        serde_json::from_value(json!({
+            "file": "a.ts",
+            "container": {},
+            "video": { "index": 0, "fps": fps },
+            "subtitles": [],
+            "attachments": [],
+            "streams": [],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn packets_are_sorted_into_display_order() {
+        let output = "\
+packet|pts_time=1.000000|dts_time=0.960000|flags=K__
+packet|pts_time=1.080000|dts_time=1.000000|flags=___
+packet|pts_time=1.040000|dts_time=1.040000|flags=___
+packet|pts_time=0.960000|dts_time=0.920000|flags=__D
+format|start_time=1.000000
+";
+        let timeline = parse_timeline(output, 0).unwrap();
+        let pts: Vec<String> = timeline.pts.iter().map(|pts| format!("{pts:.2}")).collect();
+        assert_eq!(pts, ["0.00", "0.04", "0.08"]);
+        assert_eq!(timeline.keyframes, [0]);
+        assert_eq!(
+            timeline
+                .frame_interval_sec
+                .map(|i| format!("{i:.2}"))
+                .as_deref(),
+            Some("0.04")
+        );
+        assert!(!timeline.variable_frame_rate);
+        assert!(!is_field_coded(&timeline, &info(25.0)));
+    }
+
+    #[test]
+    fn field_packets_are_detected() {
+        let output = "\
+packet|pts_time=0.000000|dts_time=0.000000|flags=K__
+packet|pts_time=N/A|dts_time=0.020000|flags=___
+packet|pts_time=0.040000|dts_time=0.040000|flags=___
+packet|pts_time=N/A|dts_time=0.060000|flags=___
+format|start_time=0.000000
+";
+        let timeline = parse_timeline(output, 0).unwrap();
+        assert_eq!(timeline.frame_count(), 4);
+        assert!(is_field_coded(&timeline, &info(25.0)));
+    }
+
+    #[test]
+    fn decoded_frames_are_read() {
+        let output = "\
+frame|key_frame=1|pts_time=0.000000|best_effort_timestamp_time=0.000000
+frame|key_frame=0|pts_time=N/A|best_effort_timestamp_time=0.040000
+frame|key_frame=0|pts_time=0.080000|best_effort_timestamp_time=0.080000
+format|start_time=0.000000
+";
+        let timeline = parse_timeline(output, 0).unwrap();
+        assert_eq!(timeline.frame_count(), 3);
+        assert_eq!(timeline.keyframes, [0]);
+        assert!(!is_field_coded(&timeline, &info(25.0)));
+        assert!(parse_timeline("format|start_time=0.0\n", 0).is_err());
+    }
+}
@@ -0,0 +1,311 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{AppError, AppResult};
+use crate::ffmpeg::FilterChain;
+use crate::layout::input_letter;
+use crate::probe::{SubtitleStreamInfo, VideoInfo};
+use crate::{escape_filter_value, ExportParams};
+
+/// Picks a stream by its absolute index from `probe_video` (a number) or by its
+/// language tag (a string such as `"eng"`).
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+pub(crate) enum TrackSelector {
+    Index(u32),
+    Language(String),
+}
+
+impl TrackSelector {
+    fn matches_subtitle(&self, subtitle: &SubtitleStreamInfo) -> bool {
+        match self {
+            TrackSelector::Index(index) => subtitle.index == *index,
+            TrackSelector::Language(language) => {
+                subtitle.language.as_deref() == Some(language.trim())
+            }
+        }
+    }
+}
+
+/// A subtitle stream rendered into the video of one input.
+pub(crate) struct BurnIn {
+    pub(crate) input: usize,
+    /// Position among the input's subtitle streams, as used by `si=` and `s:N`.
+    subtitle_index: usize,
+    bitmap: bool,
+    path: String,
+}
+
+impl BurnIn {
+    /// Image subtitles are overlaid in a separate chain; its output replaces
+    /// `{input}:v` as the input's video pad.
+    pub(crate) fn overlay_chain(&self) -> Option<(FilterChain, String)> {
+        if !self.bitmap {
+            return None;
+        }
+        let pad = format!("burn{}", self.input);
+        let chain = FilterChain::new()
+            .input(format!("{}:v", self.input))
+            .input(format!("{}:s:{}", self.input, self.subtitle_index))
+            .filter("overlay=eof_action=pass")
+            .output(pad.clone());
+        Some((chain, pad))
+    }
+
+    /// Filters that render text subtitles. The `subtitles` filter reads the file
+    /// from the start, so trimmed inputs are shifted back to source time while
+    /// rendering.
+    pub(crate) fn filters(&self, trim_start_sec: Option<f64>) -> Vec<String> {
+        if self.bitmap {
+            return Vec::new();
+        }
+        let path = escape_filter_value(&self.path.replace('\\', "/"));
+        let render = format!("subtitles=filename='{path}':si={}", self.subtitle_index);
+        match trim_start_sec.filter(|start| *start > 0.0) {
+            Some(start) => vec![
+                format!("setpts=PTS+{start:.6}/TB"),
+                render,
+                "setpts=PTS-STARTPTS".to_string(),
+            ],
+            None => vec![render],
+        }
+    }
+}
+
+/// Which audio and subtitle streams an export carries and how.
+pub(crate) struct TrackPlan {
+    /// Input (by position among the export's inputs) the tracks are taken from.
+    pub(crate) source: usize,
+    pub(crate) audio_maps: Vec<String>,
+    pub(crate) subtitle_maps: Vec<String>,
+    pub(crate) subtitle_codec: Option<&'static str>,
+    pub(crate) burn_in: Option<BurnIn>,
+}
+
+impl TrackPlan {
+    /// Whether ffmpeg's default stream selection has to be replaced by `-map`s.
+    pub(crate) fn needs_explicit_maps(&self) -> bool {
+        !self.audio_maps.is_empty() || !self.subtitle_maps.is_empty() || self.burn_in.is_some()
+    }
+}
+
+fn subtitle_codec_for(output_path: &str) -> &'static str {
+    let extension = Path::new(output_path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("mp4" | "m4v" | "mov") => "mov_text",
+        Some("webm") => "webvtt",
+        _ => "copy",
+    }
+}
+
+/// Resolves the track selection in `params` against the export's inputs.
+/// `paths` and `infos` are indexed like the inputs; probe results are needed
+/// to burn in subtitles and are used to validate index selectors when present.
+pub(crate) fn plan_tracks(
+    params: &ExportParams,
+    paths: &[&str],
+    infos: &[Option<&VideoInfo>],
+    output_path: &str,
+) -> AppResult<TrackPlan> {
+    // Single-input exports always take tracks from their only input.
+    let source = if paths.len() == 1 {
+        0
+    } else {
+        params.track_source.unwrap_or(0)
+    };
+    if source >= paths.len() {
+        return Err(AppError::invalid(format!(
+            "Input {} is not part of this export.",
+            input_letter(source)
+        )));
+    }
+    let letter = input_letter(source);
+    let info = infos.get(source).copied().flatten();
+
+    let stream_type = |index: u32| {
+        info.and_then(|info| info.streams.iter().find(|stream| stream.index == index))
+            .map(|stream| stream.codec_type.as_str())
+    };
+    // `kind` is ffprobe's codec_type, `specifier` the matching ffmpeg stream type.
+    let map_for =
+        |selector: &TrackSelector, kind: &str, specifier: char, name: &str| -> AppResult<String> {
+            match selector {
+                TrackSelector::Index(index) => {
+                    if info.is_some() && stream_type(*index) != Some(kind) {
+                        return Err(AppError::invalid(format!(
+                            "Stream #{index} of input {letter} is not {name} stream."
+                        )));
+                    }
+                    Ok(format!("{source}:{index}"))
+                }
+                TrackSelector::Language(language) => {
+                    let language = language.trim();
+                    let Some(info) = info else {
+                        // Unprobed: let ffmpeg skip the map instead of failing.
+                        return Ok(format!("{source}:{specifier}:m:language:{language}?"));
+                    };
+                    let found = info.streams.iter().any(|stream| {
+                        stream.codec_type == kind && stream.language.as_deref() == Some(language)
+                    });
+                    if !found {
+                        return Err(AppError::invalid(format!(
+                            "Input {letter} has no {kind} stream in language {language}."
+                        )));
+                    }
+                    Ok(format!("{source}:{specifier}:m:language:{language}"))
+                }
+            }
+        };
+
+    let audio_maps = params
+        .audio_streams
+        .iter()
+        .map(|selector| map_for(selector, "audio", 'a', "an audio"))
+        .collect::<AppResult<Vec<_>>>()?;
+
+    let mut plan = TrackPlan {
+        source,
+        audio_maps,
+        subtitle_maps: Vec::new(),
+        subtitle_codec: None,
+        burn_in: None,
+    };
+    if params.subtitle_streams.is_empty() {
+        return Ok(plan);
+    }
+
+    if !params.burn_subtitles {
+        plan.subtitle_maps = params
+            .subtitle_streams
+            .iter()
+            .map(|selector| map_for(selector, "subtitle", 's', "a subtitle"))
+            .collect::<AppResult<Vec<_>>>()?;
+        let codec = subtitle_codec_for(output_path);
+        if codec != "copy" {
+            let has_bitmap = info.is_some_and(|info| {
+                info.subtitles.iter().any(|subtitle| {
+                    subtitle.bitmap
+                        && params
+                            .subtitle_streams
+                            .iter()
+                            .any(|selector| selector.matches_subtitle(subtitle))
+                })
+            });
+            if has_bitmap {
+                return Err(AppError::invalid(
+                    "Image-based subtitles cannot be muxed into this container; burn them in or export to mkv.",
+                ));
+            }
+        }
+        plan.subtitle_codec = Some(codec);
+        return Ok(plan);
+    }
+
+    let [selector] = params.subtitle_streams.as_slice() else {
+        return Err(AppError::invalid(
+            "Only one subtitle stream can be burned in.",
+        ));
+    };
+    let info = info.ok_or_else(|| {
+        AppError::invalid(format!(
+            "Unable to burn in subtitles: input {letter} could not be probed."
+        ))
+    })?;
+    let found = info
+        .subtitles
+        .iter()
+        .enumerate()
+        .find(|(_, subtitle)| selector.matches_subtitle(subtitle));
+    let Some((subtitle_index, subtitle)) = found else {
+        return Err(AppError::invalid(format!(
+            "Input {letter} has no matching subtitle stream."
+        )));
+    };
+    plan.burn_in = Some(BurnIn {
+        input: source,
+        subtitle_index,
+        bitmap: subtitle.bitmap,
+        path: paths[source].to_string(),
+    });
+    Ok(plan)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn params(audio_streams: serde_json::Value) -> ExportParams {
+        serde_json::from_value(json!({
+            "exportMode": "input-a",
+            "outputPath": "out.mkv",
+            "codec": "h264",
+            "crf": 23,
+            "keepAspect": false,
+            "audioCopy": false,
+            "audioStreams": audio_streams,
+        }))
+        .unwrap()
+    }
+
+    /// Video, English audio and German audio.
+    fn info() -> VideoInfo {
+        let stream = |index: u32, kind: &str, language: Option<&str>| {
+            json!({
+                "index": index,
+                "codec_type": kind,
+                "language": language,
+                "disposition": [],
+            })
+        };
+        serde_json::from_value(json!({
+            "file": "a.mkv",
+            "container": {},
+            "subtitles": [],
+            "attachments": [],
+            "streams": [
+                stream(0, "video", None),
+                stream(1, "audio", Some("eng")),
+                stream(2, "audio", Some("ger")),
+            ],
+        }))
+        .unwrap()
+    }
+
+    fn audio_maps(
+        audio_streams: serde_json::Value,
+        info: Option<&VideoInfo>,
+    ) -> AppResult<Vec<String>> {
+        plan_tracks(&params(audio_streams), &["a.mkv"], &[info], "out.mkv")
+            .map(|plan| plan.audio_maps)
+    }
+
+    #[test]
+    fn language_selectors_resolve_against_the_probe() {
+        let info = info();
+        assert_eq!(
+            audio_maps(json!(["ger", 1]), Some(&info)).unwrap(),
+            ["0:a:m:language:ger", "0:1"]
+        );
+        assert!(matches!(
+            audio_maps(json!(["fra"]), Some(&info)),
+            Err(AppError::InvalidParams { .. })
+        ));
+        assert!(matches!(
+            audio_maps(json!([0]), Some(&info)),
+            Err(AppError::InvalidParams { .. })
+        ));
+    }
+
+    #[test]
+    fn unprobed_language_selectors_are_optional() {
+        assert_eq!(
+            audio_maps(json!(["fra"]), None).unwrap(),
+            ["0:a:m:language:fra?"]
+        );
+    }
+}
@@ -3,6 +3,8 @@
 const state = {
   infoA: null,
   infoB: null,
+  // Backend diff of infoA and infoB by field id, see diff_video_info.
+  diffs: {},
   exportId: null,
   exportOutput: null,
 };
@@ -22,6 +24,10 @@ const elements = {
   container: document.querySelector('[data-field="container"]'),
   codec: document.querySelector('[data-field="codec"]'),
   crf: document.querySelector('[data-field="crf"]'),
+  preset: document.querySelector('[data-field="preset"]'),
+  rateControl: document.querySelector('[data-field="rate-control"]'),
+  bitrate: document.querySelector('[data-field="bitrate"]'),
+  twoPass: document.querySelector('[data-field="two-pass"]'),
   resizeWidth: document.querySelector('[data-field="resize-width"]'),
   resizeHeight: document.querySelector('[data-field="resize-height"]'),
   keepAspect: document.querySelector('[data-field="keep-aspect"]'),
@@ -31,6 +37,9 @@ const elements = {
   labelA: document.querySelector('[data-field="label-a"]'),
   labelB: document.querySelector('[data-field="label-b"]'),
   audioCopy: document.querySelector('[data-field="audio-copy"]'),
+  diffGain: document.querySelector('[data-field="diff-gain"]'),
+  diffHeatmapPreset: document.querySelector('[data-field="diff-heatmap-preset"]'),
+  diffThreshold: document.querySelector('[data-field="diff-threshold"]'),
   progress: document.querySelector('progress'),
   progressText: document.querySelector('[data-field="progress-text"]'),
   status: document.querySelector('[data-field="status"]'),
@@ -43,6 +52,12 @@ function setStatus(message, isError = true) {
   elements.status.style.color = isError ? '#f87171' : '#38bdf8';
 }
 
+// Backend errors are `{ kind, message }` objects; anything else is stringified.
+function errorMessage(error) {
+  if (error && typeof error === 'object' && 'message' in error) return error.message;
+  return String(error);
+}
+
 function toVideoSrc(path) {
   if (!path) return '';
   if (tauri?.core?.convertFileSrc) {
@@ -158,6 +173,54 @@ function formatBytes(value) {
   return `${size.toFixed(2)} ${units[unitIndex]}`;
 }
 
+function formatHdr(video) {
+  const hdr = video?.hdr;
+  if (!hdr) return video ? 'SDR' : '-';
+  const parts = [hdr.format];
+  const display = hdr.mastering_display;
+  if (display) parts.push(`${display.max_luminance.toFixed(0)}/${display.min_luminance} nits`);
+  if (hdr.max_cll) parts.push(`MaxCLL ${hdr.max_cll}, MaxFALL ${hdr.max_fall ?? '-'}`);
+  if (hdr.dolby_vision?.profile !== undefined && hdr.dolby_vision?.profile !== null) {
+    parts.push(`DV profile ${hdr.dolby_vision.profile}`);
+  }
+  return parts.join(' · ');
+}
+
+function escapeHtml(value) {
+  return String(value)
+    .replace(/&/g, '&amp;')
+    .replace(/</g, '&lt;')
+    .replace(/>/g, '&gt;')
+    .replace(/"/g, '&quot;');
+}
+
+// Tags come from the file itself, so they are escaped before reaching innerHTML.
+function formatStream(stream) {
+  if (!stream) return '-';
+  const parts = [stream.codec_type, stream.codec_name ?? '?'];
+  if (stream.width && stream.height) parts.push(`${stream.width}x${stream.height}`);
+  if (stream.fps) parts.push(`${stream.fps.toFixed(3)} fps`);
+  if (stream.channels) parts.push(`${stream.channel_layout ?? `${stream.channels}ch`}`);
+  if (stream.sample_rate) parts.push(`${stream.sample_rate} Hz`);
+  if (stream.timecode) parts.push(stream.timecode);
+  if (stream.language) parts.push(stream.language);
+  if (stream.title) parts.push(`"${stream.title}"`);
+  if (stream.disposition?.length) parts.push(`[${stream.disposition.join(', ')}]`);
+  return escapeHtml(parts.join(' '));
+}
+
+// One row per stream index so added, missing or reordered tracks show up as diffs.
+function streamRows(infoA, infoB) {
+  const streamsA = infoA?.streams ?? [];
+  const streamsB = infoB?.streams ?? [];
+  const count = Math.max(streamsA.length, streamsB.length);
+  const rows = [['Streams', streamsA.length || '-', streamsB.length || '-', 'streams.count']];
+  for (let index = 0; index < count; index += 1) {
+    rows.push([`Stream #${index}`, formatStream(streamsA[index]), formatStream(streamsB[index])]);
+  }
+  return rows;
+}
+
 function setPreviewAspect(target, info) {
   if (target === 'a' && elements.previewFrameA) {
     elements.previewFrameA.dataset.hasInfo = info ? 'true' : 'false';
@@ -167,6 +230,39 @@ function setPreviewAspect(target, info) {
   }
 }
 
+// Rows with a field id follow the backend diff, which knows tolerances and
+// severities; the rest fall back to comparing the displayed text.
+function diffCell(field, a, b) {
+  const bothLoaded = state.infoA && state.infoB;
+  if (field && bothLoaded) {
+    const diff = state.diffs[field];
+    if (!diff) return { className: 'same', title: '' };
+    const delta =
+      diff.delta_percent === null || diff.delta_percent === undefined
+        ? ''
+        : ` (${diff.delta_percent > 0 ? '+' : ''}${diff.delta_percent.toFixed(1)}%)`;
+    return {
+      className: `diff ${diff.severity}`,
+      title: ` title="${escapeHtml(`${diff.severity}${delta}`)}"`,
+    };
+  }
+  const same = String(a ?? '') === String(b ?? '');
+  return { className: same ? 'same' : 'diff', title: '' };
+}
+
+async function refreshDiffs() {
+  state.diffs = {};
+  if (state.infoA && state.infoB && tauri?.core) {
+    try {
+      const diffs = await tauri.core.invoke('diff_video_info', { a: state.infoA, b: state.infoB });
+      state.diffs = Object.fromEntries(diffs.map((diff) => [diff.field, diff]));
+    } catch (error) {
+      setStatus(errorMessage(error));
+    }
+  }
+  renderCompare();
+}
+
 function renderCompare() {
   if (!state.infoA && !state.infoB) {
     elements.compareGrid.innerHTML = '<div class="placeholder">Load Input A and B to compare.</div>';
@@ -178,29 +274,36 @@ function renderCompare() {
 
   const rows = [
     ['File', fileNameFromPath(infoA?.file), fileNameFromPath(infoB?.file)],
-    ['Size', formatBytes(infoA?.size_bytes), formatBytes(infoB?.size_bytes)],
-    ['Container', formatMaybe(infoA?.container?.format_name), formatMaybe(infoB?.container?.format_name)],
-    ['Duration', formatDuration(infoA?.container?.duration_sec), formatDuration(infoB?.container?.duration_sec)],
-    ['Bitrate', formatBitrate(infoA?.container?.bitrate), formatBitrate(infoB?.container?.bitrate)],
-    ['Video Codec', formatMaybe(infoA?.video?.codec_name), formatMaybe(infoB?.video?.codec_name)],
-    ['Profile', formatMaybe(infoA?.video?.profile), formatMaybe(infoB?.video?.profile)],
-    ['Resolution', formatResolution(infoA?.video), formatResolution(infoB?.video)],
-    ['Pixel Format', formatMaybe(infoA?.video?.pix_fmt), formatMaybe(infoB?.video?.pix_fmt)],
-    ['Color Space', formatMaybe(infoA?.video?.color_space), formatMaybe(infoB?.video?.color_space)],
+    ['Size', formatBytes(infoA?.size_bytes), formatBytes(infoB?.size_bytes), 'size'],
+    ['Container', formatMaybe(infoA?.container?.format_name), formatMaybe(infoB?.container?.format_name), 'container.format'],
+    ['Duration', formatDuration(infoA?.container?.duration_sec), formatDuration(infoB?.container?.duration_sec), 'container.duration'],
+    ['Bitrate', formatBitrate(infoA?.container?.bitrate), formatBitrate(infoB?.container?.bitrate), 'container.bitrate'],
+    ['Video Codec', formatMaybe(infoA?.video?.codec_name), formatMaybe(infoB?.video?.codec_name), 'video.codec'],
+    ['Profile', formatMaybe(infoA?.video?.profile), formatMaybe(infoB?.video?.profile), 'video.profile'],
+    ['Resolution', formatResolution(infoA?.video), formatResolution(infoB?.video), 'video.resolution'],
+    ['Pixel Format', formatMaybe(infoA?.video?.pix_fmt), formatMaybe(infoB?.video?.pix_fmt), 'video.pix_fmt'],
+    ['Color Space', formatMaybe(infoA?.video?.color_space), formatMaybe(infoB?.video?.color_space), 'video.color_space'],
+    ['Transfer', formatMaybe(infoA?.video?.color_transfer), formatMaybe(infoB?.video?.color_transfer), 'video.color_transfer'],
+    ['HDR', formatHdr(infoA?.video), formatHdr(infoB?.video), 'video.hdr'],
     [
       'Frame Rate',
       infoA?.video?.fps ? `${infoA.video.fps.toFixed(3)} fps` : '-',
       infoB?.video?.fps ? `${infoB.video.fps.toFixed(3)} fps` : '-',
+      'video.fps',
     ],
-    ['Frame Count', formatMaybe(infoA?.video?.frame_count), formatMaybe(infoB?.video?.frame_count)],
-    ['Audio Codec', formatMaybe(infoA?.audio?.codec_name), formatMaybe(infoB?.audio?.codec_name)],
-    ['Channels', formatMaybe(infoA?.audio?.channels), formatMaybe(infoB?.audio?.channels)],
+    ['Frame Count', formatMaybe(infoA?.video?.frame_count), formatMaybe(infoB?.video?.frame_count), 'video.frame_count'],
+    ['Audio Codec', formatMaybe(infoA?.audio?.codec_name), formatMaybe(infoB?.audio?.codec_name), 'audio.codec'],
+    ['Channels', formatMaybe(infoA?.audio?.channels), formatMaybe(infoB?.audio?.channels), 'audio.channels'],
     [
       'Sample Rate',
       infoA?.audio?.sample_rate ? `${infoA.audio.sample_rate} Hz` : '-',
       infoB?.audio?.sample_rate ? `${infoB.audio.sample_rate} Hz` : '-',
+      'audio.sample_rate',
     ],
-    ['Audio Bitrate', formatBitrate(infoA?.audio?.bit_rate), formatBitrate(infoB?.audio?.bit_rate)],
+    ['Audio Bitrate', formatBitrate(infoA?.audio?.bit_rate), formatBitrate(infoB?.audio?.bit_rate), 'audio.bitrate'],
+    ['Subtitles', infoA?.subtitles?.length ?? '-', infoB?.subtitles?.length ?? '-', 'subtitles.count'],
+    ['Attachments', infoA?.attachments?.length ?? '-', infoB?.attachments?.length ?? '-', 'attachments.count'],
+    ...streamRows(infoA, infoB),
   ];
 
   elements.compareGrid.innerHTML = `
@@ -217,13 +320,13 @@ function renderCompare() {
           <td>Input B</td>
         </tr>
         ${rows
-          .map(([label, a, b]) => {
-            const same = String(a ?? '') === String(b ?? '');
+          .map(([label, a, b, field]) => {
+            const { className, title } = diffCell(field, a, b);
             return `
               <tr>
                 <td>${label}</td>
-                <td class="${same ? 'same' : 'diff'}">${a ?? '-'}</td>
-                <td class="${same ? 'same' : 'diff'}">${b ?? '-'}</td>
+                <td class="${className}"${title}>${a ?? '-'}</td>
+                <td class="${className}"${title}>${b ?? '-'}</td>
               </tr>
             `;
           })
@@ -300,8 +403,12 @@ function updateLinkedResize(changed) {
   }
 }
 
+function isCompositeMode(mode) {
+  return mode !== 'input-a' && mode !== 'input-b';
+}
+
 function updateExportModeUI() {
-  const isSideBySide = elements.exportMode.value === 'side-by-side';
+  const isSideBySide = isCompositeMode(elements.exportMode.value);
   const controls = [
     elements.resizeWidth,
     elements.resizeHeight,
@@ -316,6 +423,10 @@ function updateExportModeUI() {
   }
   if (elements.labelA) elements.labelA.disabled = !isSideBySide;
   if (elements.labelB) elements.labelB.disabled = !isSideBySide;
+  const isDifference = elements.exportMode.value === 'difference';
+  for (const control of [elements.diffGain, elements.diffHeatmapPreset, elements.diffThreshold]) {
+    if (control) control.disabled = !isDifference;
+  }
   if (isSideBySide) {
     elements.resizeWidth.value = '';
     elements.resizeHeight.value = '';
@@ -345,9 +456,9 @@ async function probe(target) {
     }
     setPreviewAspect(target, info);
     applyExportDefaultsFromInfo(info, target);
-    renderCompare();
+    await refreshDiffs();
   } catch (error) {
-    setStatus(String(error));
+    setStatus(errorMessage(error));
   }
 }
 
@@ -372,6 +483,107 @@ async function browseOutput() {
   }
 }
 
+// Reads every packet of the loaded inputs for exact frame counts. Later
+// probes and trimmed exports of those files use the real timestamps.
+async function countFrames() {
+  if (!tauri?.core) {
+    setStatus('Tauri invoke API not available.');
+    return;
+  }
+  const loaded = [
+    ['a', state.infoA],
+    ['b', state.infoB],
+  ].filter(([, info]) => info);
+  if (!loaded.length) {
+    setStatus('Load an input before counting frames.');
+    return;
+  }
+  setStatus('Counting frames...', false);
+  try {
+    const notes = [];
+    for (const [target, info] of loaded) {
+      const result = await tauri.core.invoke('deep_probe', { path: info.file });
+      const refreshed = await tauri.core.invoke('probe_video', { path: info.file });
+      if (target === 'a') {
+        state.infoA = refreshed;
+      } else {
+        state.infoB = refreshed;
+      }
+      const mode = result.variable_frame_rate ? 'variable frame rate' : 'constant frame rate';
+      notes.push(`${target.toUpperCase()}: ${result.frame_count} frames, ${mode}`);
+    }
+    await refreshDiffs();
+    setStatus(notes.join(' · '), false);
+  } catch (error) {
+    setStatus(errorMessage(error));
+  }
+}
+
+// Drops stored probes and frame timelines so every file is read again.
+async function clearCache() {
+  if (!tauri?.core) {
+    setStatus('Tauri invoke API not available.');
+    return;
+  }
+  try {
+    const removed = await tauri.core.invoke('clear_probe_cache');
+    const size = (removed.size_bytes / (1024 * 1024)).toFixed(1);
+    setStatus(`Cleared ${removed.entries} cached entries (${size} MB).`, false);
+  } catch (error) {
+    setStatus(errorMessage(error));
+  }
+}
+
+// Saves the frames of A and B at Input A's current preview position.
+async function saveFrames() {
+  if (!tauri?.dialog || !tauri?.core) {
+    setStatus('Tauri APIs not available.');
+    return;
+  }
+  if (!state.infoA || !state.infoB) {
+    setStatus('Load Input A and B before saving frames.');
+    return;
+  }
+  const timeSec = elements.previewA?.currentTime ?? 0;
+  const outputDir = await tauri.dialog.open({ directory: true, multiple: false });
+  if (typeof outputDir !== 'string') return;
+  try {
+    const pair = await tauri.core.invoke('extract_frame_pair', {
+      params: { inputPathA: state.infoA.file, inputPathB: state.infoB.file, timeSec, outputDir },
+    });
+    setStatus(`Frames saved: ${fileNameFromPath(pair.a.path)}, ${fileNameFromPath(pair.b.path)}`, false);
+  } catch (error) {
+    setStatus(errorMessage(error));
+  }
+}
+
+// Writes HTML, JSON and Markdown reports next to the chosen path.
+async function saveReport() {
+  if (!tauri?.dialog || !tauri?.core) {
+    setStatus('Tauri APIs not available.');
+    return;
+  }
+  if (!state.infoA || !state.infoB) {
+    setStatus('Load Input A and B before saving a report.');
+    return;
+  }
+  const path = await tauri.dialog.save({
+    defaultPath: 'comparison-report.html',
+    filters: [{ name: 'Report', extensions: ['html'] }],
+  });
+  if (typeof path !== 'string') return;
+  setStatus('Writing report...', false);
+  try {
+    const result = await tauri.core.invoke('export_report', {
+      params: { inputPathA: state.infoA.file, inputPathB: state.infoB.file, outputPath: path },
+    });
+    const warnings = result.warnings.length ? ` (${result.warnings.length} frame(s) missing)` : '';
+    setStatus(`Report saved: ${result.files.map(fileNameFromPath).join(', ')}${warnings}`, false);
+  } catch (error) {
+    setStatus(errorMessage(error));
+  }
+}
+
 function numberValue(input) {
   const value = input.value.trim();
   if (!value) return null;
@@ -382,8 +594,8 @@ function numberValue(input) {
 function validateExport(inputPathA, inputPathB, outputPath, mode) {
   if (mode === 'input-a' && !inputPathA) return 'Select an Input A file.';
   if (mode === 'input-b' && !inputPathB) return 'Select an Input B file.';
-  if (mode === 'side-by-side' && (!inputPathA || !inputPathB)) {
-    return 'Side-by-side export needs both Input A and Input B.';
+  if (isCompositeMode(mode) && (!inputPathA || !inputPathB)) {
+    return 'This layout needs both Input A and Input B.';
   }
   if (!outputPath) return 'Choose an output file.';
   if (inputPathA === outputPath || inputPathB === outputPath) return 'Output must be different from input.';
@@ -402,6 +614,21 @@ function computeStackHeight() {
   return evenize(Math.min(heightA, heightB));
 }
 
+function computeStackWidth() {
+  const widthA = state.infoA?.video?.width ?? null;
+  const widthB = state.infoB?.video?.width ?? null;
+  if (!widthA || !widthB) return null;
+  return evenize(Math.min(widthA, widthB));
+}
+
+// Tile size per layout: hstack matches heights, vstack matches widths and the
+// overlapping layouts need both.
+function computeStackSize(mode) {
+  const stackHeight = mode === 'vertical-stack' ? null : computeStackHeight();
+  const stackWidth = mode === 'side-by-side' ? null : computeStackWidth();
+  return { stackWidth, stackHeight };
+}
+
 async function startExport() {
   if (!tauri?.core) {
     setStatus('Tauri invoke API not available.');
@@ -418,16 +645,18 @@ async function startExport() {
     return;
   }
 
-  const isSideBySide = exportMode === 'side-by-side';
+  const isSideBySide = isCompositeMode(exportMode);
 
-  const stackHeight = isSideBySide ? computeStackHeight() : null;
+  const { stackWidth, stackHeight } = isSideBySide
+    ? computeStackSize(exportMode)
+    : { stackWidth: null, stackHeight: null };
   if (isSideBySide) {
     if (!state.infoA || !state.infoB) {
-      setStatus('Load both inputs before exporting side-by-side.');
+      setStatus('Load both inputs before exporting a combined layout.');
       return;
     }
-    if (!stackHeight) {
-      setStatus('Unable to determine matching heights for side-by-side export.');
+    if (!stackWidth && !stackHeight) {
+      setStatus('Unable to determine matching sizes for this layout.');
       return;
     }
   }
@@ -439,6 +668,11 @@ async function startExport() {
     outputPath,
     codec: elements.codec.value,
     crf: Number(elements.crf.value),
+    preset: elements.preset.value.trim() || null,
+    rateControl: elements.rateControl.value,
+    bitrateKbps: elements.rateControl.value === 'abr' ? numberValue(elements.bitrate) : null,
+    maxrateKbps: elements.rateControl.value === 'cvbr' ? numberValue(elements.bitrate) : null,
+    twoPass: elements.rateControl.value === 'abr' && elements.twoPass.checked,
     resizeWidth: isSideBySide ? null : numberValue(elements.resizeWidth),
     resizeHeight: isSideBySide ? null : numberValue(elements.resizeHeight),
     keepAspect: elements.keepAspect.checked,
@@ -449,6 +683,11 @@ async function startExport() {
     labelB: isSideBySide ? elements.labelB.value.trim() : '',
     audioCopy: elements.audioCopy.checked,
     stackHeight,
+    stackWidth,
+    diffGain: exportMode === 'difference' ? numberValue(elements.diffGain) : null,
+    diffHeatmap: exportMode === 'difference' && elements.diffHeatmapPreset.value !== '',
+    diffHeatmapPreset: elements.diffHeatmapPreset.value || null,
+    diffThreshold: exportMode === 'difference' ? numberValue(elements.diffThreshold) : null,
   };
 
   if (payload.resizeWidth && payload.resizeWidth % 2 !== 0) {
@@ -466,10 +705,11 @@ async function startExport() {
     const result = await tauri.core.invoke('export_video', { params: payload });
     state.exportId = result.export_id;
     state.exportOutput = result.output_path;
-    setStatus('Export started.', false);
+    const warnings = result.warnings ?? [];
+    setStatus(warnings.length ? `Export started. ${warnings.join(' ')}` : 'Export started.', false);
   } catch (err) {
     elements.cancelButton.disabled = true;
-    setStatus(String(err));
+    setStatus(errorMessage(err));
   }
 }
 
@@ -479,7 +719,7 @@ async function cancelExport() {
     await tauri.core.invoke('cancel_export', { export_id: state.exportId });
     setStatus('Export cancelled.', false);
   } catch (err) {
-    setStatus(String(err));
+    setStatus(errorMessage(err));
   } finally {
     elements.cancelButton.disabled = true;
   }
@@ -498,7 +738,7 @@ async function openOutputFolder() {
     }
     setStatus('Open folder is unavailable in this build.');
   } catch (err) {
-    setStatus(`Failed to open output folder: ${String(err)}`);
+    setStatus(`Failed to open output folder: ${errorMessage(err)}`);
   }
 }
 
@@ -510,6 +750,10 @@ function setupListeners() {
   document.querySelector('[data-action="play-both"]').addEventListener('click', playBoth);
   document.querySelector('[data-action="pause-both"]').addEventListener('click', pauseBoth);
   document.querySelector('[data-action="reset-both"]').addEventListener('click', resetBoth);
+  document.querySelector('[data-action="count-frames"]').addEventListener('click', countFrames);
+  document.querySelector('[data-action="save-frames"]').addEventListener('click', saveFrames);
+  document.querySelector('[data-action="save-report"]').addEventListener('click', saveReport);
+  document.querySelector('[data-action="clear-cache"]').addEventListener('click', clearCache);
   document.querySelector('[data-action="output-browse"]').addEventListener('click', browseOutput);
   document.querySelector('[data-action="export"]').addEventListener('click', startExport);
   elements.cancelButton.addEventListener('click', cancelExport);
@@ -568,8 +812,14 @@ function setupListeners() {
 
   if (tauri?.event?.listen) {
     tauri.event.listen('export-progress', (event) => {
-      const { export_id, progress, out_time_ms, message } = event.payload;
+      const { export_id, progress, out_time_ms, percent, speed, eta_sec, elapsed_sec, output_size_bytes, message, pass } =
+        event.payload;
       if (export_id !== state.exportId) return;
+      if (progress === 'cancelled') {
+        elements.progressText.textContent = 'Cancelled';
+        elements.cancelButton.disabled = true;
+        return;
+      }
       if (progress === 'error') {
         elements.progressText.textContent = 'Failed';
         elements.cancelButton.disabled = true;
@@ -577,20 +827,26 @@ function setupListeners() {
         setStatus(`Export failed${detail}`);
         return;
       }
-      if (out_time_ms) {
-        const seconds = out_time_ms / 1000000;
-        elements.progressText.textContent = `Processed ${seconds.toFixed(1)}s`;
-      }
       if (progress === 'end') {
         elements.progress.value = 100;
-        elements.progressText.textContent = 'Done';
+        elements.progressText.textContent = `Done in ${formatDuration(elapsed_sec)} (${formatBytes(output_size_bytes)})`;
         elements.cancelButton.disabled = true;
         elements.openOutputButton.disabled = false;
         setStatus('Export complete.', false);
+        return;
+      }
+      const parts = [];
+      if (pass) parts.push(`Pass ${pass}/2`);
+      if (percent !== null && percent !== undefined) {
+        elements.progress.value = percent;
+        parts.push(`${percent.toFixed(1)}%`);
       } else {
-        const nextValue = Math.min(95, elements.progress.value + 1);
-        elements.progress.value = nextValue;
+        elements.progress.value = Math.min(95, elements.progress.value + 1);
+        if (out_time_ms) parts.push(`Processed ${(out_time_ms / 1000000).toFixed(1)}s`);
       }
+      if (speed) parts.push(`${speed.toFixed(2)}x`);
+      if (eta_sec !== null && eta_sec !== undefined) parts.push(`ETA ${formatDuration(eta_sec)}`);
+      if (parts.length > 0) elements.progressText.textContent = parts.join(' · ');
     });
   }
 }